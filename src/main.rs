@@ -1,12 +1,41 @@
 mod rustphoto;
 
-use std::io::{Write, stdin, stdout};
+use std::io::{Read, Write, stdin, stdout};
 use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use rustphoto::compression;
-use rustphoto::image::{Image, Pixel};
+use rustphoto::config::Config;
+use rustphoto::dehaze::Dehaze;
+#[cfg(not(feature = "face-detect"))]
+use rustphoto::faces::StubFaceDetector;
+#[cfg(feature = "face-detect")]
+use rustphoto::faces::{DEFAULT_MIN_FACE_SIZE, SkinToneDetector};
+use rustphoto::faces::{FaceBlur, FaceDetector};
+use rustphoto::image::{ASCII_CHARSET, Image, Pixel, SourceColor, UNIQUE_COLORS_LIMIT};
+use rustphoto::layers::AdjustmentLayers;
+use rustphoto::lut::CubeLut;
+use rustphoto::presets;
 use rustphoto::transforms::*;
 
+// Set once at startup when running as a pipe (see `run_pipe`).
+static PIPE_MODE: AtomicBool = AtomicBool::new(false);
+
+// Command diagnostics (usage lines, parse errors) go to stdout with the rest
+// of the REPL output, and to stderr in pipe mode, where stdout carries only
+// the encoded image.
+macro_rules! message {
+    ($($arg:tt)*) => {
+        if PIPE_MODE.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
 fn cmd_load(parts: &[&str]) -> Option<Image> {
     if parts.len() < 2 {
         println!("Usage: load <path>");
@@ -18,6 +47,45 @@ fn cmd_load(parts: &[&str]) -> Option<Image> {
     match Image::load(&path) {
         Ok(img) => {
             println!("Image loaded: {}x{}", img.width, img.height);
+
+            let source = img.metadata.source_color;
+            if !matches!(source, SourceColor::Rgb | SourceColor::Rgba) {
+                println!("Source color type is {}, converted to RGB", source.name());
+            }
+
+            if img.metadata.orientation != 1 {
+                println!(
+                    "EXIF orientation is {}, run 'autoorient' to apply it",
+                    img.metadata.orientation
+                );
+            }
+
+            Some(img)
+        }
+        Err(e) => {
+            println!("Error: {}", e);
+            None
+        }
+    }
+}
+
+fn cmd_stitch(parts: &[&str]) -> Option<Image> {
+    if parts.len() < 3 {
+        println!("Usage: stitch <cols> <file1> [file2 ...]");
+        return None;
+    }
+
+    let cols = parse_number(parts[1])?;
+    let tiles = load_images(&parts[2..])?;
+
+    match Image::stitch_grid(&tiles, cols) {
+        Ok(img) => {
+            println!(
+                "Stitched {} tiles: {}x{}",
+                tiles.len(),
+                img.width,
+                img.height
+            );
             Some(img)
         }
         Err(e) => {
@@ -27,15 +95,65 @@ fn cmd_load(parts: &[&str]) -> Option<Image> {
     }
 }
 
-fn cmd_save(parts: &[&str], image: &Image) {
+fn cmd_dimensions(parts: &[&str]) {
     if parts.len() < 2 {
-        println!("Usage: save <path>");
+        println!("Usage: dimensions <path>");
         return;
     }
 
     let path = expand_path(parts[1]);
 
-    match image.save(&path) {
+    match Image::dimensions(&path) {
+        Ok((width, height)) => println!("{}x{}", width, height),
+        Err(e) => println!("Error: {}", e),
+    }
+}
+
+fn cmd_save(parts: &[&str], image: &Image, config: &Config) {
+    if parts.len() < 2 {
+        println!("Usage: save <path> [quality] [--crop] [--progressive]");
+        return;
+    }
+
+    let mut path = expand_path(parts[1]);
+    if Path::new(&path).extension().is_none() {
+        path = format!("{}.{}", path, config.default_format);
+    }
+    let lower = path.to_lowercase();
+
+    let (flags, args): (Vec<&str>, Vec<&str>) =
+        parts[2..].iter().partition(|arg| arg.starts_with("--"));
+    let progressive = flags.contains(&"--progressive");
+
+    let result = if lower.ends_with(".ico") {
+        let crop_to_square = flags.contains(&"--crop");
+        compression::save_ico(image, &path, compression::ICO_SIZES, crop_to_square)
+    } else if lower.ends_with(".jpg") || lower.ends_with(".jpeg") {
+        if progressive {
+            // The image crate's JPEG encoder only writes baseline files
+            println!("Warning: progressive JPEG is not supported, saving as baseline");
+        }
+        let quality = match args.first() {
+            Some(q) => match q.parse::<u8>() {
+                Ok(q) if (1..=100).contains(&q) => q,
+                _ => {
+                    println!("Error: quality must be 1-100");
+                    return;
+                }
+            },
+            None => config.default_quality,
+        };
+        compression::save_jpeg(image, &path, quality)
+    } else if progressive && lower.ends_with(".png") {
+        compression::save_png_interlaced(image, &path)
+    } else if progressive {
+        println!("Error: --progressive is only supported for .png");
+        return;
+    } else {
+        image.save(&path)
+    };
+
+    match result {
         Ok(_) => println!("Image saved: {}", path),
         Err(e) => println!("Error: {}", e),
     }
@@ -82,9 +200,158 @@ fn cmd_compress(parts: &[&str], image: &Image) {
     }
 }
 
-fn cmd_crop(parts: &[&str], image: &Image) -> Option<Image> {
+// Totals accumulated over one batch run.
+struct BatchReport {
+    processed: usize,
+    failed: usize,
+    input_bytes: u64,
+    output_bytes: u64,
+    elapsed: Duration,
+}
+
+impl BatchReport {
+    fn print(&self, json: bool) {
+        if json {
+            println!(
+                "{{\"processed\": {}, \"failed\": {}, \"input_bytes\": {}, \"output_bytes\": {}, \"elapsed_ms\": {}}}",
+                self.processed,
+                self.failed,
+                self.input_bytes,
+                self.output_bytes,
+                self.elapsed.as_millis()
+            );
+            return;
+        }
+
+        let delta = self.output_bytes as i64 - self.input_bytes as i64;
+        let percent = if self.input_bytes > 0 {
+            delta as f64 * 100.0 / self.input_bytes as f64
+        } else {
+            0.0
+        };
+
+        println!(
+            "Processed {}/{} files in {:.2}s",
+            self.processed,
+            self.processed + self.failed,
+            self.elapsed.as_secs_f64()
+        );
+        println!(
+            "Size: {} -> {} bytes ({:+.1}%)",
+            self.input_bytes, self.output_bytes, percent
+        );
+    }
+}
+
+fn cmd_batch(parts: &[&str], config: &Config) {
+    let json = parts.get(1) == Some(&"--json");
+    let parts = if json {
+        [&parts[..1], &parts[2..]].concat()
+    } else {
+        parts.to_vec()
+    };
+
+    if parts.len() < 4 {
+        println!("Usage: batch [--json] <pattern> <output_dir> <command...>");
+        return;
+    }
+
+    let pattern = expand_path(parts[1].trim_matches(|c| c == '\'' || c == '"'));
+    let output_dir = expand_path(parts[2]);
+
+    let Some(transform) = parse_transform(&parts[3..], config) else {
+        return;
+    };
+
+    let paths: Vec<PathBuf> = match glob::glob(&pattern) {
+        Ok(paths) => paths
+            .filter_map(Result::ok)
+            .filter(|p| p.is_file())
+            .collect(),
+        Err(e) => {
+            println!("Invalid pattern: {}", e);
+            return;
+        }
+    };
+
+    if paths.is_empty() {
+        println!("No files matched: {}", pattern);
+        return;
+    }
+
+    if let Err(e) = std::fs::create_dir_all(&output_dir) {
+        println!("Error: failed to create {}: {}", output_dir, e);
+        return;
+    }
+
+    run_batch(&paths, &output_dir, transform.as_ref(), json).print(json);
+}
+
+// Applies `transform` to every file in `paths`, writing results under
+// `output_dir` with the same file names. Failures are reported and counted
+// without stopping the run; with `quiet` (JSON output) they go to stderr
+// so stdout carries only the report.
+fn run_batch(
+    paths: &[PathBuf],
+    output_dir: &str,
+    transform: &dyn Transformation,
+    quiet: bool,
+) -> BatchReport {
+    let started = Instant::now();
+    let mut report = BatchReport {
+        processed: 0,
+        failed: 0,
+        input_bytes: 0,
+        output_bytes: 0,
+        elapsed: Duration::ZERO,
+    };
+    let report_error = |message: String| {
+        if quiet {
+            eprintln!("Error: {}", message);
+        } else {
+            println!("Error: {}", message);
+        }
+    };
+
+    for path in paths {
+        let input = path.to_string_lossy().to_string();
+        let Some(file_name) = path.file_name() else {
+            report_error(format!("{} has no file name", input));
+            report.failed += 1;
+            continue;
+        };
+        let output = Path::new(output_dir)
+            .join(file_name)
+            .to_string_lossy()
+            .to_string();
+
+        let result = Image::load(&input)
+            .and_then(|img| transform.apply(&img))
+            .and_then(|img| img.save(&output));
+
+        match result {
+            Ok(_) => {
+                if !quiet {
+                    println!("{} -> {}", input, output);
+                }
+                report.processed += 1;
+                report.input_bytes += std::fs::metadata(&input).map_or(0, |m| m.len());
+                report.output_bytes += std::fs::metadata(&output).map_or(0, |m| m.len());
+            }
+            Err(e) => {
+                report_error(e.to_string());
+                report.failed += 1;
+            }
+        }
+    }
+
+    report.elapsed = started.elapsed();
+    report
+}
+
+fn cmd_crop(parts: &[&str]) -> Option<Box<dyn Transformation>> {
     if parts.len() < 5 {
-        println!("Usage: crop <x> <y> <width> <height>");
+        message!("Usage: crop <x> <y> <width> <height>");
         return None;
     }
 
@@ -93,20 +360,12 @@ fn cmd_crop(parts: &[&str], image: &Image) -> Option<Image> {
     let width = parse_number(parts[3])?;
     let height = parse_number(parts[4])?;
 
-    let transform = Crop::new(x, y, width, height);
-
-    match transform.apply(image) {
-        Ok(result) => Some(result),
-        Err(e) => {
-            println!("Error: {}", e);
-            None
-        }
-    }
+    Some(Box::new(Crop::new(x, y, width, height)))
 }
 
-fn cmd_flip(parts: &[&str], image: &Image) -> Option<Image> {
+fn cmd_flip(parts: &[&str]) -> Option<Box<dyn Transformation>> {
     if parts.len() < 2 {
-        println!("Usage: flip <h|v>");
+        message!("Usage: flip <h|v>");
         return None;
     }
 
@@ -114,25 +373,17 @@ fn cmd_flip(parts: &[&str], image: &Image) -> Option<Image> {
         "h" => FlipAxis::Horizontal,
         "v" => FlipAxis::Vertical,
         _ => {
-            println!("Invalid axis. Use 'h' (horizontal) or 'v' (vertical)");
+            message!("Invalid axis. Use 'h' (horizontal) or 'v' (vertical)");
             return None;
         }
     };
 
-    let transform = Flip::new(axis);
-
-    match transform.apply(image) {
-        Ok(result) => Some(result),
-        Err(e) => {
-            println!("Error: {}", e);
-            None
-        }
-    }
+    Some(Box::new(Flip::new(axis)))
 }
 
-fn cmd_rotate(parts: &[&str], image: &Image) -> Option<Image> {
+fn cmd_rotate(parts: &[&str]) -> Option<Box<dyn Transformation>> {
     if parts.len() < 2 {
-        println!("Usage: rotate <90|180|270>");
+        message!("Usage: rotate <90|180|270>");
         return None;
     }
 
@@ -141,185 +392,1483 @@ fn cmd_rotate(parts: &[&str], image: &Image) -> Option<Image> {
         "180" => RotateAngle::Deg180,
         "270" => RotateAngle::Deg270,
         _ => {
-            println!("Invalid angle. Use 90, 180, or 270");
+            message!("Invalid angle. Use 90, 180, or 270");
             return None;
         }
     };
 
-    let transform = Rotate::new(angle);
+    Some(Box::new(Rotate::new(angle)))
+}
 
-    match transform.apply(image) {
-        Ok(result) => Some(result),
+fn cmd_fit(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: fit <max_width> <max_height> [--area]");
+        return None;
+    }
+
+    let max_width = parse_number(parts[1])?;
+    let max_height = parse_number(parts[2])?;
+
+    Some(Box::new(
+        Fit::new(max_width, max_height).with_sampling(parse_sampling(&parts[3..])),
+    ))
+}
+
+fn cmd_brightness(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: brightness <factor>");
+        return None;
+    }
+
+    let factor = parse_float(parts[1])?;
+    Some(Box::new(Brightness::new(factor)))
+}
+
+fn cmd_contrast(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: contrast <factor>");
+        return None;
+    }
+
+    let factor = parse_float(parts[1])?;
+    Some(Box::new(Contrast::new(factor)))
+}
+
+fn cmd_tint(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: tint <hex_color> <intensity>");
+        return None;
+    }
+
+    let hex_color = parse_hex_color(parts[1])?;
+    let intensity = parse_float(parts[2])?;
+
+    let color = Pixel::from_hex(hex_color);
+    Some(Box::new(Tint::new(color, intensity)))
+}
+
+fn cmd_colorize(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: colorize <hex_color>");
+        return None;
+    }
+
+    let hex_color = parse_hex_color(parts[1])?;
+    let color = Pixel::from_hex(hex_color);
+    Some(Box::new(Colorize::new(color)))
+}
+
+fn cmd_lightness(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: lightness <delta>");
+        return None;
+    }
+
+    let delta = parse_float(parts[1])?;
+    Some(Box::new(Lightness::new(delta)))
+}
+
+fn cmd_vibrance(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: vibrance <amount>");
+        return None;
+    }
+
+    let amount = parse_float(parts[1])?;
+    Some(Box::new(Vibrance::new(amount)))
+}
+
+fn cmd_preset(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: preset <name|list>");
+        return None;
+    }
+
+    let Some(transform) = presets::preset(parts[1]) else {
+        message!(
+            "Unknown preset. Available: {}",
+            presets::PRESET_NAMES.join(", ")
+        );
+        return None;
+    };
+
+    Some(Box::new(transform))
+}
+
+fn cmd_normalmap(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: normalmap <strength>");
+        return None;
+    }
+
+    let strength = parse_float(parts[1])?;
+    Some(Box::new(NormalMap::new(strength)))
+}
+
+fn cmd_match(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: match <reference_path>");
+        return None;
+    }
+
+    let path = expand_path(parts[1]);
+
+    let reference = match Image::load(&path) {
+        Ok(img) => img,
         Err(e) => {
-            println!("Error: {}", e);
-            None
+            message!("Error: {}", e);
+            return None;
         }
+    };
+
+    Some(Box::new(HistogramMatch::new(reference)))
+}
+
+fn cmd_sketch(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: sketch <intensity>");
+        return None;
     }
+
+    let intensity = parse_float(parts[1])?;
+    Some(Box::new(Sketch::new(intensity)))
 }
 
-fn cmd_fit(parts: &[&str], image: &Image) -> Option<Image> {
+fn cmd_oilpaint(parts: &[&str]) -> Option<Box<dyn Transformation>> {
     if parts.len() < 3 {
-        println!("Usage: fit <max_width> <max_height>");
+        message!("Usage: oilpaint <radius> <levels>");
         return None;
     }
 
-    let max_width = parse_number(parts[1])?;
-    let max_height = parse_number(parts[2])?;
+    let radius = parse_number(parts[1])?;
+    let levels = parse_number(parts[2])?.min(255) as u8;
+    Some(Box::new(OilPaint::new(radius, levels)))
+}
 
-    let transform = Fit::new(max_width, max_height);
+fn cmd_fuse(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: fuse <file1> [file2 ...]");
+        return None;
+    }
 
-    match transform.apply(image) {
-        Ok(result) => Some(result),
+    Some(Box::new(ExposureFuse::new(load_images(&parts[1..])?)))
+}
+
+fn load_images(paths: &[&str]) -> Option<Vec<Image>> {
+    let mut images = Vec::with_capacity(paths.len());
+
+    for part in paths {
+        let path = expand_path(part);
+
+        match Image::load(&path) {
+            Ok(img) => images.push(img),
+            Err(e) => {
+                message!("Error: {}", e);
+                return None;
+            }
+        }
+    }
+
+    Some(images)
+}
+
+fn cmd_replace(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: replace <from_hex> <to_hex> <tolerance> [--lab]");
+        return None;
+    }
+
+    let from = Pixel::from_hex(parse_hex_color(parts[1])?);
+    let to = Pixel::from_hex(parse_hex_color(parts[2])?);
+    let tolerance = parse_float(parts[3])?;
+
+    let distance = if parts.get(4) == Some(&"--lab") {
+        ColorDistance::Lab
+    } else {
+        ColorDistance::Rgb
+    };
+
+    Some(Box::new(ReplaceColor::new(from, to, tolerance, distance)))
+}
+
+fn cmd_lut(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: lut <path>");
+        return None;
+    }
+
+    let path = expand_path(parts[1]);
+
+    Some(Box::new(match CubeLut::load(&path) {
+        Ok(lut) => lut,
         Err(e) => {
+            message!("Error: {}", e);
+            return None;
+        }
+    }))
+}
+
+fn cmd_split(parts: &[&str], image: &Image, config: &Config) {
+    if parts.len() < 4 {
+        println!("Usage: split <cols> <rows> <path>");
+        return;
+    }
+
+    let (Some(cols), Some(rows)) = (parse_number(parts[1]), parse_number(parts[2])) else {
+        return;
+    };
+    if !(1..=image.width).contains(&cols) || !(1..=image.height).contains(&rows) {
+        println!(
+            "Error: grid must be between 1x1 and {}x{}",
+            image.width, image.height
+        );
+        return;
+    }
+
+    let path = expand_path(parts[3]);
+    let path = Path::new(&path);
+    let stem = path.with_extension("");
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or(&config.default_format);
+
+    let tiles = image.split_grid(cols, rows);
+    let tile_cols = (image.width as u32).div_ceil(tiles[0].width as u32) as usize;
+    for (i, tile) in tiles.iter().enumerate() {
+        let tile_path = format!(
+            "{}_r{}_c{}.{}",
+            stem.display(),
+            i / tile_cols,
+            i % tile_cols,
+            extension
+        );
+        if let Err(e) = tile.save(&tile_path) {
             println!("Error: {}", e);
-            None
+            return;
         }
     }
+
+    println!("Saved {} tiles", tiles.len());
 }
 
-fn cmd_invert(image: &Image) -> Option<Image> {
-    let transform = Invert::new();
+fn cmd_favicon(parts: &[&str], image: &Image) {
+    if parts.len() < 2 {
+        println!("Usage: favicon <output_dir>");
+        return;
+    }
+
+    let icons = match compression::favicon_set(image) {
+        Ok(icons) => icons,
+        Err(e) => {
+            println!("Error: {}", e);
+            return;
+        }
+    };
+
+    let output_dir = expand_path(parts[1]);
+    if let Err(e) = std::fs::create_dir_all(&output_dir) {
+        println!("Error: failed to create {}: {}", output_dir, e);
+        return;
+    }
+
+    for (name, icon) in &icons {
+        let path = Path::new(&output_dir).join(name);
+        if let Err(e) = icon.save(&path.to_string_lossy()) {
+            println!("Error: {}", e);
+            return;
+        }
+    }
+
+    println!("Saved {} favicons to {}", icons.len(), output_dir);
+}
+
+fn cmd_histimage(parts: &[&str], image: &Image) -> Option<Image> {
+    if parts.len() < 3 {
+        println!("Usage: histimage <width> <height>");
+        return None;
+    }
+
+    let width = parse_number(parts[1])?;
+    let height = parse_number(parts[2])?;
+    if width < 1 || height < 1 {
+        println!("Error: width and height must be positive");
+        return None;
+    }
+
+    Some(image.histogram_image(width, height))
+}
+
+fn cmd_components(parts: &[&str], image: &Image) -> Option<Image> {
+    if parts.len() < 3 {
+        println!("Usage: components <background_hex> <tolerance> [--draw]");
+        return None;
+    }
+
+    let background = Pixel::from_hex(parse_hex_color(parts[1])?);
+    let tolerance = parse_float(parts[2])?;
+
+    let boxes = image.connected_components(background, tolerance);
+    println!("Found {} components", boxes.len());
+
+    for (i, bbox) in boxes.iter().enumerate() {
+        println!(
+            "  #{}: {}x{} at ({}, {}), {} pixels",
+            i + 1,
+            bbox.width,
+            bbox.height,
+            bbox.x,
+            bbox.y,
+            bbox.pixel_count
+        );
+    }
+
+    if parts.get(3) != Some(&"--draw") {
+        return None;
+    }
+
+    let transform = DrawBoxes::new(boxes, Pixel::new(255, 0, 0));
+    apply_transform(&transform, image)
+}
+
+fn cmd_erode(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: erode <radius>");
+        return None;
+    }
+
+    let radius = parse_number(parts[1])?;
+    Some(Box::new(Erode::new(radius)))
+}
+
+fn cmd_dilate(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: dilate <radius>");
+        return None;
+    }
+
+    let radius = parse_number(parts[1])?;
+    Some(Box::new(Dilate::new(radius)))
+}
+
+fn cmd_open(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: open <radius>");
+        return None;
+    }
+
+    let radius = parse_number(parts[1])?;
+    Some(Box::new(Open::new(radius)))
+}
+
+fn cmd_close(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: close <radius>");
+        return None;
+    }
+
+    let radius = parse_number(parts[1])?;
+    Some(Box::new(Close::new(radius)))
+}
+
+fn cmd_bayer(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: bayer <levels> <matrix_size>");
+        return None;
+    }
+
+    let levels = parse_number(parts[1])?.min(255) as u8;
+    let matrix_size = parse_number(parts[2])?.min(255) as u8;
+
+    Some(Box::new(BayerDither::new(levels, matrix_size)))
+}
+
+fn cmd_gradient(parts: &[&str], config: &Config) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 5 {
+        message!("Usage: gradient <start_hex> <end_hex> <h|v|radial> <opacity>");
+        return None;
+    }
+
+    let start = Pixel::from_hex(parse_hex_color(parts[1])?);
+    let end = Pixel::from_hex(parse_hex_color(parts[2])?);
+
+    let kind = match parts[3] {
+        "h" => GradientKind::LinearHorizontal,
+        "v" => GradientKind::LinearVertical,
+        "radial" => GradientKind::Radial,
+        _ => {
+            message!("Invalid gradient kind. Use 'h', 'v', or 'radial'");
+            return None;
+        }
+    };
+
+    let opacity = parse_float(parts[4])?;
+
+    Some(Box::new(
+        GradientOverlay::new(start, end, kind, opacity).with_space(config.composite_space),
+    ))
+}
+
+fn cmd_seamless(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: seamless <blend>");
+        return None;
+    }
+
+    let blend = parse_number(parts[1])?;
+
+    Some(Box::new(MakeSeamless::new(blend)))
+}
+
+fn cmd_region(parts: &[&str], config: &Config) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 6 {
+        message!("Usage: region <x> <y> <width> <height> <command...>");
+        return None;
+    }
+
+    let x = parse_number(parts[1])?;
+    let y = parse_number(parts[2])?;
+    let width = parse_number(parts[3])?;
+    let height = parse_number(parts[4])?;
+    let inner = parse_transform(&parts[5..], config)?;
+
+    Some(Box::new(SubRegion::new(x, y, width, height, inner)))
+}
+
+fn cmd_perspective(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 11 {
+        message!(
+            "Usage: perspective <x1> <y1> <x2> <y2> <x3> <y3> <x4> <y4> <out_w> <out_h> [--supersample N]"
+        );
+        return None;
+    }
+
+    let mut corners = [(0.0, 0.0); 4];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        *corner = (
+            parse_float(parts[1 + 2 * i])?,
+            parse_float(parts[2 + 2 * i])?,
+        );
+    }
+
+    let width = parse_number(parts[9])?;
+    let height = parse_number(parts[10])?;
+
+    let supersample = parse_supersample(&parts[11..])?;
+
+    Some(Box::new(
+        Perspective::new(corners, width, height).with_supersample(supersample),
+    ))
+}
+
+fn cmd_chromaberr(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: chromaberr <shift>");
+        return None;
+    }
+
+    let shift = parse_number(parts[1])?;
+
+    Some(Box::new(ChromaticAberration::new(shift)))
+}
+
+fn cmd_glitch(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: glitch <intensity> <seed>");
+        return None;
+    }
+
+    let intensity = parse_float(parts[1])?;
+    let seed = parse_seed(parts[2])?;
+
+    Some(Box::new(Glitch::new(intensity, seed)))
+}
+
+fn cmd_colorblind(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: colorblind <protanopia|deuteranopia|tritanopia>");
+        return None;
+    }
+
+    let kind = match parts[1] {
+        "protanopia" => CbKind::Protanopia,
+        "deuteranopia" => CbKind::Deuteranopia,
+        "tritanopia" => CbKind::Tritanopia,
+        _ => {
+            message!("Invalid kind. Use protanopia, deuteranopia, or tritanopia");
+            return None;
+        }
+    };
+
+    Some(Box::new(ColorBlind::new(kind)))
+}
+
+fn cmd_redeye(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: redeye <x> <y> <radius>");
+        return None;
+    }
+
+    let x = parse_number(parts[1])?;
+    let y = parse_number(parts[2])?;
+    let radius = parse_number(parts[3])?;
+
+    Some(Box::new(RedEye::new(x, y, radius)))
+}
+
+fn cmd_grain(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: grain <amount> <size> <seed>");
+        return None;
+    }
+
+    let amount = parse_float(parts[1])?;
+    let size = parse_float(parts[2])?;
+    let seed = parse_seed(parts[3])?;
+
+    Some(Box::new(FilmGrain::new(amount, size, seed)))
+}
+
+fn cmd_heatmap(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: heatmap <jet|viridis|grayscale|inferno>");
+        return None;
+    }
+
+    let palette = match parts[1] {
+        "jet" => HeatmapPalette::Jet,
+        "viridis" => HeatmapPalette::Viridis,
+        "grayscale" => HeatmapPalette::Grayscale,
+        "inferno" => HeatmapPalette::Inferno,
+        _ => {
+            message!("Invalid palette. Use jet, viridis, grayscale, or inferno");
+            return None;
+        }
+    };
+
+    Some(Box::new(Heatmap::new(palette)))
+}
+
+fn cmd_resize(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: resize <width> <height> [--area]");
+        return None;
+    }
+
+    let width = parse_number(parts[1])?;
+    let height = parse_number(parts[2])?;
+
+    Some(Box::new(
+        Resize::new(width, height).with_sampling(parse_sampling(&parts[3..])),
+    ))
+}
+
+fn cmd_letterbox(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: letterbox <width> <height> <hex_color> [--area]");
+        return None;
+    }
+
+    let width = parse_number(parts[1])?;
+    let height = parse_number(parts[2])?;
+    let fill = Pixel::from_hex(parse_hex_color(parts[3])?);
+
+    Some(Box::new(
+        Resize::letterboxed(width, height, fill).with_sampling(parse_sampling(&parts[4..])),
+    ))
+}
+
+fn cmd_autolevels(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: autolevels <clip> [--luma]");
+        return None;
+    }
+
+    let clip = parse_float(parts[1])?;
+    let per_channel = parts.get(2) != Some(&"--luma");
+
+    Some(Box::new(AutoLevels::new(per_channel, clip)))
+}
+
+fn cmd_embed(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: embed <text...>");
+        return None;
+    }
+
+    Some(Box::new(EmbedMessage::new(parts[1..].join(" "))))
+}
+
+fn cmd_blur(parts: &[&str], config: &Config) -> Option<Box<dyn Transformation>> {
+    let radius = match parts.get(1) {
+        Some(radius) => Some(parse_number(radius)?),
+        None => config.default_blur_radius,
+    };
+
+    match radius {
+        Some(radius) => Some(Box::new(GaussianBlur::with_radius(radius))),
+        None => Some(Box::new(GaussianBlur::new())),
+    }
+}
+
+fn cmd_blurfill(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: blurfill <width> <height> <radius>");
+        return None;
+    }
+
+    let width = parse_number(parts[1])?;
+    let height = parse_number(parts[2])?;
+    let radius = parse_number(parts[3])?;
+
+    Some(Box::new(BlurFill::new(width, height, radius)))
+}
+
+fn cmd_autostraighten(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: autostraighten <max_angle> [--supersample N]");
+        return None;
+    }
+
+    let max_angle = parse_float(parts[1])?;
+
+    let supersample = parse_supersample(&parts[2..])?;
+
+    Some(Box::new(
+        AutoStraighten::new(max_angle).with_supersample(supersample),
+    ))
+}
+
+fn cmd_channelconvolve(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: channelconvolve <r_kernel> <g_kernel> <b_kernel>");
+        message!("Each kernel is 'identity' or comma-separated values, e.g. 0,-1,0,-1,5,-1,0,-1,0");
+        return None;
+    }
+
+    let builder = PerChannelConvolve::builder()
+        .red(parse_kernel(parts[1])?)
+        .green(parse_kernel(parts[2])?)
+        .blue(parse_kernel(parts[3])?);
+
+    Some(Box::new(builder.build()))
+}
+
+fn cmd_filmneg(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: filmneg <base_hex|auto>");
+        return None;
+    }
+
+    if parts[1] == "auto" {
+        return Some(Box::new(FilmNegative::auto()));
+    }
+
+    let base = Pixel::from_hex(parse_hex_color(parts[1])?);
+
+    Some(Box::new(FilmNegative::new(base)))
+}
+
+fn cmd_selective(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 5 {
+        message!("Usage: selective <hue> <range> <sat_delta> <light_delta>");
+        return None;
+    }
+
+    let hue = parse_float(parts[1])?;
+    let range = parse_float(parts[2])?;
+    let sat_delta = parse_float(parts[3])?;
+    let light_delta = parse_float(parts[4])?;
+
+    Some(Box::new(SelectiveColor::new(
+        hue,
+        range,
+        sat_delta,
+        light_delta,
+    )))
+}
+
+fn cmd_dog(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: dog <sigma1> <sigma2> <threshold>");
+        return None;
+    }
+
+    let sigma1 = parse_float(parts[1])?;
+    let sigma2 = parse_float(parts[2])?;
+    let threshold = parse_float(parts[3])?;
+
+    Some(Box::new(DoG::new(sigma1, sigma2, threshold)))
+}
+
+fn cmd_cartoon(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: cartoon <levels> <edge_threshold>");
+        return None;
+    }
+
+    let levels = parse_number(parts[1])?.min(255) as u8;
+    let edge_threshold = parse_float(parts[2])?;
+
+    Some(Box::new(Cartoon::new(levels, edge_threshold)))
+}
+
+fn cmd_tinyplanet(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: tinyplanet <size> [--supersample N]");
+        return None;
+    }
+
+    let size = parse_number(parts[1])?;
+
+    let supersample = parse_supersample(&parts[2..])?;
+
+    Some(Box::new(
+        TinyPlanet::new(size).with_supersample(supersample),
+    ))
+}
+
+fn cmd_scanlines(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: scanlines <intensity> <spacing> [--rgb]");
+        return None;
+    }
+
+    let intensity = parse_float(parts[1])?;
+    let spacing = parse_number(parts[2])?;
+    let scanlines = Scanlines::new(intensity, spacing);
+
+    if parts.get(3) == Some(&"--rgb") {
+        Some(Box::new(scanlines.with_subpixel()))
+    } else {
+        Some(Box::new(scanlines))
+    }
+}
+
+fn cmd_ascii(parts: &[&str], image: &Image) {
+    if parts.len() < 2 {
+        println!("Usage: ascii <cols> [charset]");
+        return;
+    }
+
+    let Some(cols) = parse_number(parts[1]) else {
+        return;
+    };
+    if cols == 0 {
+        println!("Error: cols must be positive");
+        return;
+    }
+
+    let charset = parts.get(2).copied().unwrap_or(ASCII_CHARSET);
+    print!("{}", image.to_ascii(cols, charset));
+}
+
+fn cmd_diffmap(parts: &[&str], config: &Config) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: diffmap <path> <scale>");
+        return None;
+    }
+
+    let path = expand_path(parts[1]);
+    let scale = parse_float(parts[2])?;
+
+    let other = match Image::load(&path) {
+        Ok(img) => img,
+        Err(e) => {
+            message!("Error: {}", e);
+            return None;
+        }
+    };
+
+    Some(Box::new(
+        DiffHeatmap::new(other, scale).with_space(config.composite_space),
+    ))
+}
+
+fn cmd_lightleak(parts: &[&str], config: &Config) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: lightleak <hex_color> <tl|tr|bl|br|t|b|l|r> <intensity> [seed]");
+        return None;
+    }
+
+    let color = Pixel::from_hex(parse_hex_color(parts[1])?);
+
+    let position = match parts[2] {
+        "tl" => LeakPosition::TopLeft,
+        "tr" => LeakPosition::TopRight,
+        "bl" => LeakPosition::BottomLeft,
+        "br" => LeakPosition::BottomRight,
+        "t" => LeakPosition::Top,
+        "b" => LeakPosition::Bottom,
+        "l" => LeakPosition::Left,
+        "r" => LeakPosition::Right,
+        _ => {
+            message!("Invalid position. Use a corner (tl, tr, bl, br) or an edge (t, b, l, r)");
+            return None;
+        }
+    };
+
+    let intensity = parse_float(parts[3])?;
+    let seed = match parts.get(4) {
+        Some(seed) => parse_seed(seed)?,
+        None => 0,
+    };
+
+    Some(Box::new(
+        LightLeak::new(color, position, intensity, seed).with_space(config.composite_space),
+    ))
+}
+
+fn cmd_faceblur(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: faceblur <radius> [min_size]");
+        return None;
+    }
+
+    let radius = parse_number(parts[1])?;
+    let detector = face_detector(parts.get(2).copied())?;
+
+    Some(Box::new(FaceBlur::new(radius, detector)))
+}
+
+#[cfg(feature = "face-detect")]
+fn face_detector(min_size: Option<&str>) -> Option<Box<dyn FaceDetector>> {
+    let min_size = match min_size {
+        Some(size) => parse_number(size)?,
+        None => DEFAULT_MIN_FACE_SIZE,
+    };
+
+    Some(Box::new(SkinToneDetector::new(min_size)))
+}
+
+#[cfg(not(feature = "face-detect"))]
+fn face_detector(_min_size: Option<&str>) -> Option<Box<dyn FaceDetector>> {
+    message!("Note: built without face-detect, so no regions will be blurred");
+    Some(Box::new(StubFaceDetector))
+}
+
+fn cmd_lenscorrect(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: lenscorrect <k1> <k2> [--supersample N]");
+        return None;
+    }
+
+    let k1 = parse_float(parts[1])?;
+    let k2 = parse_float(parts[2])?;
+
+    let supersample = parse_supersample(&parts[3..])?;
+
+    Some(Box::new(
+        LensCorrect::new(k1, k2).with_supersample(supersample),
+    ))
+}
+
+fn cmd_focuspeaking(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: focuspeaking <hex_color> <threshold>");
+        return None;
+    }
+
+    let color = Pixel::from_hex(parse_hex_color(parts[1])?);
+    let threshold = parse_float(parts[2])?;
+
+    Some(Box::new(FocusPeaking::new(color, threshold)))
+}
+
+fn cmd_spotlight(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 6 {
+        message!("Usage: spotlight <x> <y> <radius> <intensity> <falloff>");
+        return None;
+    }
+
+    let x = parse_number(parts[1])?;
+    let y = parse_number(parts[2])?;
+    let radius = parse_float(parts[3])?;
+    let intensity = parse_float(parts[4])?;
+    let falloff = parse_float(parts[5])?;
+
+    Some(Box::new(Spotlight::new(x, y, radius, intensity, falloff)))
+}
+
+fn cmd_grid(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: grid <spacing> <hex_color>");
+        return None;
+    }
+
+    let spacing = parse_number(parts[1])?;
+    if spacing < 1 {
+        message!("Error: spacing must be at least 1");
+        return None;
+    }
+    let color = Pixel::from_hex(parse_hex_color(parts[2])?);
+
+    Some(Box::new(MapPixels::new(move |x, y, p| {
+        if x % spacing == 0 || y % spacing == 0 {
+            color
+        } else {
+            p
+        }
+    })))
+}
+
+fn cmd_adjust(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: adjust <step> [step ...]");
+        message!("Steps: brightness <factor>, contrast <factor>, invert");
+        return None;
+    }
+
+    let mut stack = AdjustmentStack::new();
+    let mut args = parts[1..].iter();
+    while let Some(&step) = args.next() {
+        stack = match step {
+            "brightness" => stack.brightness(parse_float(args.next().unwrap_or(&""))?),
+            "contrast" => stack.contrast(parse_float(args.next().unwrap_or(&""))?),
+            "invert" => stack.invert(),
+            _ => {
+                message!("Unknown adjustment: {}", step);
+                return None;
+            }
+        };
+    }
+
+    Some(Box::new(stack.build()))
+}
+
+fn cmd_avgstack(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: avgstack <file1> [file2 ...]");
+        return None;
+    }
+
+    Some(Box::new(AverageStack::new(load_images(&parts[1..])?)))
+}
+
+fn cmd_maxstack(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: maxstack <file1> [file2 ...]");
+        return None;
+    }
+
+    Some(Box::new(MaxStack::new(load_images(&parts[1..])?)))
+}
+
+fn cmd_kaleidoscope(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: kaleidoscope <segments> [--supersample N]");
+        return None;
+    }
+
+    let segments = parse_number(parts[1])?;
+    let supersample = parse_supersample(&parts[2..])?;
+
+    Some(Box::new(
+        Kaleidoscope::new(segments).with_supersample(supersample),
+    ))
+}
+
+fn cmd_keystone(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: keystone <top_inset> <bottom_inset> <fill_hex> [--supersample N]");
+        return None;
+    }
+
+    let top_inset = parse_number(parts[1])?;
+    let bottom_inset = parse_number(parts[2])?;
+    let fill = Pixel::from_hex(parse_hex_color(parts[3])?);
+
+    let supersample = parse_supersample(&parts[4..])?;
+
+    Some(Box::new(
+        Keystone::new(top_inset, bottom_inset, fill).with_supersample(supersample),
+    ))
+}
+
+fn cmd_repeat(parts: &[&str], config: &Config) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: repeat <n> <command...>");
+        return None;
+    }
+
+    let times = parse_number(parts[1])?;
+    let inner = parse_transform(&parts[2..], config)?;
+
+    Some(Box::new(Repeat::new(inner, times)))
+}
+
+fn cmd_lumasharpen(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: lumasharpen <amount> <radius>");
+        return None;
+    }
+
+    let amount = parse_float(parts[1])?;
+    let radius = parse_number(parts[2])?;
+
+    Some(Box::new(LumaSharpen::new(amount, radius)))
+}
+
+fn cmd_tonemap(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: tonemap <key>");
+        return None;
+    }
+
+    let key = parse_float(parts[1])?;
+
+    Some(Box::new(ToneMapReinhard::new(key)))
+}
+
+fn cmd_temperature(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: temperature <kelvin> <tint>");
+        return None;
+    }
+
+    let kelvin = parse_float(parts[1])?;
+    let tint = parse_float(parts[2])?;
+
+    Some(Box::new(Temperature::new(kelvin, tint)))
+}
+
+fn cmd_mirrorpad(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: mirrorpad <pad>");
+        return None;
+    }
+
+    let pad = parse_number(parts[1])?;
+
+    Some(Box::new(MirrorPad::new(pad)))
+}
+
+fn cmd_zoomblur(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: zoomblur <x> <y> <strength>");
+        return None;
+    }
+
+    let x = parse_number(parts[1])?;
+    let y = parse_number(parts[2])?;
+    let strength = parse_float(parts[3])?;
+
+    Some(Box::new(ZoomBlur::new(x, y, strength)))
+}
+
+fn cmd_dehaze(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 2 {
+        message!("Usage: dehaze <strength>");
+        return None;
+    }
+
+    let strength = parse_float(parts[1])?;
+
+    Some(Box::new(Dehaze::new(strength)))
+}
+
+fn cmd_halftone(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: halftone <dot_size> <angle>");
+        return None;
+    }
+
+    let dot_size = parse_number(parts[1])?;
+    let angle = parse_float(parts[2])?;
+
+    Some(Box::new(Halftone::new(dot_size, angle)))
+}
+
+fn cmd_relief(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: relief <azimuth> <elevation> <depth>");
+        return None;
+    }
+
+    let azimuth = parse_float(parts[1])?;
+    let elevation = parse_float(parts[2])?;
+    let depth = parse_float(parts[3])?;
+
+    Some(Box::new(Relief::new(azimuth, elevation, depth)))
+}
+
+fn cmd_caption(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 5 {
+        message!("Usage: caption <text...> <height> <bg_hex> <fg_hex>");
+        return None;
+    }
+
+    let n = parts.len();
+    let text = parts[1..n - 3].join(" ");
+    let height = parse_number(parts[n - 3])?;
+    let bg = Pixel::from_hex(parse_hex_color(parts[n - 2])?);
+    let fg = Pixel::from_hex(parse_hex_color(parts[n - 1])?);
+
+    Some(Box::new(CaptionBar::new(text, height, bg, fg)))
+}
+
+fn cmd_pixelsort(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: pixelsort <threshold> <h|v>");
+        return None;
+    }
 
-    match transform.apply(image) {
-        Ok(result) => Some(result),
-        Err(e) => {
-            println!("Error: {}", e);
-            None
+    let threshold = parse_float(parts[1])?;
+    let direction = match parts[2] {
+        "h" => SortDirection::Horizontal,
+        "v" => SortDirection::Vertical,
+        _ => {
+            message!("Invalid direction. Use 'h' (rows) or 'v' (columns)");
+            return None;
         }
-    }
+    };
+
+    Some(Box::new(PixelSort::new(threshold, direction)))
 }
 
-fn cmd_grayscale(image: &Image) -> Option<Image> {
-    let transform = Grayscale::new();
+fn cmd_gradientmap(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 3 {
+        message!("Usage: gradientmap <pos:hex> <pos:hex> [pos:hex...]");
+        return None;
+    }
 
-    match transform.apply(image) {
-        Ok(result) => Some(result),
-        Err(e) => {
-            println!("Error: {}", e);
-            None
-        }
+    let mut stops = Vec::with_capacity(parts.len() - 1);
+    for stop in &parts[1..] {
+        let Some((pos, hex)) = stop.split_once(':') else {
+            message!(
+                "Invalid stop '{}'. Use <position>:<hex>, e.g. 0.5:ff8800",
+                stop
+            );
+            return None;
+        };
+        let color = Pixel::from_hex(parse_hex_color(hex)?);
+        stops.push((parse_float(pos)?, color));
     }
+
+    Some(Box::new(GradientMap::new(stops)))
 }
 
-fn cmd_brightness(parts: &[&str], image: &Image) -> Option<Image> {
+fn cmd_chromablur(parts: &[&str]) -> Option<Box<dyn Transformation>> {
     if parts.len() < 2 {
-        println!("Usage: brightness <factor>");
+        message!("Usage: chromablur <radius>");
         return None;
     }
 
-    let factor = parse_float(parts[1])?;
-    let transform = Brightness::new(factor);
+    let radius = parse_number(parts[1])?;
 
-    match transform.apply(image) {
-        Ok(result) => Some(result),
-        Err(e) => {
-            println!("Error: {}", e);
-            None
-        }
-    }
+    Some(Box::new(ChromaBlur::new(radius)))
 }
 
-fn cmd_contrast(parts: &[&str], image: &Image) -> Option<Image> {
-    if parts.len() < 2 {
-        println!("Usage: contrast <factor>");
+fn cmd_masked(parts: &[&str], config: &Config) -> Option<Box<dyn Transformation>> {
+    let separator = parts.iter().position(|&p| p == "/");
+    let Some(separator) = separator.filter(|&i| i > 1 && i + 1 < parts.len()) else {
+        message!("Usage: masked <mask command...> / <effect command...>");
+        return None;
+    };
+
+    let mask_source = parse_transform(&parts[1..separator], config)?;
+    let effect = parse_transform(&parts[separator + 1..], config)?;
+
+    Some(Box::new(
+        MaskedBy::new(mask_source, effect).with_space(config.composite_space),
+    ))
+}
+
+fn cmd_depthblur(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: depthblur <depth_path> <focus> <max_radius>");
         return None;
     }
 
-    let factor = parse_float(parts[1])?;
-    let transform = Contrast::new(factor);
+    let path = expand_path(parts[1]);
+    let focus = parse_float(parts[2])?;
+    let max_radius = parse_number(parts[3])?;
 
-    match transform.apply(image) {
-        Ok(result) => Some(result),
+    let depth_map = match Image::load(&path) {
+        Ok(img) => img,
         Err(e) => {
-            println!("Error: {}", e);
-            None
+            message!("Error: {}", e);
+            return None;
         }
-    }
+    };
+
+    Some(Box::new(DepthBlur::new(depth_map, focus, max_radius)))
 }
 
-fn cmd_tint(parts: &[&str], image: &Image) -> Option<Image> {
+fn cmd_displace(parts: &[&str]) -> Option<Box<dyn Transformation>> {
     if parts.len() < 3 {
-        println!("Usage: tint <hex_color> <intensity>");
+        message!("Usage: displace <map_path> <scale> [--supersample N]");
         return None;
     }
 
-    let hex_color = parse_hex_color(parts[1])?;
-    let intensity = parse_float(parts[2])?;
-
-    let color = Pixel::from_hex(hex_color);
-    let transform = Tint::new(color, intensity);
+    let path = expand_path(parts[1]);
+    let scale = parse_float(parts[2])?;
 
-    match transform.apply(image) {
-        Ok(result) => Some(result),
+    let map = match Image::load(&path) {
+        Ok(img) => img,
         Err(e) => {
-            println!("Error: {}", e);
-            None
+            message!("Error: {}", e);
+            return None;
         }
+    };
+
+    let supersample = parse_supersample(&parts[3..])?;
+
+    Some(Box::new(
+        DisplacementMap::new(map, scale).with_supersample(supersample),
+    ))
+}
+
+fn cmd_shapevignette(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: shapevignette <circle|ellipse|rect> <feather> <darkness>");
+        return None;
     }
+
+    let shape = match parts[1] {
+        "circle" => VignetteShape::Circle,
+        "ellipse" => VignetteShape::Ellipse,
+        "rect" => VignetteShape::Rectangle,
+        _ => {
+            message!("Invalid shape. Use circle, ellipse, or rect");
+            return None;
+        }
+    };
+    let feather = parse_number(parts[2])?;
+    let darkness = parse_float(parts[3])?;
+
+    Some(Box::new(ShapeVignette::new(shape, feather, darkness)))
 }
 
-fn cmd_colorize(parts: &[&str], image: &Image) -> Option<Image> {
-    if parts.len() < 2 {
-        println!("Usage: colorize <hex_color>");
+fn cmd_tiltshift(parts: &[&str]) -> Option<Box<dyn Transformation>> {
+    if parts.len() < 4 {
+        message!("Usage: tiltshift <focus_y> <focus_height> <max_blur> [vibrance]");
         return None;
     }
 
-    let hex_color = parse_hex_color(parts[1])?;
-    let color = Pixel::from_hex(hex_color);
-    let transform = Colorize::new(color);
+    let focus_y = parse_number(parts[1])?;
+    let focus_height = parse_number(parts[2])?;
+    let max_blur = parse_number(parts[3])?;
+    let vibrance = match parts.get(4) {
+        Some(vibrance) => parse_float(vibrance)?,
+        None => 0.0,
+    };
 
-    match transform.apply(image) {
-        Ok(result) => Some(result),
-        Err(e) => {
-            println!("Error: {}", e);
+    Some(Box::new(
+        TiltShift::new(focus_y, focus_height, max_blur).with_vibrance(vibrance),
+    ))
+}
+
+fn parse_transform(parts: &[&str], config: &Config) -> Option<Box<dyn Transformation>> {
+    match parts[0] {
+        "crop" => cmd_crop(parts),
+        "flip" => cmd_flip(parts),
+        "rotate" => cmd_rotate(parts),
+        "fit" => cmd_fit(parts),
+        "invert" => Some(Box::new(Invert::new())),
+        "grayscale" => Some(Box::new(Grayscale::new())),
+        "brightness" => cmd_brightness(parts),
+        "contrast" => cmd_contrast(parts),
+        "tint" => cmd_tint(parts),
+        "colorize" => cmd_colorize(parts),
+        "lightness" => cmd_lightness(parts),
+        "vibrance" => cmd_vibrance(parts),
+        "preset" => cmd_preset(parts),
+        "blur" => cmd_blur(parts, config),
+        "sharpen" => Some(Box::new(Sharpen::new())),
+        "edge" => Some(Box::new(EdgeDetect::new())),
+        "emboss" => Some(Box::new(Emboss::new())),
+        "normalmap" => cmd_normalmap(parts),
+        "match" => cmd_match(parts),
+        "sketch" => cmd_sketch(parts),
+        "oilpaint" => cmd_oilpaint(parts),
+        "fuse" => cmd_fuse(parts),
+        "replace" => cmd_replace(parts),
+        "lut" => cmd_lut(parts),
+        "erode" => cmd_erode(parts),
+        "dilate" => cmd_dilate(parts),
+        "open" => cmd_open(parts),
+        "close" => cmd_close(parts),
+        "bayer" => cmd_bayer(parts),
+        "gradient" => cmd_gradient(parts, config),
+        "autoorient" => Some(Box::new(AutoOrient::new())),
+        "seamless" => cmd_seamless(parts),
+        "region" => cmd_region(parts, config),
+        "repeat" => cmd_repeat(parts, config),
+        "perspective" => cmd_perspective(parts),
+        "chromaberr" => cmd_chromaberr(parts),
+        "glitch" => cmd_glitch(parts),
+        "colorblind" => cmd_colorblind(parts),
+        "redeye" => cmd_redeye(parts),
+        "grain" => cmd_grain(parts),
+        "heatmap" => cmd_heatmap(parts),
+        "resize" => cmd_resize(parts),
+        "letterbox" => cmd_letterbox(parts),
+        "autolevels" => cmd_autolevels(parts),
+        "embed" => cmd_embed(parts),
+        "blurfill" => cmd_blurfill(parts),
+        "autostraighten" => cmd_autostraighten(parts),
+        "channelconvolve" => cmd_channelconvolve(parts),
+        "filmneg" => cmd_filmneg(parts),
+        "selective" => cmd_selective(parts),
+        "dog" => cmd_dog(parts),
+        "cartoon" => cmd_cartoon(parts),
+        "tinyplanet" => cmd_tinyplanet(parts),
+        "scanlines" => cmd_scanlines(parts),
+        "diffmap" => cmd_diffmap(parts, config),
+        "lightleak" => cmd_lightleak(parts, config),
+        "faceblur" => cmd_faceblur(parts),
+        "lenscorrect" => cmd_lenscorrect(parts),
+        "focuspeaking" => cmd_focuspeaking(parts),
+        "spotlight" => cmd_spotlight(parts),
+        "grid" => cmd_grid(parts),
+        "adjust" => cmd_adjust(parts),
+        "avgstack" => cmd_avgstack(parts),
+        "maxstack" => cmd_maxstack(parts),
+        "kaleidoscope" => cmd_kaleidoscope(parts),
+        "keystone" => cmd_keystone(parts),
+        "lumasharpen" => cmd_lumasharpen(parts),
+        "tonemap" => cmd_tonemap(parts),
+        "temperature" => cmd_temperature(parts),
+        "mirrorpad" => cmd_mirrorpad(parts),
+        "zoomblur" => cmd_zoomblur(parts),
+        "dehaze" => cmd_dehaze(parts),
+        "halftone" => cmd_halftone(parts),
+        "relief" => cmd_relief(parts),
+        "caption" => cmd_caption(parts),
+        "pixelsort" => cmd_pixelsort(parts),
+        "gradientmap" => cmd_gradientmap(parts),
+        "chromablur" => cmd_chromablur(parts),
+        "masked" => cmd_masked(parts, config),
+        "zones" => Some(Box::new(ZoneOverlay::new())),
+        "depthblur" => cmd_depthblur(parts),
+        "displace" => cmd_displace(parts),
+        "shapevignette" => cmd_shapevignette(parts),
+        "tiltshift" => cmd_tiltshift(parts),
+        _ => {
+            message!("Unknown command: {}", parts.join(" "));
             None
         }
     }
 }
 
-fn cmd_blur(image: &Image) -> Option<Image> {
-    let transform = GaussianBlur::new();
+// Adjustment layers over the image as it was when the first layer was added.
+// The current image is always `stack` rendered over `base`.
+struct LayerSession {
+    base: Image,
+    stack: AdjustmentLayers,
+}
 
-    match transform.apply(image) {
-        Ok(result) => Some(result),
-        Err(e) => {
-            println!("Error: {}", e);
-            None
-        }
+// Any edit outside the stack works on the rendered image, so the layers are
+// baked in from then on and the session ends.
+fn flatten_layers(layers: &mut Option<LayerSession>) {
+    if let Some(session) = layers.take()
+        && !session.stack.is_empty()
+    {
+        println!("Flattened {} layer(s)", session.stack.len());
     }
 }
 
-fn cmd_sharpen(image: &Image) -> Option<Image> {
-    let transform = Sharpen::new();
-
-    match transform.apply(image) {
-        Ok(result) => Some(result),
-        Err(e) => {
-            println!("Error: {}", e);
+fn cmd_layer(
+    parts: &[&str],
+    config: &Config,
+    current_image: &mut Option<Image>,
+    previous_image: &mut Option<Image>,
+    layers: &mut Option<LayerSession>,
+) {
+    let usage = "Usage: layer add <command...> | toggle <n> | move <from> <to> | remove <n> | list";
+    let position = |s: &str| match parse_number(s)? {
+        0 => {
+            println!("Error: layers are numbered from 1");
             None
         }
+        n => Some(n as usize - 1),
+    };
+
+    let action = parts.get(1).copied().unwrap_or("");
+    if action == "list" {
+        match layers {
+            Some(session) if !session.stack.is_empty() => {
+                for (i, (name, enabled)) in session.stack.iter().enumerate() {
+                    let mark = if enabled { "x" } else { " " };
+                    println!("{}. [{}] {}", i + 1, mark, name);
+                }
+            }
+            _ => println!("No layers"),
+        }
+        return;
     }
-}
 
-fn cmd_edge(image: &Image) -> Option<Image> {
-    let transform = EdgeDetect::new();
+    let session = match layers {
+        Some(session) => session,
+        None => {
+            // The first layer turns the current image into the base
+            let Some(base) = current_image.take() else {
+                return;
+            };
+            let stack = AdjustmentLayers::new();
+            *current_image = stack.render(&base).ok();
+            layers.insert(LayerSession { base, stack })
+        }
+    };
 
-    match transform.apply(image) {
-        Ok(result) => Some(result),
+    let edited = match (action, parts.len()) {
+        ("add", 3..) => {
+            let Some(transform) = parse_transform(&parts[2..], config) else {
+                return;
+            };
+            session.stack.add(parts[2..].join(" "), transform);
+            Ok(())
+        }
+        ("toggle", 3..) => {
+            let Some(index) = position(parts[2]) else {
+                return;
+            };
+            session.stack.toggle(index).map(|_| ())
+        }
+        ("move", 4..) => {
+            let (Some(from), Some(to)) = (position(parts[2]), position(parts[3])) else {
+                return;
+            };
+            session.stack.move_layer(from, to)
+        }
+        ("remove", 3..) => {
+            let Some(index) = position(parts[2]) else {
+                return;
+            };
+            session.stack.remove(index)
+        }
+        _ => {
+            println!("{}", usage);
+            return;
+        }
+    };
+
+    if let Err(e) = edited {
+        println!("Error: {}", e);
+        return;
+    }
+
+    match session.stack.render(&session.base) {
+        Ok(result) => {
+            *previous_image = current_image.take();
+            *current_image = Some(result);
+        }
         Err(e) => {
             println!("Error: {}", e);
-            None
+            // A layer that cannot be applied is not kept
+            if action == "add" {
+                let _ = session.stack.remove(session.stack.len() - 1);
+            }
         }
     }
 }
 
-fn cmd_emboss(image: &Image) -> Option<Image> {
-    let transform = Emboss::new();
-
+fn apply_transform(transform: &dyn Transformation, image: &Image) -> Option<Image> {
     match transform.apply(image) {
         Ok(result) => Some(result),
         Err(e) => {
-            println!("Error: {}", e);
+            message!("Error: {}", e);
             None
         }
     }
@@ -327,8 +1876,10 @@ fn cmd_emboss(image: &Image) -> Option<Image> {
 
 fn parse_command(
     command: &str,
+    config: &Config,
     current_image: &mut Option<Image>,
     previous_image: &mut Option<Image>,
+    layers: &mut Option<LayerSession>,
 ) -> ControlFlow<()> {
     let parts: Vec<&str> = command.split_whitespace().collect();
 
@@ -344,6 +1895,7 @@ fn parse_command(
             if let Some(img) = cmd_load(&parts) {
                 *current_image = Some(img);
                 *previous_image = None;
+                *layers = None;
             }
 
             return ControlFlow::Continue(());
@@ -352,10 +1904,45 @@ fn parse_command(
             println!(concat!(
                 "Available commands: load, save, compress, crop, flip, rotate, ",
                 "fit, invert, grayscale, brightness, contrast, tint, colorize, ",
-                "blur, sharpen, edge, emboss, undo, help, exit"
+                "lightness, vibrance, preset, blur, sharpen, edge, emboss, ",
+                "normalmap, match, sketch, oilpaint, fuse, replace, lut, ",
+                "components, erode, dilate, open, close, batch, bayer, ",
+                "dimensions, gradient, autoorient, seamless, colorcount, ",
+                "region, perspective, chromaberr, glitch, colorblind, redeye, ",
+                "grain, heatmap, resize, letterbox, autolevels, embed, extract, ",
+                "blurfill, autostraighten, channelconvolve, filmneg, selective, ",
+                "dog, cartoon, tinyplanet, scanlines, ascii, diffmap, ",
+                "lightleak, faceblur, lenscorrect, focuspeaking, spotlight, ",
+                "grid, adjust, avgstack, maxstack, kaleidoscope, keystone, ",
+                "saliency, repeat, lumasharpen, split, favicon, stitch, ",
+                "tonemap, temperature, mirrorpad, zoomblur, dehaze, halftone, ",
+                "relief, caption, pixelsort, gradientmap, chromablur, masked, ",
+                "zones, depthblur, displace, histimage, shapevignette, ",
+                "tiltshift, layer, undo, help, exit"
             ));
             return ControlFlow::Continue(());
         }
+        "stitch" => {
+            if let Some(img) = cmd_stitch(&parts) {
+                flatten_layers(layers);
+                *previous_image = current_image.take();
+                *current_image = Some(img);
+            }
+
+            return ControlFlow::Continue(());
+        }
+        "dimensions" => {
+            cmd_dimensions(&parts);
+            return ControlFlow::Continue(());
+        }
+        "batch" => {
+            cmd_batch(&parts, config);
+            return ControlFlow::Continue(());
+        }
+        "preset" if parts.get(1) == Some(&"list") => {
+            println!("Available presets: {}", presets::PRESET_NAMES.join(", "));
+            return ControlFlow::Continue(());
+        }
         _ => {}
     }
 
@@ -365,111 +1952,157 @@ fn parse_command(
     };
 
     match parts[0] {
-        "save" => cmd_save(&parts, image),
+        "save" => cmd_save(&parts, image, config),
         "compress" => cmd_compress(&parts, image),
         "undo" => {
             if let Some(prev) = previous_image.take() {
+                flatten_layers(layers);
                 *current_image = Some(prev);
             } else {
                 println!("Nothing to undo");
             }
         }
-        "crop" => {
-            if let Some(result) = cmd_crop(&parts, image) {
-                *previous_image = current_image.take();
-                *current_image = Some(result);
-            }
-        }
-        "flip" => {
-            if let Some(result) = cmd_flip(&parts, image) {
-                *previous_image = current_image.take();
-                *current_image = Some(result);
-            }
-        }
-        "rotate" => {
-            if let Some(result) = cmd_rotate(&parts, image) {
-                *previous_image = current_image.take();
-                *current_image = Some(result);
-            }
-        }
-        "fit" => {
-            if let Some(result) = cmd_fit(&parts, image) {
-                *previous_image = current_image.take();
-                *current_image = Some(result);
-            }
-        }
-        "invert" => {
-            if let Some(result) = cmd_invert(image) {
-                *previous_image = current_image.take();
-                *current_image = Some(result);
+        "colorcount" => {
+            let count = image.unique_colors();
+            if count >= UNIQUE_COLORS_LIMIT {
+                println!("At least {} unique colors", count);
+            } else {
+                println!("{} unique colors", count);
             }
         }
-        "grayscale" => {
-            if let Some(result) = cmd_grayscale(image) {
+        "ascii" => cmd_ascii(&parts, image),
+        "extract" => match extract_message(image) {
+            Ok(text) => println!("{}", text),
+            Err(e) => println!("Error: {}", e),
+        },
+        "components" => {
+            if let Some(result) = cmd_components(&parts, image) {
+                flatten_layers(layers);
                 *previous_image = current_image.take();
                 *current_image = Some(result);
             }
         }
-        "brightness" => {
-            if let Some(result) = cmd_brightness(&parts, image) {
+        "histimage" => {
+            if let Some(result) = cmd_histimage(&parts, image) {
+                flatten_layers(layers);
                 *previous_image = current_image.take();
                 *current_image = Some(result);
             }
         }
-        "contrast" => {
-            if let Some(result) = cmd_contrast(&parts, image) {
-                *previous_image = current_image.take();
-                *current_image = Some(result);
-            }
+        "layer" => cmd_layer(&parts, config, current_image, previous_image, layers),
+        "split" => cmd_split(&parts, image, config),
+        "favicon" => cmd_favicon(&parts, image),
+        "saliency" => {
+            let result = image.saliency_map();
+            flatten_layers(layers);
+            *previous_image = current_image.take();
+            *current_image = Some(result);
         }
-        "tint" => {
-            if let Some(result) = cmd_tint(&parts, image) {
+        _ => {
+            let Some(transform) = parse_transform(&parts, config) else {
+                return ControlFlow::Continue(());
+            };
+
+            if let Some(result) = apply_transform(transform.as_ref(), image) {
+                flatten_layers(layers);
                 *previous_image = current_image.take();
                 *current_image = Some(result);
             }
         }
-        "colorize" => {
-            if let Some(result) = cmd_colorize(&parts, image) {
-                *previous_image = current_image.take();
-                *current_image = Some(result);
-            }
+    }
+
+    ControlFlow::Continue(())
+}
+
+// Non-interactive mode for shell pipelines: reads an encoded image from stdin,
+// applies each `--op` in order and writes the result to stdout. Diagnostics go
+// to stderr so stdout only ever carries image bytes.
+fn run_pipe(args: &[String], config: &Config) -> Result<(), String> {
+    let usage = "Usage: rustphoto --op <command> [--op <command> ...] [--format <ext>]";
+    let mut ops = Vec::new();
+    let mut format = config.default_format.as_str();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match (arg.as_str(), iter.next()) {
+            ("--op", Some(op)) => ops.push(op.as_str()),
+            ("--format", Some(ext)) => format = ext.as_str(),
+            _ => return Err(usage.to_string()),
         }
-        "blur" => {
-            if let Some(result) = cmd_blur(image) {
-                *previous_image = current_image.take();
-                *current_image = Some(result);
-            }
+    }
+
+    if ops.is_empty() {
+        return Err(usage.to_string());
+    }
+
+    let mut transforms = Vec::new();
+    for op in ops {
+        let parts: Vec<&str> = op.split_whitespace().collect();
+        if parts.is_empty() {
+            return Err(usage.to_string());
         }
-        "sharpen" => {
-            if let Some(result) = cmd_sharpen(image) {
-                *previous_image = current_image.take();
-                *current_image = Some(result);
+        transforms
+            .push(parse_transform(&parts, config).ok_or(format!("Invalid operation: {}", op))?);
+    }
+
+    let mut bytes = Vec::new();
+    stdin()
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Error: {}", e))?;
+
+    let mut image = Image::load_from_bytes(&bytes).map_err(|e| format!("Error: {}", e))?;
+    for transform in &transforms {
+        image = transform
+            .apply(&image)
+            .map_err(|e| format!("Error: {}", e))?;
+    }
+
+    let encoded = image
+        .to_bytes(format)
+        .map_err(|e| format!("Error: {}", e))?;
+    let mut out = stdout().lock();
+    out.write_all(&encoded)
+        .and_then(|_| out.flush())
+        .map_err(|e| format!("Error: {}", e))
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    let config = match args.iter().position(|arg| arg == "--config") {
+        Some(i) if i + 1 < args.len() => {
+            let path = expand_path(&args[i + 1]);
+            args.drain(i..=i + 1);
+            match Config::load(&path) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
             }
         }
-        "edge" => {
-            if let Some(result) = cmd_edge(image) {
-                *previous_image = current_image.take();
-                *current_image = Some(result);
-            }
+        Some(_) => {
+            eprintln!("Usage: rustphoto [--config <path>] [--op <command> ...]");
+            std::process::exit(1);
         }
-        "emboss" => {
-            if let Some(result) = cmd_emboss(image) {
-                *previous_image = current_image.take();
-                *current_image = Some(result);
-            }
+        None => Config::default(),
+    };
+
+    if !args.is_empty() {
+        PIPE_MODE.store(true, Ordering::Relaxed);
+        if let Err(message) = run_pipe(&args, &config) {
+            eprintln!("{}", message);
+            std::process::exit(1);
         }
-        _ => println!("Unknown command: {}", command),
+        return;
     }
 
-    ControlFlow::Continue(())
-}
-
-fn main() {
     println!("Welcome to RustPhoto CLI!");
     println!("Type 'exit' to quit");
 
     let mut current_image: Option<Image> = None;
     let mut previous_image: Option<Image> = None;
+    let mut layers: Option<LayerSession> = None;
 
     loop {
         print!("> ");
@@ -484,9 +2117,13 @@ fn main() {
         match stdin().read_line(&mut input) {
             Ok(0) => break, // EOF
             Ok(_) => {
-                if let ControlFlow::Break(()) =
-                    parse_command(input.trim(), &mut current_image, &mut previous_image)
-                {
+                if let ControlFlow::Break(()) = parse_command(
+                    input.trim(),
+                    &config,
+                    &mut current_image,
+                    &mut previous_image,
+                    &mut layers,
+                ) {
                     break;
                 }
             }
@@ -501,10 +2138,10 @@ fn main() {
 // Utils
 
 fn expand_path(path: &str) -> String {
-    if let Some(rest) = path.strip_prefix("~/") {
-        if let Some(home) = dirs::home_dir() {
-            return home.join(rest).to_string_lossy().to_string();
-        }
+    if let Some(rest) = path.strip_prefix("~/")
+        && let Some(home) = dirs::home_dir()
+    {
+        return home.join(rest).to_string_lossy().to_string();
     }
 
     path.to_string()
@@ -514,11 +2151,11 @@ fn parse_number(s: &str) -> Option<i32> {
     match s.parse::<i32>() {
         Ok(n) if n >= 0 => Some(n),
         Ok(_) => {
-            println!("Number must be non-negative");
+            message!("Number must be non-negative");
             None
         }
         Err(_) => {
-            println!("Invalid number");
+            message!("Invalid number");
             None
         }
     }
@@ -526,15 +2163,333 @@ fn parse_number(s: &str) -> Option<i32> {
 
 fn parse_float(s: &str) -> Option<f32> {
     s.parse().ok().or_else(|| {
-        println!("Invalid number");
+        message!("Invalid number");
         None
     })
 }
 
-fn parse_hex_color(s: &str) -> Option<u32> {
-    if s.starts_with("#") {
-        u32::from_str_radix(&s[1..], 16).ok()
+fn parse_seed(s: &str) -> Option<u64> {
+    s.parse().ok().or_else(|| {
+        message!("Invalid seed");
+        None
+    })
+}
+
+fn parse_kernel(s: &str) -> Option<Kernel> {
+    if s == "identity" {
+        return Some(Kernel::identity());
+    }
+
+    let values: Option<Vec<f32>> = s.split(',').map(|v| v.trim().parse().ok()).collect();
+    let Some(values) = values else {
+        message!("Invalid kernel: {}", s);
+        return None;
+    };
+
+    Kernel::from_values(values)
+        .map_err(|e| message!("Error: {}", e))
+        .ok()
+}
+
+fn parse_sampling(flags: &[&str]) -> Sampling {
+    if flags.contains(&"--area") {
+        Sampling::Area
     } else {
-        u32::from_str_radix(s, 16).ok()
+        Sampling::Nearest
+    }
+}
+
+// Reads an optional `--supersample N` flag; 1 (no supersampling) when absent.
+fn parse_supersample(flags: &[&str]) -> Option<u8> {
+    let Some(pos) = flags.iter().position(|&f| f == "--supersample") else {
+        return Some(1);
+    };
+
+    let Some(value) = flags.get(pos + 1) else {
+        message!("Missing value for --supersample (1, 2 or 4)");
+        return None;
+    };
+
+    Some(parse_number(value)?.clamp(0, 255) as u8)
+}
+
+fn parse_hex_color(s: &str) -> Option<u32> {
+    let hex = s.strip_prefix("#").unwrap_or(s);
+    u32::from_str_radix(hex, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustphoto::image::Metadata;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustphoto-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn gray(width: i32, height: i32, value: u8) -> Image {
+        Image {
+            width,
+            height,
+            pixels: vec![Pixel::new(value, value, value); (width * height) as usize],
+            metadata: Metadata::default(),
+        }
+    }
+
+    #[test]
+    fn batch_only_processes_matching_files() {
+        let input = temp_dir("batch-in");
+        let output = temp_dir("batch-out");
+        for name in ["a.png", "b.png", "c.bmp"] {
+            gray(4, 4, 10)
+                .save(&input.join(name).to_string_lossy())
+                .unwrap();
+        }
+        std::fs::write(input.join("notes.png.txt"), "not an image").unwrap();
+
+        let pattern = input.join("*.png").to_string_lossy().into_owned();
+        let output_dir = output.to_string_lossy().into_owned();
+        cmd_batch(
+            &["batch", &pattern, &output_dir, "invert"],
+            &Config::default(),
+        );
+
+        let mut written: Vec<String> = std::fs::read_dir(&output)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        written.sort();
+        assert_eq!(written, ["a.png", "b.png"]);
+
+        let inverted = Image::load(&output.join("a.png").to_string_lossy()).unwrap();
+        assert_eq!(inverted.pixels[0].r, 245);
+
+        std::fs::remove_dir_all(&input).unwrap();
+        std::fs::remove_dir_all(&output).unwrap();
+    }
+
+    #[test]
+    fn save_uses_the_configured_default_quality() {
+        let dir = temp_dir("save-quality");
+        let image = Image {
+            width: 32,
+            height: 32,
+            pixels: (0..32 * 32)
+                .map(|i| Pixel::new((i * 37 % 256) as u8, (i * 11 % 256) as u8, (i % 256) as u8))
+                .collect(),
+            metadata: Metadata::default(),
+        };
+        let config = Config {
+            default_quality: 70,
+            ..Config::default()
+        };
+
+        let saved = dir.join("foo.jpg").to_string_lossy().into_owned();
+        cmd_save(&["save", &saved], &image, &config);
+
+        let at_70 = dir.join("at70.jpg").to_string_lossy().into_owned();
+        let at_75 = dir.join("at75.jpg").to_string_lossy().into_owned();
+        compression::save_jpeg(&image, &at_70, 70).unwrap();
+        compression::save_jpeg(&image, &at_75, 75).unwrap();
+
+        let saved_bytes = std::fs::read(&saved).unwrap();
+        assert_eq!(saved_bytes, std::fs::read(&at_70).unwrap());
+        assert_ne!(saved_bytes, std::fs::read(&at_75).unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batch_report_counts_processed_and_failed_files() {
+        let input = temp_dir("report-in");
+        let output = temp_dir("report-out");
+        let mut paths = Vec::new();
+        for name in ["a.png", "b.png"] {
+            let path = input.join(name);
+            gray(8, 8, 40).save(&path.to_string_lossy()).unwrap();
+            paths.push(path);
+        }
+        let broken = input.join("broken.png");
+        std::fs::write(&broken, "not an image").unwrap();
+        paths.push(broken);
+        // No file name to write the result under
+        paths.push(input.join(".."));
+
+        let report = run_batch(&paths, &output.to_string_lossy(), &Invert::new(), true);
+
+        assert_eq!(report.processed, 2);
+        assert_eq!(report.failed, 2);
+        let written: u64 = ["a.png", "b.png"]
+            .iter()
+            .map(|name| std::fs::metadata(output.join(name)).unwrap().len())
+            .sum();
+        assert_eq!(report.output_bytes, written);
+        assert!(report.input_bytes > 0);
+
+        std::fs::remove_dir_all(&input).unwrap();
+        std::fs::remove_dir_all(&output).unwrap();
+    }
+
+    #[test]
+    fn progressive_jpeg_falls_back_to_baseline() {
+        let dir = temp_dir("save-progressive");
+        let image = gray(16, 16, 90);
+
+        let progressive = dir.join("progressive.jpg").to_string_lossy().into_owned();
+        let baseline = dir.join("baseline.jpg").to_string_lossy().into_owned();
+        cmd_save(
+            &["save", &progressive, "80", "--progressive"],
+            &image,
+            &Config::default(),
+        );
+        compression::save_jpeg(&image, &baseline, 80).unwrap();
+
+        assert_eq!(
+            std::fs::read(&progressive).unwrap(),
+            std::fs::read(&baseline).unwrap()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn repeat_applies_the_command_n_times_as_one_edit() {
+        let config = Config::default();
+        let source = Image {
+            width: 3,
+            height: 1,
+            pixels: vec![
+                Pixel::new(0, 50, 100),
+                Pixel::new(150, 200, 250),
+                Pixel::new(7, 8, 9),
+            ],
+            metadata: Metadata::default(),
+        };
+        let rgb = |image: &Image| -> Vec<(u8, u8, u8)> {
+            image.pixels.iter().map(|p| (p.r, p.g, p.b)).collect()
+        };
+
+        let thrice = parse_transform(&["repeat", "3", "invert"], &config).unwrap();
+        let once = Invert::new().apply(&source).unwrap();
+        assert_eq!(rgb(&thrice.apply(&source).unwrap()), rgb(&once));
+
+        let twice = parse_transform(&["repeat", "2", "invert"], &config).unwrap();
+        assert_eq!(rgb(&twice.apply(&source).unwrap()), rgb(&source));
+
+        // A single undo steps back over the whole repetition
+        let (mut current, mut previous, mut layers) = (Some(source), None, None);
+        let original = rgb(current.as_ref().unwrap());
+        let _ = parse_command(
+            "repeat 3 invert",
+            &config,
+            &mut current,
+            &mut previous,
+            &mut layers,
+        );
+        assert_eq!(rgb(current.as_ref().unwrap()), rgb(&once));
+        let _ = parse_command("undo", &config, &mut current, &mut previous, &mut layers);
+        assert_eq!(rgb(current.as_ref().unwrap()), original);
+    }
+
+    #[test]
+    fn split_saves_one_file_per_tile() {
+        let dir = temp_dir("split");
+        let path = dir.join("map.png").to_string_lossy().into_owned();
+        cmd_split(
+            &["split", "2", "3", &path],
+            &gray(4, 6, 90),
+            &Config::default(),
+        );
+
+        for row in 0..3 {
+            for col in 0..2 {
+                let tile = Image::load(
+                    &dir.join(format!("map_r{}_c{}.png", row, col))
+                        .to_string_lossy(),
+                )
+                .unwrap();
+                assert_eq!((tile.width, tile.height), (2, 2));
+            }
+        }
+        assert!(!dir.join("map_r3_c0.png").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn masked_applies_the_effect_only_where_the_mask_is_lit() {
+        let config = Config::default();
+        let masked = parse_transform(&["masked", "edge", "/", "invert"], &config).unwrap();
+
+        // A flat image has no edges, so the mask is black everywhere
+        let flat = gray(6, 6, 70);
+        assert!(
+            masked
+                .apply(&flat)
+                .unwrap()
+                .pixels
+                .iter()
+                .all(|p| p.r == 70)
+        );
+
+        let mut step = gray(6, 6, 20);
+        for p in step.pixels.iter_mut().skip(18) {
+            *p = Pixel::new(230, 230, 230);
+        }
+        let output = masked.apply(&step).unwrap();
+        assert_eq!(output.pixels[0].r, 20);
+        assert!((12..24).any(|i| output.pixels[i].r != step.pixels[i].r));
+
+        assert!(parse_transform(&["masked", "edge", "invert"], &config).is_none());
+    }
+
+    #[test]
+    fn displace_takes_a_supersample_flag() {
+        let dir = temp_dir("displace");
+        let map = dir.join("map.png").to_string_lossy().into_owned();
+        gray(5, 5, 128).save(&map).unwrap();
+        let config = Config::default();
+
+        let source = gray(5, 5, 40);
+        for args in [
+            &["displace", &map, "8"][..],
+            &["displace", &map, "8", "--supersample", "2"],
+        ] {
+            let output = parse_transform(args, &config)
+                .unwrap()
+                .apply(&source)
+                .unwrap();
+            assert!(output.pixels.iter().all(|p| p.r == 40));
+        }
+
+        let bad = parse_transform(&["displace", &map, "8", "--supersample", "3"], &config).unwrap();
+        assert!(bad.apply(&source).is_err());
+        assert!(parse_transform(&["displace", &map, "8", "--supersample"], &config).is_none());
+    }
+
+    #[test]
+    fn layer_commands_rerender_from_the_base() {
+        let config = Config::default();
+        let source = Image {
+            width: 2,
+            height: 1,
+            pixels: vec![Pixel::new(200, 40, 10), Pixel::new(5, 90, 250)],
+            metadata: Metadata::default(),
+        };
+        let rgb = |image: &Image| -> Vec<(u8, u8, u8)> {
+            image.pixels.iter().map(|p| (p.r, p.g, p.b)).collect()
+        };
+        let gray_only = Grayscale::new().apply(&source).unwrap();
+
+        let (mut current, mut previous, mut layers) = (Some(source), None, None);
+        for command in ["layer add invert", "layer add grayscale", "layer toggle 1"] {
+            let _ = parse_command(command, &config, &mut current, &mut previous, &mut layers);
+        }
+        assert_eq!(rgb(current.as_ref().unwrap()), rgb(&gray_only));
+
+        let session = layers.as_ref().unwrap();
+        let state: Vec<(&str, bool)> = session.stack.iter().collect();
+        assert_eq!(state, vec![("invert", false), ("grayscale", true)]);
     }
 }
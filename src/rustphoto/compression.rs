@@ -3,20 +3,53 @@
 // Uses binary search on JPEG quality parameter (1-100) to find the highest
 // quality setting that produces a file within the target size constraint.
 // Minimum quality 10 prevents extremely degraded output.
+//
+// Also provides multi-resolution ICO output: each requested size is
+// resampled (Lanczos3) from a square source and stored as a PNG entry,
+// and a web favicon set: the center square area-resampled to the usual
+// browser, Apple touch and PWA manifest sizes.
+//
+// Interlaced PNG output is written by hand (the image crate's encoder has no
+// Adam7 support): each of the seven passes is serialized with the Sub filter
+// and the whole stream is zlib-compressed into a single IDAT chunk.
 
 use super::error::ProcessError;
 use super::image::Image;
+use super::transforms::{Crop, Resize, Sampling, Transformation};
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use image::codecs::ico::{IcoEncoder, IcoFrame};
 use image::codecs::jpeg::JpegEncoder;
-use std::io::Cursor;
+use image::imageops::FilterType;
+use std::io::{Cursor, Write};
 
-fn encode_jpeg_to_buffer(image: &Image, quality: u8) -> Result<Vec<u8>, ProcessError> {
-    let mut buffer = image::RgbImage::new(image.width as u32, image.height as u32);
+pub const ICO_SIZES: &[i32] = &[16, 32, 48, 256];
 
-    for (i, pixel) in image.pixels.iter().enumerate() {
-        let x = (i as i32 % image.width) as u32;
-        let y = (i as i32 / image.width) as u32;
-        buffer.put_pixel(x, y, image::Rgb([pixel.r, pixel.g, pixel.b]));
-    }
+// Favicon sizes with their conventional file names
+pub const FAVICON_SIZES: &[(i32, &str)] = &[
+    (16, "favicon-16x16.png"),
+    (32, "favicon-32x32.png"),
+    (48, "favicon-48x48.png"),
+    (180, "apple-touch-icon.png"),
+    (192, "android-chrome-192x192.png"),
+    (512, "android-chrome-512x512.png"),
+];
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// Adam7 passes as (x start, y start, x step, y step)
+const ADAM7_PASSES: [(i32, i32, i32, i32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+fn encode_jpeg_to_buffer(image: &Image, quality: u8) -> Result<Vec<u8>, ProcessError> {
+    let buffer = image.to_rgb_buffer();
 
     let mut encoded = Cursor::new(Vec::new());
     let mut encoder = JpegEncoder::new_with_quality(&mut encoded, quality);
@@ -32,6 +65,15 @@ fn encode_jpeg_to_buffer(image: &Image, quality: u8) -> Result<Vec<u8>, ProcessE
     Ok(encoded.into_inner())
 }
 
+pub fn save_jpeg(image: &Image, path: &str, quality: u8) -> Result<(), ProcessError> {
+    let encoded = encode_jpeg_to_buffer(image, quality)?;
+
+    std::fs::write(path, encoded).map_err(|e| ProcessError::FileWrite {
+        path: path.to_string(),
+        source: Box::new(e),
+    })
+}
+
 pub fn save_jpeg_compressed(
     image: &Image,
     path: &str,
@@ -78,3 +120,293 @@ pub fn save_jpeg_compressed(
         }
     }
 }
+
+pub fn save_ico(
+    image: &Image,
+    path: &str,
+    sizes: &[i32],
+    crop_to_square: bool,
+) -> Result<(), ProcessError> {
+    if let Some(size) = sizes.iter().find(|&&size| !(1..=256).contains(&size)) {
+        return Err(ProcessError::InvalidInput {
+            operation: "ico".to_string(),
+            details: format!("icon size {} is outside 1-256", size),
+        });
+    }
+
+    if image.width <= 0 || image.height <= 0 {
+        return Err(ProcessError::InvalidInput {
+            operation: "ico".to_string(),
+            details: "source image is empty".to_string(),
+        });
+    }
+
+    let square = if image.width == image.height {
+        None
+    } else if crop_to_square {
+        Some(center_square(image)?)
+    } else {
+        return Err(ProcessError::InvalidInput {
+            operation: "ico".to_string(),
+            details: format!("image must be square, got {}x{}", image.width, image.height),
+        });
+    };
+
+    let buffer = square.as_ref().unwrap_or(image).to_rgb_buffer();
+    let mut frames = Vec::with_capacity(sizes.len());
+
+    for &size in sizes {
+        let resized =
+            image::imageops::resize(&buffer, size as u32, size as u32, FilterType::Lanczos3);
+        // PNG entries must be 32-bit RGBA; readers reject RGB ones
+        let rgba = image::DynamicImage::ImageRgb8(resized).to_rgba8();
+        let frame = IcoFrame::as_png(
+            rgba.as_raw(),
+            size as u32,
+            size as u32,
+            image::ExtendedColorType::Rgba8,
+        )
+        .map_err(|e| ProcessError::IcoEncoding(Box::new(e)))?;
+        frames.push(frame);
+    }
+
+    let mut encoded = Vec::new();
+    IcoEncoder::new(&mut encoded)
+        .encode_images(&frames)
+        .map_err(|e| ProcessError::IcoEncoding(Box::new(e)))?;
+
+    std::fs::write(path, encoded).map_err(|e| ProcessError::FileWrite {
+        path: path.to_string(),
+        source: Box::new(e),
+    })?;
+
+    Ok(())
+}
+
+// Largest centered square of `image`.
+fn center_square(image: &Image) -> Result<Image, ProcessError> {
+    let side = image.width.min(image.height);
+    let x = (image.width - side) / 2;
+    let y = (image.height - side) / 2;
+    Crop::new(x, y, side, side).apply(image)
+}
+
+// Returns (file name, icon) for every entry in FAVICON_SIZES, cropping a
+// non-square image to its center square first.
+pub fn favicon_set(image: &Image) -> Result<Vec<(String, Image)>, ProcessError> {
+    if image.width <= 0 || image.height <= 0 {
+        return Err(ProcessError::InvalidInput {
+            operation: "favicon".to_string(),
+            details: "source image is empty".to_string(),
+        });
+    }
+
+    let square = center_square(image)?;
+
+    FAVICON_SIZES
+        .iter()
+        .map(|&(size, name)| {
+            let icon = Resize::new(size, size)
+                .with_sampling(Sampling::Area)
+                .apply(&square)?;
+            Ok((name.to_string(), icon))
+        })
+        .collect()
+}
+
+pub fn save_png_interlaced(image: &Image, path: &str) -> Result<(), ProcessError> {
+    let encoded = encode_png_interlaced(image)?;
+
+    std::fs::write(path, encoded).map_err(|e| ProcessError::FileWrite {
+        path: path.to_string(),
+        source: Box::new(e),
+    })
+}
+
+fn encode_png_interlaced(image: &Image) -> Result<Vec<u8>, ProcessError> {
+    let encoding_error = |e: std::io::Error| ProcessError::ImageEncoding {
+        format: "PNG".to_string(),
+        source: Box::new(e),
+    };
+
+    let mut raw = Vec::new();
+    for &(x0, y0, dx, dy) in &ADAM7_PASSES {
+        // Passes that contain no pixels are omitted entirely
+        if x0 >= image.width || y0 >= image.height {
+            continue;
+        }
+
+        for y in (y0..image.height).step_by(dy as usize) {
+            let row: Vec<u8> = (x0..image.width)
+                .step_by(dx as usize)
+                .flat_map(|x| {
+                    let p = image.pixels[(y * image.width + x) as usize];
+                    [p.r, p.g, p.b]
+                })
+                .collect();
+
+            // Sub filter: each byte minus the same channel of the previous pixel
+            raw.push(1);
+            raw.extend(
+                row.iter()
+                    .enumerate()
+                    .map(|(i, &b)| if i < 3 { b } else { b.wrapping_sub(row[i - 3]) }),
+            );
+        }
+    }
+
+    let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+    zlib.write_all(&raw).map_err(encoding_error)?;
+    let compressed = zlib.finish().map_err(encoding_error)?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend((image.width as u32).to_be_bytes());
+    header.extend((image.height as u32).to_be_bytes());
+    // 8-bit RGB, deflate, adaptive filtering, Adam7 interlace
+    header.extend([8, 2, 0, 0, 1]);
+
+    let mut out = PNG_SIGNATURE.to_vec();
+    write_png_chunk(&mut out, b"IHDR", &header);
+    write_png_chunk(&mut out, b"IDAT", &compressed);
+    write_png_chunk(&mut out, b"IEND", &[]);
+
+    Ok(out)
+}
+
+fn write_png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+
+    out.extend((data.len() as u32).to_be_bytes());
+    out.extend(kind);
+    out.extend(data);
+    out.extend(crc.finalize().to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rustphoto::image::{Metadata, Pixel};
+
+    fn gradient(width: i32, height: i32) -> Image {
+        let mut pixels = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(Pixel::new((x * 7) as u8, (y * 5) as u8, 90));
+            }
+        }
+
+        Image {
+            width,
+            height,
+            pixels,
+            metadata: Metadata::default(),
+        }
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("rustphoto-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn ico_holds_one_entry_per_size_and_decodes() {
+        let path = temp_path("icon.ico");
+        save_ico(&gradient(40, 30), &path, ICO_SIZES, true).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let count = u16::from_le_bytes([bytes[4], bytes[5]]) as usize;
+        assert_eq!(count, ICO_SIZES.len());
+
+        // The decoder picks the largest entry
+        let decoded = Image::load(&path).unwrap();
+        assert_eq!((decoded.width, decoded.height), (256, 256));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn ico_rejects_non_square_without_crop() {
+        let path = temp_path("rejected.ico");
+        assert!(save_ico(&gradient(40, 30), &path, ICO_SIZES, false).is_err());
+        assert!(save_ico(&gradient(8, 8), &path, &[300], false).is_err());
+    }
+
+    #[test]
+    fn interlaced_png_round_trips() {
+        // Odd sizes leave some Adam7 passes partial or empty
+        for (width, height) in [(13, 11), (3, 2), (1, 1)] {
+            let image = gradient(width, height);
+            let path = temp_path(&format!("adam7-{}x{}.png", width, height));
+            save_png_interlaced(&image, &path).unwrap();
+
+            let bytes = std::fs::read(&path).unwrap();
+            // Signature (8) + IHDR length and type (8) + interlace is the 13th data byte
+            assert_eq!(bytes[28], 1);
+
+            let loaded = Image::load(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!((loaded.width, loaded.height), (width, height));
+            for (a, b) in image.pixels.iter().zip(&loaded.pixels) {
+                assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+            }
+        }
+    }
+
+    #[test]
+    fn favicon_set_produces_every_square_size_from_the_center() {
+        // Red side bars that the center crop must drop
+        let mut wide = gradient(60, 40);
+        for (i, p) in wide.pixels.iter_mut().enumerate() {
+            let x = i as i32 % 60;
+            *p = if !(10..50).contains(&x) {
+                Pixel::new(255, 0, 0)
+            } else {
+                Pixel::new(0, 0, 255)
+            };
+        }
+
+        let icons = favicon_set(&wide).unwrap();
+        let names: Vec<&str> = icons.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "favicon-16x16.png",
+                "favicon-32x32.png",
+                "favicon-48x48.png",
+                "apple-touch-icon.png",
+                "android-chrome-192x192.png",
+                "android-chrome-512x512.png",
+            ]
+        );
+
+        for ((_, icon), &(size, _)) in icons.iter().zip(FAVICON_SIZES) {
+            assert_eq!((icon.width, icon.height), (size, size));
+            assert!(icon.pixels.iter().all(|p| p.r == 0 && p.b == 255));
+        }
+    }
+
+    #[test]
+    fn favicon_set_rejects_an_empty_image() {
+        assert!(matches!(
+            favicon_set(&gradient(0, 0)),
+            Err(ProcessError::InvalidInput { operation, .. }) if operation == "favicon"
+        ));
+        assert!(favicon_set(&gradient(0, 40)).is_err());
+    }
+
+    #[test]
+    fn ico_rejects_an_empty_image_without_writing() {
+        let path = temp_path("empty.ico");
+        for crop in [false, true] {
+            assert!(matches!(
+                save_ico(&gradient(0, 0), &path, ICO_SIZES, crop),
+                Err(ProcessError::InvalidInput { operation, .. }) if operation == "ico"
+            ));
+        }
+        assert!(save_ico(&gradient(0, 30), &path, ICO_SIZES, true).is_err());
+        assert!(!std::path::Path::new(&path).exists());
+    }
+}
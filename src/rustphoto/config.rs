@@ -0,0 +1,114 @@
+// User defaults loaded from a TOML file passed with `--config <path>`.
+//
+// Every key is optional; missing keys (or a missing file) fall back to the
+// built-in defaults:
+//
+// ```toml
+// default_quality = 75        # JPEG quality when `save` gets none
+// default_blur_radius = 3     # radius when `blur` gets none (unset: 3x3)
+// default_format = "png"      # pipe mode output and extensionless saves
+// composite_space = "srgb"    # "linear" blends in linear light
+// ```
+//
+// `composite_space` covers every partial blend: `gradient`, `masked`,
+// `lightleak` and `diffmap`.
+
+use super::error::ProcessError;
+use super::transforms::CompositeSpace;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub default_quality: u8,
+    pub default_blur_radius: Option<i32>,
+    pub default_format: String,
+    pub composite_space: CompositeSpace,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            default_quality: 75,
+            default_blur_radius: None,
+            default_format: "png".to_string(),
+            composite_space: CompositeSpace::Srgb,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Self, ProcessError> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(ProcessError::FileRead {
+                    path: path.to_string(),
+                    source: Box::new(e),
+                });
+            }
+        };
+
+        let config: Self = toml::from_str(&contents).map_err(|e| ProcessError::ConfigParse {
+            path: path.to_string(),
+            details: e.message().to_string(),
+        })?;
+
+        if !(1..=100).contains(&config.default_quality) {
+            return Err(ProcessError::ConfigParse {
+                path: path.to_string(),
+                details: format!(
+                    "default_quality must be 1-100, got {}",
+                    config.default_quality
+                ),
+            });
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_config(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "rustphoto-config-{}-{}.toml",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn set_keys_override_the_defaults() {
+        let path = temp_config("quality", "default_quality = 70\n");
+        let config = Config::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.default_quality, 70);
+        assert_eq!(config.default_format, "png");
+        assert_eq!(config.default_blur_radius, None);
+    }
+
+    #[test]
+    fn missing_file_falls_back_to_defaults() {
+        let config = Config::load("/nonexistent/rustphoto.toml").unwrap();
+        assert_eq!(config.default_quality, 75);
+    }
+
+    #[test]
+    fn invalid_values_and_unknown_keys_are_rejected() {
+        for (name, contents) in [
+            ("range", "default_quality = 0\n"),
+            ("unknown", "default_qualty = 70\n"),
+        ] {
+            let path = temp_config(name, contents);
+            assert!(Config::load(&path).is_err());
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,289 @@
+// Haze removal with the dark channel prior (He, Sun and Tang, 2009).
+//
+// # Model
+//
+// A hazy image is I = J * t + A * (1 - t): the scene radiance J attenuated
+// by the transmission t and mixed with the atmospheric light A.
+//
+// # Steps
+//
+// 1. Dark channel: the per-pixel minimum over R, G and B, followed by a
+//    minimum filter over a `PATCH_RADIUS` square. In haze-free outdoor
+//    images it is close to zero almost everywhere, so a bright dark channel
+//    measures haze.
+// 2. Atmospheric light: the average color of the brightest 0.1% of the
+//    dark channel, which picks the densest haze rather than white objects.
+// 3. Transmission: t = 1 - omega * dark(I / A), smoothed with a guided
+//    filter on luma so edges in t follow edges in the image (no halos).
+// 4. Recovery: J = (I - A) / max(t, T_MIN) + A.
+//
+// `strength` (0-1) scales omega, so 0 leaves the image unchanged.
+
+use super::error::ProcessError;
+use super::image::{Image, Pixel};
+use super::transforms::Transformation;
+
+const PATCH_RADIUS: i32 = 7;
+const GUIDE_RADIUS: i32 = 28;
+const GUIDE_EPSILON: f32 = 1e-3;
+// omega at full strength; slightly below 1 keeps a trace of haze for depth
+const MAX_OMEGA: f32 = 0.95;
+// Lower bound on transmission, so dense haze is not amplified into noise
+const T_MIN: f32 = 0.1;
+
+pub struct Dehaze {
+    strength: f32,
+}
+
+impl Dehaze {
+    pub fn new(strength: f32) -> Self {
+        Self { strength }
+    }
+}
+
+// Minimum over a (2r+1)^2 square, as a horizontal pass then a vertical one.
+fn min_filter(values: &[f32], width: i32, height: i32, radius: i32) -> Vec<f32> {
+    let pass = |src: &[f32], dx: i32, dy: i32| -> Vec<f32> {
+        let mut out = vec![0.0; src.len()];
+        for y in 0..height {
+            for x in 0..width {
+                let mut min = f32::MAX;
+                for k in -radius..=radius {
+                    let sx = (x + k * dx).clamp(0, width - 1);
+                    let sy = (y + k * dy).clamp(0, height - 1);
+                    min = min.min(src[(sy * width + sx) as usize]);
+                }
+                out[(y * width + x) as usize] = min;
+            }
+        }
+        out
+    };
+
+    pass(&pass(values, 1, 0), 0, 1)
+}
+
+// Mean over a (2r+1)^2 square clipped to the image, from a summed-area table.
+fn box_mean(values: &[f32], width: i32, height: i32, radius: i32) -> Vec<f32> {
+    let stride = (width + 1) as usize;
+    let mut integral = vec![0.0f64; stride * (height + 1) as usize];
+    for y in 0..height as usize {
+        let mut row = 0.0;
+        for x in 0..width as usize {
+            row += values[y * width as usize + x] as f64;
+            integral[(y + 1) * stride + x + 1] = integral[y * stride + x + 1] + row;
+        }
+    }
+
+    let mut out = Vec::with_capacity(values.len());
+    for y in 0..height {
+        for x in 0..width {
+            let x0 = (x - radius).max(0) as usize;
+            let y0 = (y - radius).max(0) as usize;
+            let x1 = (x + radius + 1).min(width) as usize;
+            let y1 = (y + radius + 1).min(height) as usize;
+
+            let sum = integral[y1 * stride + x1]
+                - integral[y0 * stride + x1]
+                - integral[y1 * stride + x0]
+                + integral[y0 * stride + x0];
+            out.push((sum / ((x1 - x0) * (y1 - y0)) as f64) as f32);
+        }
+    }
+
+    out
+}
+
+// Edge-preserving smoothing of `input` steered by `guide` (He et al. 2010):
+// locally, the output is a linear function of the guide.
+fn guided_filter(guide: &[f32], input: &[f32], width: i32, height: i32) -> Vec<f32> {
+    let mean = |values: &[f32]| box_mean(values, width, height, GUIDE_RADIUS);
+    let product =
+        |a: &[f32], b: &[f32]| -> Vec<f32> { a.iter().zip(b).map(|(x, y)| x * y).collect() };
+
+    let mean_guide = mean(guide);
+    let mean_input = mean(input);
+    let corr_guide = mean(&product(guide, guide));
+    let corr_cross = mean(&product(guide, input));
+
+    let mut a = Vec::with_capacity(guide.len());
+    let mut b = Vec::with_capacity(guide.len());
+    for i in 0..guide.len() {
+        let variance = corr_guide[i] - mean_guide[i] * mean_guide[i];
+        let covariance = corr_cross[i] - mean_guide[i] * mean_input[i];
+        let slope = covariance / (variance + GUIDE_EPSILON);
+        a.push(slope);
+        b.push(mean_input[i] - slope * mean_guide[i]);
+    }
+
+    let mean_a = mean(&a);
+    let mean_b = mean(&b);
+    (0..guide.len())
+        .map(|i| mean_a[i] * guide[i] + mean_b[i])
+        .collect()
+}
+
+// Average color of the pixels with the brightest dark channel (top 0.1%).
+fn atmospheric_light(colors: &[[f32; 3]], dark: &[f32]) -> [f32; 3] {
+    let mut order: Vec<usize> = (0..dark.len()).collect();
+    order.sort_unstable_by(|&a, &b| dark[b].total_cmp(&dark[a]));
+
+    let count = (dark.len() / 1000).max(1);
+    let mut light = [0.0f32; 3];
+    for &i in &order[..count] {
+        for (l, c) in light.iter_mut().zip(colors[i]) {
+            *l += c / count as f32;
+        }
+    }
+
+    // Avoid dividing by a zero channel when normalizing by A
+    light.map(|v| v.max(1e-3))
+}
+
+impl Transformation for Dehaze {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if !(0.0..=1.0).contains(&self.strength) {
+            return Err(ProcessError::InvalidInput {
+                operation: "dehaze".to_string(),
+                details: format!("strength must be 0.0-1.0, got {}", self.strength),
+            });
+        }
+
+        // Nothing to estimate the atmospheric light from
+        if image.pixels.is_empty() {
+            return Ok(Image {
+                width: image.width,
+                height: image.height,
+                pixels: Vec::new(),
+                metadata: image.metadata,
+            });
+        }
+
+        let (width, height) = (image.width, image.height);
+        let colors: Vec<[f32; 3]> = image
+            .pixels
+            .iter()
+            .map(|p| [p.r, p.g, p.b].map(|v| v as f32 / 255.0))
+            .collect();
+
+        let channel_min = |c: &[f32; 3]| c[0].min(c[1]).min(c[2]);
+        let dark: Vec<f32> = colors.iter().map(channel_min).collect();
+        let dark = min_filter(&dark, width, height, PATCH_RADIUS);
+        let light = atmospheric_light(&colors, &dark);
+
+        let omega = MAX_OMEGA * self.strength;
+        let normalized_dark: Vec<f32> = colors
+            .iter()
+            .map(|c| channel_min(&[c[0] / light[0], c[1] / light[1], c[2] / light[2]]))
+            .collect();
+        let transmission: Vec<f32> = min_filter(&normalized_dark, width, height, PATCH_RADIUS)
+            .iter()
+            .map(|d| 1.0 - omega * d)
+            .collect();
+
+        let luma: Vec<f32> = image.pixels.iter().map(|p| p.luma() / 255.0).collect();
+        let transmission = guided_filter(&luma, &transmission, width, height);
+
+        let pixels: Vec<Pixel> = colors
+            .iter()
+            .zip(&transmission)
+            .map(|(c, &t)| {
+                let t = t.clamp(T_MIN, 1.0);
+                let [r, g, b] =
+                    std::array::from_fn(|i| (((c[i] - light[i]) / t + light[i]) * 255.0).round());
+                Pixel::from_f32(r, g, b)
+            })
+            .collect();
+
+        Ok(Image {
+            width,
+            height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rustphoto::image::Metadata;
+
+    fn image(width: i32, height: i32, f: impl Fn(i32, i32) -> Pixel) -> Image {
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(f(x, y));
+            }
+        }
+
+        Image {
+            width,
+            height,
+            pixels,
+            metadata: Metadata::default(),
+        }
+    }
+
+    // Saturated color blocks seen through haze: I = J * t + A * (1 - t)
+    // with white atmospheric light and t = 0.5
+    fn hazy_scene() -> Image {
+        image(40, 40, |x, y| {
+            let clear = match ((x / 10) + (y / 10)) % 3 {
+                0 => [200.0, 40.0, 30.0],
+                1 => [20.0, 150.0, 60.0],
+                _ => [30.0, 50.0, 180.0],
+            };
+            let [r, g, b] = clear.map(|v: f32| (v * 0.5 + 255.0 * 0.5).round());
+            Pixel::from_f32(r, g, b)
+        })
+    }
+
+    fn mean_dark_channel(image: &Image) -> f32 {
+        let sum: f32 = image
+            .pixels
+            .iter()
+            .map(|p| p.r.min(p.g).min(p.b) as f32)
+            .sum();
+        sum / image.pixels.len() as f32
+    }
+
+    #[test]
+    fn dehaze_lowers_the_dark_channel_of_a_hazy_scene() {
+        let hazy = hazy_scene();
+        let output = Dehaze::new(1.0).apply(&hazy).unwrap();
+
+        let (before, after) = (mean_dark_channel(&hazy), mean_dark_channel(&output));
+        assert!(
+            after < before - 40.0,
+            "dark channel {} -> {}",
+            before,
+            after
+        );
+    }
+
+    #[test]
+    fn dehaze_with_no_strength_keeps_the_image() {
+        let hazy = hazy_scene();
+        let output = Dehaze::new(0.0).apply(&hazy).unwrap();
+
+        for (a, b) in hazy.pixels.iter().zip(&output.pixels) {
+            assert!((a.r as i32 - b.r as i32).abs() <= 1);
+            assert!((a.g as i32 - b.g as i32).abs() <= 1);
+            assert!((a.b as i32 - b.b as i32).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn dehaze_returns_an_empty_image_unchanged() {
+        let empty = image(0, 0, |_, _| Pixel::new(0, 0, 0));
+        let output = Dehaze::new(0.5).apply(&empty).unwrap();
+
+        assert_eq!((output.width, output.height), (0, 0));
+        assert!(output.pixels.is_empty());
+    }
+
+    #[test]
+    fn dehaze_rejects_out_of_range_strength() {
+        assert!(Dehaze::new(1.5).apply(&hazy_scene()).is_err());
+    }
+}
@@ -9,6 +9,16 @@ pub enum ProcessError {
         source: Box<dyn std::error::Error + Send + Sync>,
     },
 
+    #[error("Failed to decode image: {0}")]
+    ImageDecode(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Failed to read file from {path}: {source}")]
+    FileRead {
+        path: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
     #[error("Failed to write file to {path}: {source}")]
     FileWrite {
         path: String,
@@ -19,9 +29,28 @@ pub enum ProcessError {
     #[error("{operation} operation out of bounds: {details}")]
     OutOfBounds { operation: String, details: String },
 
+    #[error("Invalid LUT file {path}: {details}")]
+    LutParse { path: String, details: String },
+
+    #[error("Invalid config file {path}: {details}")]
+    ConfigParse { path: String, details: String },
+
+    #[error("Invalid {operation} input: {details}")]
+    InvalidInput { operation: String, details: String },
+
+    #[error("{format} encoding failed: {source}")]
+    ImageEncoding {
+        format: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
     #[error("JPEG encoding failed: {0}")]
     JpegEncoding(#[source] Box<dyn std::error::Error + Send + Sync>),
 
+    #[error("ICO encoding failed: {0}")]
+    IcoEncoding(#[source] Box<dyn std::error::Error + Send + Sync>),
+
     #[error(
         "Target size {target_kb} KB ({target_bytes} bytes) is too small. Minimum achievable size is {min_kb} KB ({min_bytes} bytes) at quality 10"
     )]
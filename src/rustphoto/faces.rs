@@ -0,0 +1,280 @@
+// Face blurring for privacy.
+//
+// `FaceBlur` blurs whatever rectangles a `FaceDetector` reports, so a
+// model-based detector can be plugged in. Default builds ship
+// `StubFaceDetector`, which finds nothing; `--features face-detect`
+// replaces it with `SkinToneDetector`, the classic skin-color heuristic:
+//
+// 1. Skin mask: pixels whose chroma lies in the skin cluster of YCbCr
+//    (Cb 77-127, Cr 133-173, Chai and Ngan 1999). Skin tones differ mostly
+//    in luma, so one chroma box covers light and dark skin alike.
+// 2. Candidates: 4-connected regions of the mask.
+// 3. Filter: regions at least `min_size` pixels on each side, 0.8-2.2
+//    times as tall as wide (a face, maybe with some neck), and filling at
+//    least 40% of their bounding box, which drops thin skin-colored strokes.
+//
+// Bare arms, wood and sand can pass the same tests. For privacy blurring
+// a false positive is the safer mistake.
+
+#[cfg(feature = "face-detect")]
+use std::ops::RangeInclusive;
+
+use super::error::ProcessError;
+#[cfg(feature = "face-detect")]
+use super::image::Pixel;
+use super::image::{BBox, Image};
+use super::transforms::{GaussianBlur, SubRegion, Transformation};
+
+#[cfg(feature = "face-detect")]
+const SKIN_CB: RangeInclusive<f32> = 77.0..=127.0;
+#[cfg(feature = "face-detect")]
+const SKIN_CR: RangeInclusive<f32> = 133.0..=173.0;
+#[cfg(feature = "face-detect")]
+const FACE_ASPECT: RangeInclusive<f32> = 0.8..=2.2;
+#[cfg(feature = "face-detect")]
+const MIN_FILL: f32 = 0.4;
+
+// Smallest face side, in pixels, that `faceblur` looks for by default.
+#[cfg(feature = "face-detect")]
+pub const DEFAULT_MIN_FACE_SIZE: i32 = 16;
+
+// Finds face rectangles for `FaceBlur`.
+pub trait FaceDetector {
+    fn detect(&self, image: &Image) -> Vec<BBox>;
+}
+
+// Placeholder detector that never finds anything, used by `faceblur` when
+// the crate is built without `face-detect`.
+#[cfg(not(feature = "face-detect"))]
+pub struct StubFaceDetector;
+
+#[cfg(not(feature = "face-detect"))]
+impl FaceDetector for StubFaceDetector {
+    fn detect(&self, _image: &Image) -> Vec<BBox> {
+        Vec::new()
+    }
+}
+
+#[cfg(feature = "face-detect")]
+pub struct SkinToneDetector {
+    min_size: i32,
+}
+
+#[cfg(feature = "face-detect")]
+impl SkinToneDetector {
+    pub fn new(min_size: i32) -> Self {
+        Self { min_size }
+    }
+
+    fn is_skin(p: Pixel) -> bool {
+        let (_, cb, cr) = p.to_ycbcr();
+        SKIN_CB.contains(&cb) && SKIN_CR.contains(&cr)
+    }
+
+    fn is_face_shaped(&self, region: &BBox) -> bool {
+        let aspect = region.height as f32 / region.width as f32;
+        let fill = region.pixel_count as f32 / (region.width * region.height) as f32;
+
+        region.width >= self.min_size
+            && region.height >= self.min_size
+            && FACE_ASPECT.contains(&aspect)
+            && fill >= MIN_FILL
+    }
+}
+
+#[cfg(feature = "face-detect")]
+impl FaceDetector for SkinToneDetector {
+    fn detect(&self, image: &Image) -> Vec<BBox> {
+        let background = Pixel::new(0, 0, 0);
+        let skin = Pixel::new(255, 255, 255);
+        let mask = Image {
+            width: image.width,
+            height: image.height,
+            pixels: image
+                .pixels
+                .iter()
+                .map(|&p| if Self::is_skin(p) { skin } else { background })
+                .collect(),
+            metadata: image.metadata,
+        };
+
+        mask.connected_components(background, 0.0)
+            .into_iter()
+            .filter(|region| self.is_face_shaped(region))
+            .collect()
+    }
+}
+
+// Blurs every rectangle the detector reports, clipped to the image, using
+// `SubRegion` with a Gaussian blur of `blur_radius`.
+pub struct FaceBlur {
+    blur_radius: i32,
+    detector: Box<dyn FaceDetector>,
+}
+
+impl FaceBlur {
+    pub fn new(blur_radius: i32, detector: Box<dyn FaceDetector>) -> Self {
+        Self {
+            blur_radius,
+            detector,
+        }
+    }
+}
+
+impl Transformation for FaceBlur {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let mut result = Image {
+            width: image.width,
+            height: image.height,
+            pixels: image.pixels.clone(),
+            metadata: image.metadata,
+        };
+
+        for face in self.detector.detect(image) {
+            let x0 = face.x.clamp(0, image.width);
+            let y0 = face.y.clamp(0, image.height);
+            let x1 = (face.x + face.width).clamp(0, image.width);
+            let y1 = (face.y + face.height).clamp(0, image.height);
+            if x1 <= x0 || y1 <= y0 {
+                continue;
+            }
+
+            let blur = Box::new(GaussianBlur::with_radius(self.blur_radius));
+            result = SubRegion::new(x0, y0, x1 - x0, y1 - y0, blur).apply(&result)?;
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rustphoto::image::{Metadata, Pixel};
+
+    #[cfg(feature = "face-detect")]
+    const SKIN: Pixel = Pixel {
+        r: 224,
+        g: 172,
+        b: 140,
+    };
+    #[cfg(feature = "face-detect")]
+    const BACKDROP: Pixel = Pixel {
+        r: 40,
+        g: 60,
+        b: 200,
+    };
+
+    fn image(width: i32, height: i32, f: impl Fn(i32, i32) -> Pixel) -> Image {
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(f(x, y));
+            }
+        }
+
+        Image {
+            width,
+            height,
+            pixels,
+            metadata: Metadata::default(),
+        }
+    }
+
+    struct FixedDetector(BBox);
+
+    impl FaceDetector for FixedDetector {
+        fn detect(&self, _image: &Image) -> Vec<BBox> {
+            vec![self.0]
+        }
+    }
+
+    #[test]
+    fn only_the_detected_rectangle_is_blurred() {
+        let stripes = image(20, 20, |x, _| {
+            if x % 2 == 0 {
+                Pixel::new(255, 255, 255)
+            } else {
+                Pixel::new(0, 0, 0)
+            }
+        });
+        let face = BBox {
+            x: 4,
+            y: 4,
+            width: 8,
+            height: 8,
+            pixel_count: 64,
+        };
+
+        let output = FaceBlur::new(2, Box::new(FixedDetector(face)))
+            .apply(&stripes)
+            .unwrap();
+
+        for y in 0..20 {
+            for x in 0..20 {
+                let index = (y * 20 + x) as usize;
+                let inside = (4..12).contains(&x) && (4..12).contains(&y);
+                let (before, after) = (stripes.pixels[index].r, output.pixels[index].r);
+                if inside {
+                    assert!(after > 0 && after < 255, "({}, {}) not blurred", x, y);
+                } else {
+                    assert_eq!(before, after, "({}, {}) changed", x, y);
+                }
+            }
+        }
+    }
+
+    #[cfg(not(feature = "face-detect"))]
+    #[test]
+    fn stub_detector_leaves_the_image_untouched() {
+        let stripes = image(8, 8, |x, _| Pixel::new((x * 30) as u8, 0, 0));
+        let output = FaceBlur::new(3, Box::new(StubFaceDetector))
+            .apply(&stripes)
+            .unwrap();
+
+        assert!(
+            stripes
+                .pixels
+                .iter()
+                .zip(&output.pixels)
+                .all(|(a, b)| a.r == b.r)
+        );
+    }
+
+    #[cfg(feature = "face-detect")]
+    #[test]
+    fn skin_tone_detector_finds_a_face_shaped_region() {
+        // A 21x27 skin-colored oval, a small skin spot and a thin skin stroke
+        let scene = image(60, 40, |x, y| {
+            let (dx, dy) = ((x - 15) as f32 / 10.0, (y - 16) as f32 / 13.0);
+            let oval = dx * dx + dy * dy <= 1.0;
+            let spot = (40..44).contains(&x) && (5..9).contains(&y);
+            let stroke = (35..58).contains(&x) && y == 30;
+            if oval || spot || stroke {
+                SKIN
+            } else {
+                BACKDROP
+            }
+        });
+
+        let faces = SkinToneDetector::new(DEFAULT_MIN_FACE_SIZE).detect(&scene);
+        assert_eq!(faces.len(), 1);
+        let face = &faces[0];
+        assert_eq!((face.x, face.y), (5, 3));
+        assert_eq!((face.width, face.height), (21, 27));
+    }
+
+    #[cfg(feature = "face-detect")]
+    #[test]
+    fn skin_tone_detector_ignores_other_colors() {
+        let scene = image(30, 30, |x, y| {
+            if (5..25).contains(&x) && (3..27).contains(&y) {
+                Pixel::new(60, 180, 70)
+            } else {
+                BACKDROP
+            }
+        });
+
+        assert!(SkinToneDetector::new(8).detect(&scene).is_empty());
+    }
+}
@@ -3,16 +3,35 @@
 // # Data Structures
 //
 // - `Pixel`: RGB color representation with 8-bit channels (0-255).
+//   Provides HSL conversions for hue/saturation/lightness-based operations
+//   and CIE Lab conversions (sRGB, D65 white point) for perceptual ones,
+//   plus YCbCr for separating luma from chroma.
 //
 // - `Image`: Contains width, height (i32), and a flat pixel array.
 //   Pixels are stored in row-major order: `pixels[y * width + x]`.
 //
+// - `Metadata`: What an image carries over from its file besides pixels.
+//   Transformations pass it through unchanged unless they act on it.
+//
+// - `BBox`: Bounding box and pixel count of a connected component.
+//
+// - `SourceColor`: Color model of a file before conversion to RGB.
+//
 // # Type Conversions
 //
 // - Loading: `u32` (image crate) → `i32` (internal)
 // - Saving: `i32` (internal) → `u32` (image crate)
 
 use super::error::ProcessError;
+use image::{ExtendedColorType, ImageDecoder};
+use std::collections::HashSet;
+
+// Counting stops here to bound memory; `unique_colors` returning this value
+// means "at least this many".
+pub const UNIQUE_COLORS_LIMIT: usize = 1 << 20;
+
+// Default `to_ascii` ramp for light text on a dark terminal.
+pub const ASCII_CHARSET: &str = " .:-=+*#%@";
 
 #[derive(Debug, Clone, Copy)]
 pub struct Pixel {
@@ -41,33 +60,797 @@ impl Pixel {
             b: (hex & 0xFF) as u8,
         }
     }
+
+    // Euclidean distance between RGB triples (0-441).
+    pub fn distance(self, other: Pixel) -> f32 {
+        let dr = self.r as f32 - other.r as f32;
+        let dg = self.g as f32 - other.g as f32;
+        let db = self.b as f32 - other.b as f32;
+        (dr * dr + dg * dg + db * db).sqrt()
+    }
+
+    // Rec. 601 luma in the 0-255 range.
+    pub fn luma(self) -> f32 {
+        0.299 * self.r as f32 + 0.587 * self.g as f32 + 0.114 * self.b as f32
+    }
+
+    // Returns (hue, saturation, lightness) with hue in degrees [0, 360)
+    // and saturation/lightness in [0, 1].
+    pub fn to_hsl(self) -> (f32, f32, f32) {
+        let r = self.r as f32 / 255.0;
+        let g = self.g as f32 / 255.0;
+        let b = self.b as f32 / 255.0;
+
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let l = (max + min) / 2.0;
+        let delta = max - min;
+
+        if delta == 0.0 {
+            return (0.0, 0.0, l);
+        }
+
+        let s = delta / (1.0 - (2.0 * l - 1.0).abs());
+
+        let h = if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+
+        (h, s, l)
+    }
+
+    pub fn from_hsl(h: f32, s: f32, l: f32) -> Self {
+        let h = h.rem_euclid(360.0);
+        let s = s.clamp(0.0, 1.0);
+        let l = l.clamp(0.0, 1.0);
+
+        let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        let x = c * (1.0 - ((h / 60.0).rem_euclid(2.0) - 1.0).abs());
+        let m = l - c / 2.0;
+
+        let (r, g, b) = match (h / 60.0) as i32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+
+        Self::from_f32(
+            ((r + m) * 255.0).round(),
+            ((g + m) * 255.0).round(),
+            ((b + m) * 255.0).round(),
+        )
+    }
+
+    // Returns (L, a, b) with L in [0, 100].
+    pub fn to_lab(self) -> (f32, f32, f32) {
+        let r = srgb_to_linear(self.r as f32 / 255.0);
+        let g = srgb_to_linear(self.g as f32 / 255.0);
+        let b = srgb_to_linear(self.b as f32 / 255.0);
+
+        let x = 0.4124564 * r + 0.3575761 * g + 0.1804375 * b;
+        let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+        let z = 0.0193339 * r + 0.119192 * g + 0.9503041 * b;
+
+        let fx = lab_f(x / WHITE_X);
+        let fy = lab_f(y / WHITE_Y);
+        let fz = lab_f(z / WHITE_Z);
+
+        (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+    }
+
+    pub fn from_lab(l: f32, a: f32, b: f32) -> Self {
+        let fy = (l + 16.0) / 116.0;
+        let fx = fy + a / 500.0;
+        let fz = fy - b / 200.0;
+
+        let x = WHITE_X * lab_f_inv(fx);
+        let y = WHITE_Y * lab_f_inv(fy);
+        let z = WHITE_Z * lab_f_inv(fz);
+
+        let r = 3.2404542 * x - 1.5371385 * y - 0.4985314 * z;
+        let g = -0.969266 * x + 1.8760108 * y + 0.041556 * z;
+        let b = 0.0556434 * x - 0.2040259 * y + 1.0572252 * z;
+
+        let encode = |v: f32| (linear_to_srgb(v.clamp(0.0, 1.0)) * 255.0).round();
+        Self::from_f32(encode(r), encode(g), encode(b))
+    }
+
+    // Full-range BT.601 YCbCr (as in JPEG): Y is luma, Cb and Cr are
+    // centered on 128.
+    pub fn to_ycbcr(self) -> (f32, f32, f32) {
+        let (r, g, b) = (self.r as f32, self.g as f32, self.b as f32);
+        let y = 0.299 * r + 0.587 * g + 0.114 * b;
+        let cb = 128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b;
+        let cr = 128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b;
+        (y, cb, cr)
+    }
+
+    pub fn from_ycbcr(y: f32, cb: f32, cr: f32) -> Self {
+        let r = y + 1.402 * (cr - 128.0);
+        let g = y - 0.344136 * (cb - 128.0) - 0.714136 * (cr - 128.0);
+        let b = y + 1.772 * (cb - 128.0);
+        Self::from_f32(r.round(), g.round(), b.round())
+    }
+
+    // Naive CMYK to RGB with ink amounts in 0-1: R = 255 * (1 - C) * (1 - K),
+    // and likewise G from M and B from Y. No ICC profile is applied.
+    pub fn from_cmyk(c: f32, m: f32, y: f32, k: f32) -> Self {
+        let channel = |ink: f32| (255.0 * (1.0 - ink) * (1.0 - k)).round();
+        Self::from_f32(channel(c), channel(m), channel(y))
+    }
+
+    // CIE76 color difference: Euclidean distance in Lab space.
+    pub fn delta_e(self, other: Pixel) -> f32 {
+        let (l1, a1, b1) = self.to_lab();
+        let (l2, a2, b2) = other.to_lab();
+        ((l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)).sqrt()
+    }
+}
+
+// D65 reference white in XYZ
+const WHITE_X: f32 = 0.95047;
+const WHITE_Y: f32 = 1.0;
+const WHITE_Z: f32 = 1.08883;
+
+pub(crate) fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn lab_f(t: f32) -> f32 {
+    let delta: f32 = 6.0 / 29.0;
+    if t > delta.powi(3) {
+        t.cbrt()
+    } else {
+        t / (3.0 * delta * delta) + 4.0 / 29.0
+    }
+}
+
+fn lab_f_inv(t: f32) -> f32 {
+    let delta: f32 = 6.0 / 29.0;
+    if t > delta {
+        t.powi(3)
+    } else {
+        3.0 * delta * delta * (t - 4.0 / 29.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BBox {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub pixel_count: i32,
+}
+
+fn find_root(parents: &mut [usize], mut label: usize) -> usize {
+    while parents[label] != label {
+        parents[label] = parents[parents[label]];
+        label = parents[label];
+    }
+    label
+}
+
+// Color model a file was stored in. Everything is converted to 8-bit RGB
+// on load; this records what the conversion started from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourceColor {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    Rgba,
+    Cmyk,
+    Other,
+}
+
+impl SourceColor {
+    fn from_extended(color: ExtendedColorType) -> Self {
+        use ExtendedColorType::*;
+
+        match color {
+            L1 | L2 | L4 | L8 | L16 => SourceColor::Gray,
+            La1 | La2 | La4 | La8 | La16 => SourceColor::GrayAlpha,
+            Rgb1 | Rgb2 | Rgb4 | Rgb8 | Rgb16 | Rgb32F | Bgr8 => SourceColor::Rgb,
+            Rgba1 | Rgba2 | Rgba4 | Rgba8 | Rgba16 | Rgba32F | Bgra8 => SourceColor::Rgba,
+            Cmyk8 | Cmyk16 => SourceColor::Cmyk,
+            _ => SourceColor::Other,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SourceColor::Gray => "grayscale",
+            SourceColor::GrayAlpha => "grayscale with alpha",
+            SourceColor::Rgb => "RGB",
+            SourceColor::Rgba => "RGBA",
+            SourceColor::Cmyk => "CMYK",
+            SourceColor::Other => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Metadata {
+    // EXIF orientation tag (1-8); 1 means the pixels are already upright.
+    pub(crate) orientation: u8,
+    // Color model of the file the pixels were decoded from.
+    pub(crate) source_color: SourceColor,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Self {
+            orientation: 1,
+            source_color: SourceColor::Rgb,
+        }
+    }
 }
 
 pub struct Image {
     pub(crate) width: i32,
     pub(crate) height: i32,
     pub(crate) pixels: Vec<Pixel>,
+    pub(crate) metadata: Metadata,
 }
 
 impl Image {
     pub fn load(path: &str) -> Result<Self, ProcessError> {
-        let img = image::open(path).map_err(|e| ProcessError::ImageLoad {
+        let load_error = |e: image::ImageError| ProcessError::ImageLoad {
             path: path.to_string(),
             source: Box::new(e),
-        })?;
-        let rgb = img.to_rgb8();
-        let (width, height) = rgb.dimensions();
+        };
+
+        let bytes = std::fs::read(path).map_err(|e| load_error(e.into()))?;
+        Self::decode(&bytes).map_err(load_error)
+    }
+
+    // Decodes an in-memory encoded image, guessing the format from its header.
+    pub fn load_from_bytes(bytes: &[u8]) -> Result<Self, ProcessError> {
+        Self::decode(bytes).map_err(|e| ProcessError::ImageDecode(Box::new(e)))
+    }
 
-        let pixels: Vec<Pixel> = rgb.pixels().map(|p| Pixel::new(p[0], p[1], p[2])).collect();
+    // Decodes the pixels and keeps the header fields `Metadata` tracks.
+    fn decode(bytes: &[u8]) -> image::ImageResult<Self> {
+        let reader = image::ImageReader::new(std::io::Cursor::new(bytes)).with_guessed_format()?;
+        let format = reader.format();
+
+        let mut decoder = reader.into_decoder()?;
+        let metadata = Metadata {
+            orientation: decoder.orientation()?.to_exif(),
+            source_color: SourceColor::from_extended(decoder.original_color_type()),
+        };
+
+        if metadata.source_color == SourceColor::Cmyk && format == Some(image::ImageFormat::Tiff) {
+            return Self::decode_cmyk_tiff(bytes, metadata);
+        }
+
+        let img = image::DynamicImage::from_decoder(decoder)?;
+        Ok(Self::from_dynamic(img, metadata))
+    }
+
+    // Reads the raw ink samples of a CMYK TIFF (0 = no ink, 8 or 16 bits)
+    // and converts them with `Pixel::from_cmyk`.
+    fn decode_cmyk_tiff(bytes: &[u8], metadata: Metadata) -> image::ImageResult<Self> {
+        let tiff_error = |e: tiff::TiffError| {
+            image::ImageError::Decoding(image::error::DecodingError::new(
+                image::ImageFormat::Tiff.into(),
+                e,
+            ))
+        };
+
+        let mut decoder =
+            tiff::decoder::Decoder::new(std::io::Cursor::new(bytes)).map_err(tiff_error)?;
+        let (width, height) = decoder.dimensions().map_err(tiff_error)?;
+
+        let inks: Vec<f32> = match decoder.read_image().map_err(tiff_error)? {
+            tiff::decoder::DecodingResult::U8(samples) => {
+                samples.iter().map(|&s| s as f32 / 255.0).collect()
+            }
+            tiff::decoder::DecodingResult::U16(samples) => {
+                samples.iter().map(|&s| s as f32 / 65535.0).collect()
+            }
+            _ => {
+                return Err(image::ImageError::Unsupported(
+                    image::error::UnsupportedError::from_format_and_kind(
+                        image::ImageFormat::Tiff.into(),
+                        image::error::UnsupportedErrorKind::Color(ExtendedColorType::Cmyk8),
+                    ),
+                ));
+            }
+        };
 
         Ok(Self {
             width: width as i32,
             height: height as i32,
-            pixels,
+            pixels: inks
+                .chunks_exact(4)
+                .map(|ink| Pixel::from_cmyk(ink[0], ink[1], ink[2], ink[3]))
+                .collect(),
+            metadata,
         })
     }
 
+    // Gray sources are expanded by copying luma into all three channels;
+    // everything else goes through the image crate's RGB conversion. CMYK
+    // TIFFs are handled by `decode_cmyk_tiff` instead. CMYK JPEGs are left to
+    // the JPEG decoder, which needs the Adobe marker it parses to tell
+    // inverted CMYK from YCCK.
+    fn from_dynamic(img: image::DynamicImage, metadata: Metadata) -> Self {
+        let (width, height) = (img.width(), img.height());
+
+        let pixels: Vec<Pixel> = match img {
+            image::DynamicImage::ImageLuma8(gray) => gray
+                .pixels()
+                .map(|p| Pixel::new(p[0], p[0], p[0]))
+                .collect(),
+            image::DynamicImage::ImageLumaA8(gray) => gray
+                .pixels()
+                .map(|p| Pixel::new(p[0], p[0], p[0]))
+                .collect(),
+            other => other
+                .to_rgb8()
+                .pixels()
+                .map(|p| Pixel::new(p[0], p[1], p[2]))
+                .collect(),
+        };
+
+        Self {
+            width: width as i32,
+            height: height as i32,
+            pixels,
+            metadata,
+        }
+    }
+
+    // Reads only the header, without decoding pixel data.
+    pub fn dimensions(path: &str) -> Result<(i32, i32), ProcessError> {
+        let (width, height) =
+            image::image_dimensions(path).map_err(|e| ProcessError::ImageLoad {
+                path: path.to_string(),
+                source: Box::new(e),
+            })?;
+
+        Ok((width as i32, height as i32))
+    }
+
     pub fn save(&self, path: &str) -> Result<(), ProcessError> {
+        let buffer = self.to_rgb_buffer();
+
+        buffer.save(path).map_err(|e| ProcessError::FileWrite {
+            path: path.to_string(),
+            source: Box::new(e),
+        })?;
+
+        Ok(())
+    }
+
+    // Encodes the image in memory; `format` is a file extension such as "png" or "jpg".
+    pub fn to_bytes(&self, format: &str) -> Result<Vec<u8>, ProcessError> {
+        let image_format = image::ImageFormat::from_extension(format).ok_or_else(|| {
+            ProcessError::InvalidInput {
+                operation: "encode".to_string(),
+                details: format!("unsupported format '{}'", format),
+            }
+        })?;
+
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        self.to_rgb_buffer()
+            .write_to(&mut bytes, image_format)
+            .map_err(|e| ProcessError::ImageEncoding {
+                format: format.to_string(),
+                source: Box::new(e),
+            })?;
+
+        Ok(bytes.into_inner())
+    }
+
+    // Number of distinct RGB colors, capped at `UNIQUE_COLORS_LIMIT`.
+    pub fn unique_colors(&self) -> usize {
+        let mut colors = HashSet::new();
+
+        for p in &self.pixels {
+            colors.insert((p.r, p.g, p.b));
+            if colors.len() >= UNIQUE_COLORS_LIMIT {
+                break;
+            }
+        }
+
+        colors.len()
+    }
+
+    // Labels 4-connected regions of pixels farther than `tolerance` from
+    // `background` using two-pass union-find labeling.
+    pub fn connected_components(&self, background: Pixel, tolerance: f32) -> Vec<BBox> {
+        let mut labels = vec![0usize; self.pixels.len()];
+        // Label 0 is background; parents[i] is the union-find parent of label i.
+        let mut parents = vec![0usize];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let idx = (y * self.width + x) as usize;
+                if self.pixels[idx].distance(background) <= tolerance {
+                    continue;
+                }
+
+                let left = if x > 0 { labels[idx - 1] } else { 0 };
+                let up = if y > 0 {
+                    labels[idx - self.width as usize]
+                } else {
+                    0
+                };
+
+                labels[idx] = match (left, up) {
+                    (0, 0) => {
+                        parents.push(parents.len());
+                        parents.len() - 1
+                    }
+                    (label, 0) | (0, label) => label,
+                    (left, up) => {
+                        let left_root = find_root(&mut parents, left);
+                        let up_root = find_root(&mut parents, up);
+                        let root = left_root.min(up_root);
+                        parents[left_root] = root;
+                        parents[up_root] = root;
+                        root
+                    }
+                };
+            }
+        }
+
+        let mut boxes: Vec<BBox> = Vec::new();
+        let mut box_index = vec![usize::MAX; parents.len()];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let label = labels[(y * self.width + x) as usize];
+                if label == 0 {
+                    continue;
+                }
+
+                let root = find_root(&mut parents, label);
+                if box_index[root] == usize::MAX {
+                    box_index[root] = boxes.len();
+                    boxes.push(BBox {
+                        x,
+                        y,
+                        width: 1,
+                        height: 1,
+                        pixel_count: 0,
+                    });
+                }
+
+                let bbox = &mut boxes[box_index[root]];
+                let right = (bbox.x + bbox.width).max(x + 1);
+                let bottom = (bbox.y + bbox.height).max(y + 1);
+                bbox.x = bbox.x.min(x);
+                bbox.y = bbox.y.min(y);
+                bbox.width = right - bbox.x;
+                bbox.height = bottom - bbox.y;
+                bbox.pixel_count += 1;
+            }
+        }
+
+        boxes
+    }
+
+    // Cuts the image into a `cols` x `rows` grid, returned row-major. Tiles
+    // are ceil(width / cols) x ceil(height / rows); the last column and row
+    // take whatever remains, so they may be smaller, and rounding can leave
+    // fewer columns or rows than requested (e.g. 5 pixels in 4 columns gives
+    // tiles of 2, 2 and 1). `cols` and `rows` are clamped to the image size.
+    pub fn split_grid(&self, cols: i32, rows: i32) -> Vec<Image> {
+        let cols = cols.clamp(1, self.width.max(1));
+        let rows = rows.clamp(1, self.height.max(1));
+        let tile_width = (self.width as u32).div_ceil(cols as u32).max(1) as i32;
+        let tile_height = (self.height as u32).div_ceil(rows as u32).max(1) as i32;
+
+        let mut tiles = Vec::with_capacity((cols * rows) as usize);
+        for y0 in (0..self.height).step_by(tile_height as usize) {
+            for x0 in (0..self.width).step_by(tile_width as usize) {
+                let width = tile_width.min(self.width - x0);
+                let height = tile_height.min(self.height - y0);
+
+                let mut pixels = Vec::with_capacity((width * height) as usize);
+                for y in y0..y0 + height {
+                    let start = (y * self.width + x0) as usize;
+                    pixels.extend_from_slice(&self.pixels[start..start + width as usize]);
+                }
+
+                tiles.push(Image {
+                    width,
+                    height,
+                    pixels,
+                    metadata: self.metadata,
+                });
+            }
+        }
+
+        tiles
+    }
+
+    // Inverse of `split_grid`: lays `tiles` out row-major, `cols` per row.
+    // Tiles in a row must share a height, and every row must add up to the
+    // same width.
+    pub fn stitch_grid(tiles: &[Image], cols: i32) -> Result<Image, ProcessError> {
+        let invalid = |details: String| ProcessError::InvalidInput {
+            operation: "stitch".to_string(),
+            details,
+        };
+
+        if tiles.is_empty() || cols < 1 {
+            return Err(invalid(format!(
+                "need at least one tile and one column, got {} tiles in {} columns",
+                tiles.len(),
+                cols
+            )));
+        }
+
+        let rows: Vec<&[Image]> = tiles.chunks(cols as usize).collect();
+        let width: i32 = rows[0].iter().map(|tile| tile.width).sum();
+
+        for (r, row) in rows.iter().enumerate() {
+            let row_width: i32 = row.iter().map(|tile| tile.width).sum();
+            if row_width != width {
+                return Err(invalid(format!(
+                    "row {} is {} pixels wide, expected {}",
+                    r, row_width, width
+                )));
+            }
+            if let Some(tile) = row.iter().find(|tile| tile.height != row[0].height) {
+                return Err(invalid(format!(
+                    "row {} mixes tile heights {} and {}",
+                    r, row[0].height, tile.height
+                )));
+            }
+        }
+
+        let height: i32 = rows.iter().map(|row| row[0].height).sum();
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for row in &rows {
+            for y in 0..row[0].height {
+                for tile in row.iter() {
+                    let start = (y * tile.width) as usize;
+                    pixels.extend_from_slice(&tile.pixels[start..start + tile.width as usize]);
+                }
+            }
+        }
+
+        Ok(Image {
+            width,
+            height,
+            pixels,
+            metadata: Metadata::default(),
+        })
+    }
+
+    // Builds a new image row by row: `f` gets each row index and its pixels
+    // and returns the new row, which must have the same width. With the
+    // `parallel` feature the rows are split into one contiguous band per
+    // available core, each processed on its own scoped thread.
+    //
+    // A horizontal flip, for example, is one line:
+    //
+    //     let flipped = image.process_rows(|_, row| row.iter().rev().copied().collect());
+    //
+    // The crate has no library target, so this cannot be a doctest; the
+    // `process_rows_reverses_each_row` unit test runs it instead.
+    pub fn process_rows<F>(&self, f: F) -> Image
+    where
+        F: Fn(i32, &[Pixel]) -> Vec<Pixel> + Sync,
+    {
+        let width = self.width.max(1) as usize;
+        let mut pixels = vec![Pixel::new(0, 0, 0); self.pixels.len()];
+
+        let process_band = |first_row: usize, band: &mut [Pixel]| {
+            for (i, out) in band.chunks_mut(width).enumerate() {
+                let y = first_row + i;
+                let row = f(y as i32, &self.pixels[y * width..(y + 1) * width]);
+                assert_eq!(row.len(), width, "process_rows: row {} changed width", y);
+                out.copy_from_slice(&row);
+            }
+        };
+
+        #[cfg(feature = "parallel")]
+        {
+            let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+            let band_rows = (self.height.max(1) as usize).div_ceil(threads);
+            std::thread::scope(|scope| {
+                for (i, band) in pixels.chunks_mut(band_rows * width).enumerate() {
+                    let process_band = &process_band;
+                    scope.spawn(move || process_band(i * band_rows, band));
+                }
+            });
+        }
+        #[cfg(not(feature = "parallel"))]
+        process_band(0, &mut pixels);
+
+        Image {
+            width: self.width,
+            height: self.height,
+            pixels,
+            metadata: self.metadata,
+        }
+    }
+
+    // Frequency-tuned saliency (Achanta et al. 2009): each pixel's Lab color,
+    // lightly smoothed with a 5-tap binomial filter to suppress noise and
+    // texture, is compared with the mean Lab color of the whole image. The
+    // distances are scaled so the most salient pixel is white.
+    pub fn saliency_map(&self) -> Image {
+        const TAPS: [f32; 5] = [1.0, 4.0, 6.0, 4.0, 1.0];
+
+        let lab: Vec<[f32; 3]> = self
+            .pixels
+            .iter()
+            .map(|p| {
+                let (l, a, b) = p.to_lab();
+                [l, a, b]
+            })
+            .collect();
+
+        let count = lab.len().max(1) as f32;
+        let mut mean = [0.0f32; 3];
+        for color in &lab {
+            for (m, c) in mean.iter_mut().zip(color) {
+                *m += c / count;
+            }
+        }
+
+        // Separable blur with edge clamping: horizontal pass, then vertical
+        let smooth = |src: &[[f32; 3]], dx: i32, dy: i32| -> Vec<[f32; 3]> {
+            let mut out = vec![[0.0f32; 3]; src.len()];
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    let mut sum = [0.0f32; 3];
+                    for (k, weight) in TAPS.iter().enumerate() {
+                        let offset = k as i32 - 2;
+                        let sx = (x + offset * dx).clamp(0, self.width - 1);
+                        let sy = (y + offset * dy).clamp(0, self.height - 1);
+                        let color = src[(sy * self.width + sx) as usize];
+                        for (s, c) in sum.iter_mut().zip(color) {
+                            *s += c * weight / 16.0;
+                        }
+                    }
+                    out[(y * self.width + x) as usize] = sum;
+                }
+            }
+            out
+        };
+        let blurred = smooth(&smooth(&lab, 1, 0), 0, 1);
+
+        let distances: Vec<f32> = blurred
+            .iter()
+            .map(|c| {
+                let (dl, da, db) = (c[0] - mean[0], c[1] - mean[1], c[2] - mean[2]);
+                (dl * dl + da * da + db * db).sqrt()
+            })
+            .collect();
+        let max = distances.iter().cloned().fold(0.0f32, f32::max);
+        // Below this the distances are float rounding, which a flat image
+        // would otherwise stretch to full white noise.
+        let scale = if max > 1e-3 { 255.0 / max } else { 0.0 };
+
+        let pixels = distances
+            .iter()
+            .map(|&d| {
+                let v = (d * scale).round();
+                Pixel::from_f32(v, v, v)
+            })
+            .collect();
+
+        Image {
+            width: self.width,
+            height: self.height,
+            pixels,
+            metadata: self.metadata,
+        }
+    }
+
+    // Draws the R, G and B histograms as filled curves on a dark background,
+    // `width` x `height`, with 0 on the left and 255 on the right. Each
+    // column shows the tallest bin it covers, heights are scaled so the
+    // largest bin fills the image, and overlapping curves add up toward
+    // white as in an editor's histogram panel.
+    pub fn histogram_image(&self, width: i32, height: i32) -> Image {
+        let (width, height) = (width.max(1), height.max(1));
+
+        let mut counts = [[0u32; 256]; 3];
+        for p in &self.pixels {
+            counts[0][p.r as usize] += 1;
+            counts[1][p.g as usize] += 1;
+            counts[2][p.b as usize] += 1;
+        }
+        let peak = counts.iter().flatten().copied().max().unwrap_or(0).max(1);
+
+        // Curve height in pixels per channel for each output column
+        let columns: Vec<[i32; 3]> = (0..width)
+            .map(|x| {
+                let first = (x * 256 / width) as usize;
+                let last = (((x + 1) * 256 / width) as usize).max(first + 1);
+                std::array::from_fn(|c| {
+                    let count = counts[c][first..last].iter().copied().max().unwrap_or(0);
+                    (count as f32 / peak as f32 * height as f32).round() as i32
+                })
+            })
+            .collect();
+
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            let level = height - y;
+            for column in &columns {
+                let [r, g, b] = column.map(|h| if h >= level { 200 } else { 0 });
+                pixels.push(Pixel::new(32 + r, 32 + g, 32 + b));
+            }
+        }
+
+        Image {
+            width,
+            height,
+            pixels,
+            metadata: self.metadata,
+        }
+    }
+
+    // Renders the image as text, `cols` characters wide. Rows are halved to
+    // match the ~2:1 cell shape of monospace fonts. Each cell's average luma
+    // picks a character from `charset`, which is ordered dark to light.
+    pub fn to_ascii(&self, cols: i32, charset: &str) -> String {
+        let chars: Vec<char> = charset.chars().collect();
+        if chars.is_empty() || cols <= 0 || self.width == 0 || self.height == 0 {
+            return String::new();
+        }
+
+        let cols = cols.min(self.width);
+        let cell_width = self.width as f32 / cols as f32;
+        let rows = ((self.height as f32 / (cell_width * 2.0)).round() as i32).clamp(1, self.height);
+        let cell_height = self.height as f32 / rows as f32;
+
+        let mut text = String::with_capacity(((cols + 1) * rows) as usize);
+        for row in 0..rows {
+            let y0 = (row as f32 * cell_height) as i32;
+            let y1 = (((row + 1) as f32 * cell_height) as i32).max(y0 + 1);
+
+            for col in 0..cols {
+                let x0 = (col as f32 * cell_width) as i32;
+                let x1 = (((col + 1) as f32 * cell_width) as i32).max(x0 + 1);
+
+                let mut sum = 0.0;
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        sum += self.pixels[(y * self.width + x) as usize].luma();
+                    }
+                }
+                let luma = sum / ((x1 - x0) * (y1 - y0)) as f32;
+
+                let index = (luma / 255.0 * (chars.len() - 1) as f32).round() as usize;
+                text.push(chars[index.min(chars.len() - 1)]);
+            }
+            text.push('\n');
+        }
+
+        text
+    }
+
+    pub(crate) fn to_rgb_buffer(&self) -> image::RgbImage {
         let mut buffer = image::RgbImage::new(self.width as u32, self.height as u32);
 
         for (i, pixel) in self.pixels.iter().enumerate() {
@@ -76,11 +859,433 @@ impl Image {
             buffer.put_pixel(x, y, image::Rgb([pixel.r, pixel.g, pixel.b]));
         }
 
-        buffer.save(path).map_err(|e| ProcessError::FileWrite {
-            path: path.to_string(),
-            source: Box::new(e),
-        })?;
+        buffer
+    }
+}
 
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: i32, height: i32, f: impl Fn(i32, i32) -> Pixel) -> Image {
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(f(x, y));
+            }
+        }
+
+        Image {
+            width,
+            height,
+            pixels,
+            metadata: Metadata::default(),
+        }
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("rustphoto-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn lab_round_trip_stays_within_one_level() {
+        for r in (0..=255).step_by(15) {
+            for g in (0..=255).step_by(15) {
+                for b in (0..=255).step_by(15) {
+                    let p = Pixel::new(r as u8, g as u8, b as u8);
+                    let (l, a, bb) = p.to_lab();
+                    let back = Pixel::from_lab(l, a, bb);
+                    assert!(
+                        (back.r as i32 - p.r as i32).abs() <= 1,
+                        "{:?} -> {:?}",
+                        p,
+                        back
+                    );
+                    assert!(
+                        (back.g as i32 - p.g as i32).abs() <= 1,
+                        "{:?} -> {:?}",
+                        p,
+                        back
+                    );
+                    assert!(
+                        (back.b as i32 - p.b as i32).abs() <= 1,
+                        "{:?} -> {:?}",
+                        p,
+                        back
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn similar_colors_have_a_small_delta_e() {
+        let a = Pixel::new(120, 60, 200);
+
+        assert!(a.delta_e(Pixel::new(122, 61, 198)) < 2.0);
+        assert!(a.delta_e(Pixel::new(20, 200, 60)) > 50.0);
+        assert_eq!(a.delta_e(a), 0.0);
+    }
+
+    #[test]
+    fn two_separate_shapes_give_two_components() {
+        let white = Pixel::new(255, 255, 255);
+        // A 2x2 square at (1, 1) and an L shape around (6, 4)
+        let shapes = image(10, 8, |x, y| {
+            let square = (1..3).contains(&x) && (1..3).contains(&y);
+            let l_shape = (x == 6 && (4..7).contains(&y)) || (y == 6 && (6..9).contains(&x));
+            if square || l_shape {
+                Pixel::new(0, 0, 0)
+            } else {
+                white
+            }
+        });
+
+        let mut boxes = shapes.connected_components(white, 10.0);
+        boxes.sort_by_key(|b| b.x);
+        assert_eq!(boxes.len(), 2);
+        assert_eq!(
+            (boxes[0].x, boxes[0].y, boxes[0].width, boxes[0].height),
+            (1, 1, 2, 2)
+        );
+        assert_eq!(boxes[0].pixel_count, 4);
+        assert_eq!(
+            (boxes[1].x, boxes[1].y, boxes[1].width, boxes[1].height),
+            (6, 4, 3, 3)
+        );
+        assert_eq!(boxes[1].pixel_count, 5);
+    }
+
+    #[test]
+    fn u_shape_merges_into_one_component() {
+        let white = Pixel::new(255, 255, 255);
+        let u_shape = image(5, 4, |x, y| {
+            if x == 0 || x == 4 || y == 3 {
+                Pixel::new(0, 0, 0)
+            } else {
+                white
+            }
+        });
+
+        assert_eq!(u_shape.connected_components(white, 0.0).len(), 1);
+    }
+
+    #[test]
+    fn dimensions_reads_the_header_size() {
+        let path = temp_path("dimensions.png");
+        image(7, 5, |x, y| Pixel::new(x as u8, y as u8, 0))
+            .save(&path)
+            .unwrap();
+
+        assert_eq!(Image::dimensions(&path).unwrap(), (7, 5));
+        std::fs::remove_file(&path).unwrap();
+        assert!(Image::dimensions(&path).is_err());
+    }
+
+    #[test]
+    fn load_reads_the_exif_orientation() {
+        use image::ImageEncoder;
+
+        // Big-endian TIFF header with one IFD entry: Orientation (0x0112) = 6
+        let exif = vec![
+            b'M', b'M', 0, 42, 0, 0, 0, 8, 0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, 0, 0, 0,
+            0,
+        ];
+        let mut bytes = Vec::new();
+        let mut encoder = image::codecs::png::PngEncoder::new(&mut bytes);
+        encoder.set_exif_metadata(exif).unwrap();
+        encoder
+            .write_image(&[0; 12], 2, 2, ExtendedColorType::Rgb8)
+            .unwrap();
+
+        let loaded = Image::load_from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.metadata.orientation, 6);
+
+        let plain = image(2, 2, |_, _| Pixel::new(0, 0, 0))
+            .to_bytes("png")
+            .unwrap();
+        assert_eq!(
+            Image::load_from_bytes(&plain).unwrap().metadata.orientation,
+            1
+        );
+    }
+
+    #[test]
+    fn unique_colors_counts_distinct_values() {
+        let quadrants = image(4, 4, |x, y| match (x < 2, y < 2) {
+            (true, true) => Pixel::new(255, 0, 0),
+            (false, true) => Pixel::new(0, 255, 0),
+            (true, false) => Pixel::new(0, 0, 255),
+            (false, false) => Pixel::new(255, 255, 255),
+        });
+        assert_eq!(quadrants.unique_colors(), 4);
+
+        let flat = image(3, 3, |_, _| Pixel::new(9, 9, 9));
+        assert_eq!(flat.unique_colors(), 1);
+    }
+
+    #[test]
+    fn bytes_round_trip_is_lossless_for_png() {
+        let original = image(5, 3, |x, y| Pixel::new((x * 50) as u8, (y * 80) as u8, 7));
+
+        let bytes = original.to_bytes("png").unwrap();
+        let decoded = Image::load_from_bytes(&bytes).unwrap();
+        assert_eq!((decoded.width, decoded.height), (5, 3));
+        for (a, b) in original.pixels.iter().zip(&decoded.pixels) {
+            assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+        }
+    }
+
+    #[test]
+    fn bytes_reject_unknown_formats_and_garbage() {
+        let original = image(2, 2, |_, _| Pixel::new(0, 0, 0));
+
+        assert!(original.to_bytes("nope").is_err());
+        assert!(Image::load_from_bytes(b"not an image").is_err());
+    }
+
+    #[test]
+    fn ascii_maps_black_and_white_to_the_charset_ends() {
+        let black = image(40, 40, |_, _| Pixel::new(0, 0, 0));
+        let white = image(40, 40, |_, _| Pixel::new(255, 255, 255));
+
+        let dark = black.to_ascii(10, "@%#*+=-:. ");
+        let light = white.to_ascii(10, "@%#*+=-:. ");
+
+        // 40x40 at 10 columns: 4-pixel cells, 8-pixel rows
+        assert_eq!(dark.lines().count(), 5);
+        for line in dark.lines() {
+            assert_eq!(line, "@".repeat(10));
+        }
+        for line in light.lines() {
+            assert_eq!(line, " ".repeat(10));
+        }
+        assert!(black.to_ascii(10, "").is_empty());
+    }
+
+    #[test]
+    fn grayscale_png_loads_as_equal_channels() {
+        let path = temp_path("gray.png");
+        image::GrayImage::from_fn(6, 4, |x, y| image::Luma([(x * 40 + y) as u8]))
+            .save(&path)
+            .unwrap();
+
+        let loaded = Image::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.metadata.source_color, SourceColor::Gray);
+        for (i, p) in loaded.pixels.iter().enumerate() {
+            let expected = ((i % 6) * 40 + i / 6) as u8;
+            assert_eq!((p.r, p.g, p.b), (expected, expected, expected));
+        }
+    }
+
+    #[test]
+    fn cmyk_converts_with_the_standard_formula() {
+        let rgb = |p: Pixel| (p.r, p.g, p.b);
+
+        assert_eq!(rgb(Pixel::from_cmyk(0.0, 0.0, 0.0, 0.0)), (255, 255, 255));
+        assert_eq!(rgb(Pixel::from_cmyk(0.0, 0.0, 0.0, 1.0)), (0, 0, 0));
+        assert_eq!(rgb(Pixel::from_cmyk(1.0, 0.0, 1.0, 0.0)), (0, 255, 0));
+        // 255 * (1 - 0.2) * (1 - 0.5) = 102, 255 * 0.5 = 127.5, 255 * 0.1 * 0.5 = 12.75
+        assert_eq!(rgb(Pixel::from_cmyk(0.2, 0.0, 0.9, 0.5)), (102, 128, 13));
+    }
+
+    #[test]
+    fn cmyk_tiff_is_converted_explicitly() {
+        let inks: [[u8; 4]; 3] = [[0, 0, 0, 0], [255, 0, 255, 0], [51, 0, 230, 128]];
+        let samples: Vec<u8> = inks.iter().flatten().copied().collect();
+
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        tiff::encoder::TiffEncoder::new(&mut bytes)
+            .unwrap()
+            .write_image::<tiff::encoder::colortype::CMYK8>(3, 1, &samples)
+            .unwrap();
+
+        let loaded = Image::load_from_bytes(bytes.get_ref()).unwrap();
+        assert_eq!(loaded.metadata.source_color, SourceColor::Cmyk);
+        for (p, ink) in loaded.pixels.iter().zip(&inks) {
+            let [c, m, y, k] = ink.map(|v| v as f32 / 255.0);
+            let expected = Pixel::from_cmyk(c, m, y, k);
+            assert_eq!((p.r, p.g, p.b), (expected.r, expected.g, expected.b));
+        }
+        assert_eq!((loaded.pixels[1].r, loaded.pixels[1].g), (0, 255));
+    }
+
+    #[test]
+    fn saliency_is_high_on_a_lone_object() {
+        let scene = image(30, 30, |x, y| {
+            if (12..18).contains(&x) && (12..18).contains(&y) {
+                Pixel::new(230, 20, 20)
+            } else {
+                Pixel::new(90, 110, 100)
+            }
+        });
+
+        let map = scene.saliency_map();
+        let at = |x: i32, y: i32| map.pixels[(y * 30 + x) as usize];
+        let center = at(15, 15);
+        assert!(center.r > 200 && center.r == center.g && center.g == center.b);
+        for (x, y) in [(0, 0), (29, 0), (3, 25), (27, 27)] {
+            assert!(at(x, y).r < 30, "({}, {}) = {}", x, y, at(x, y).r);
+        }
+
+        let flat = image(8, 8, |_, _| Pixel::new(50, 50, 50)).saliency_map();
+        assert!(flat.pixels.iter().all(|p| p.r == 0));
+    }
+
+    #[test]
+    fn ycbcr_round_trips() {
+        for p in [
+            Pixel::new(0, 0, 0),
+            Pixel::new(255, 255, 255),
+            Pixel::new(224, 172, 140),
+            Pixel::new(10, 200, 90),
+        ] {
+            let (y, cb, cr) = p.to_ycbcr();
+            let back = Pixel::from_ycbcr(y, cb, cr);
+            assert_eq!((back.r, back.g, back.b), (p.r, p.g, p.b));
+        }
+
+        let (_, cb, cr) = Pixel::new(128, 128, 128).to_ycbcr();
+        assert!((cb - 128.0).abs() < 0.5 && (cr - 128.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn split_grid_tiles_reassemble_to_the_original() {
+        let source = image(4, 4, |x, y| Pixel::new((y * 4 + x) as u8, 0, 0));
+        let tiles = source.split_grid(2, 2);
+
+        assert_eq!(tiles.len(), 4);
+        assert!(tiles.iter().all(|t| (t.width, t.height) == (2, 2)));
+        for (i, tile) in tiles.iter().enumerate() {
+            let (x0, y0) = ((i % 2) as i32 * 2, (i / 2) as i32 * 2);
+            for y in 0..2 {
+                for x in 0..2 {
+                    let expected = source.pixels[((y0 + y) * 4 + x0 + x) as usize].r;
+                    assert_eq!(tile.pixels[(y * 2 + x) as usize].r, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn split_grid_keeps_partial_edge_tiles() {
+        let source = image(5, 3, |x, _| Pixel::new(x as u8, 0, 0));
+        let sizes: Vec<(i32, i32)> = source
+            .split_grid(2, 2)
+            .iter()
+            .map(|t| (t.width, t.height))
+            .collect();
+
+        assert_eq!(sizes, vec![(3, 2), (2, 2), (3, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn stitch_grid_lays_tiles_out_row_major() {
+        let tiles: Vec<Image> = (0..4)
+            .map(|i| image(2, 2, move |_, _| Pixel::new(i * 10, 0, 0)))
+            .collect();
+        let stitched = Image::stitch_grid(&tiles, 2).unwrap();
+
+        assert_eq!((stitched.width, stitched.height), (4, 4));
+        let reds: Vec<u8> = stitched.pixels.iter().map(|p| p.r).collect();
+        assert_eq!(
+            reds,
+            vec![0, 0, 10, 10, 0, 0, 10, 10, 20, 20, 30, 30, 20, 20, 30, 30]
+        );
+
+        let source = image(5, 3, |x, y| Pixel::new(x as u8, y as u8, 0));
+        let round_trip = Image::stitch_grid(&source.split_grid(2, 2), 2).unwrap();
+        let pixels =
+            |img: &Image| -> Vec<(u8, u8)> { img.pixels.iter().map(|p| (p.r, p.g)).collect() };
+        assert_eq!(pixels(&round_trip), pixels(&source));
+    }
+
+    #[test]
+    fn stitch_grid_rejects_inconsistent_tiles() {
+        let black = |w, h| image(w, h, |_, _| Pixel::new(0, 0, 0));
+
+        // Mixed heights within a row
+        assert!(Image::stitch_grid(&[black(2, 2), black(2, 3)], 2).is_err());
+        // Rows of different widths
+        assert!(
+            Image::stitch_grid(&[black(2, 2), black(2, 2), black(3, 2), black(2, 2)], 2).is_err()
+        );
+        assert!(Image::stitch_grid(&[], 2).is_err());
+        assert!(Image::stitch_grid(&[black(2, 2)], 0).is_err());
+    }
+
+    #[test]
+    fn process_rows_reverses_each_row() {
+        let source = image(5, 3, |x, y| Pixel::new(x as u8, y as u8, 0));
+        let flipped = source.process_rows(|_, row| row.iter().rev().copied().collect());
+
+        assert_eq!((flipped.width, flipped.height), (5, 3));
+        for y in 0..3 {
+            for x in 0..5 {
+                let p = flipped.pixels[(y * 5 + x) as usize];
+                assert_eq!((p.r, p.g), ((4 - x) as u8, y as u8));
+            }
+        }
+    }
+
+    #[test]
+    fn process_rows_passes_every_row_index_once() {
+        // Taller than any core count, so bands split mid-image with `parallel`
+        let source = image(3, 97, |_, _| Pixel::new(0, 0, 0));
+        let tagged = source.process_rows(|y, row| vec![Pixel::new(y as u8, 0, 0); row.len()]);
+
+        for (i, p) in tagged.pixels.iter().enumerate() {
+            assert_eq!(p.r as usize, i / 3);
+        }
+        let empty = image(0, 0, |_, _| Pixel::new(0, 0, 0));
+        assert!(empty.process_rows(|_, row| row.to_vec()).pixels.is_empty());
+    }
+
+    // With `parallel` the panic surfaces as "a scoped thread panicked"
+    #[test]
+    #[should_panic]
+    fn process_rows_rejects_a_row_of_the_wrong_width() {
+        let source = image(4, 2, |_, _| Pixel::new(0, 0, 0));
+        source.process_rows(|_, row| row[1..].to_vec());
+    }
+
+    #[test]
+    fn histogram_image_spikes_at_the_channel_values() {
+        let red = image(10, 10, |_, _| Pixel::new(255, 0, 0));
+        let hist = red.histogram_image(128, 50);
+        assert_eq!((hist.width, hist.height), (128, 50));
+
+        let at = |x: i32, y: i32| {
+            let p = hist.pixels[(y * 128 + x) as usize];
+            (p.r, p.g, p.b)
+        };
+        // Red peaks at 255 on the right, green and blue at 0 on the left
+        assert_eq!(at(127, 0), (232, 32, 32));
+        assert_eq!(at(0, 0), (32, 232, 232));
+        assert_eq!(at(64, 49), (32, 32, 32));
+    }
+
+    #[test]
+    fn histogram_image_scales_bins_to_the_tallest() {
+        // Three quarters at 0 and one quarter at 255 in every channel
+        let split = image(4, 4, |x, _| {
+            let v = if x == 3 { 255 } else { 0 };
+            Pixel::new(v, v, v)
+        });
+        let hist = split.histogram_image(256, 40);
+
+        let lit_rows = |x: i32| {
+            (0..40)
+                .filter(|&y| hist.pixels[(y * 256 + x) as usize].r > 32)
+                .count()
+        };
+        assert_eq!(lit_rows(0), 40);
+        assert_eq!(lit_rows(255), 13);
+        assert_eq!(lit_rows(128), 0);
     }
 }
@@ -0,0 +1,189 @@
+// Non-destructive adjustment layers.
+//
+// An `AdjustmentLayers` stack holds named transformations in order, each
+// with an on/off switch. Nothing is applied until `render`, which runs the
+// enabled layers over an untouched base image, so an early adjustment can
+// be toggled, moved or removed without redoing the later ones by hand.
+//
+// Indices are 0-based; out-of-range indices return `OutOfBounds`.
+
+use super::error::ProcessError;
+use super::image::Image;
+use super::transforms::Transformation;
+
+struct Layer {
+    name: String,
+    transform: Box<dyn Transformation>,
+    enabled: bool,
+}
+
+pub struct AdjustmentLayers {
+    layers: Vec<Layer>,
+}
+
+impl AdjustmentLayers {
+    pub fn new() -> Self {
+        Self { layers: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    // Name and enabled state of each layer, bottom (applied first) to top.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, bool)> {
+        self.layers
+            .iter()
+            .map(|layer| (layer.name.as_str(), layer.enabled))
+    }
+
+    // Adds an enabled layer on top of the stack.
+    pub fn add(&mut self, name: String, transform: Box<dyn Transformation>) {
+        self.layers.push(Layer {
+            name,
+            transform,
+            enabled: true,
+        });
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<(), ProcessError> {
+        self.check_index("remove", index)?;
+        self.layers.remove(index);
+        Ok(())
+    }
+
+    // Flips a layer on or off and returns its new state.
+    pub fn toggle(&mut self, index: usize) -> Result<bool, ProcessError> {
+        self.check_index("toggle", index)?;
+        let layer = &mut self.layers[index];
+        layer.enabled = !layer.enabled;
+        Ok(layer.enabled)
+    }
+
+    // Moves the layer at `from` so it ends up at position `to`, shifting
+    // the layers in between.
+    pub fn move_layer(&mut self, from: usize, to: usize) -> Result<(), ProcessError> {
+        self.check_index("move", from)?;
+        self.check_index("move", to)?;
+        let layer = self.layers.remove(from);
+        self.layers.insert(to, layer);
+        Ok(())
+    }
+
+    // Applies the enabled layers to a copy of `base`, bottom to top.
+    pub fn render(&self, base: &Image) -> Result<Image, ProcessError> {
+        let mut current = Image {
+            width: base.width,
+            height: base.height,
+            pixels: base.pixels.clone(),
+            metadata: base.metadata,
+        };
+
+        for layer in self.layers.iter().filter(|layer| layer.enabled) {
+            current = layer.transform.apply(&current)?;
+        }
+
+        Ok(current)
+    }
+
+    fn check_index(&self, operation: &str, index: usize) -> Result<(), ProcessError> {
+        if index >= self.layers.len() {
+            return Err(ProcessError::OutOfBounds {
+                operation: format!("layer {}", operation),
+                details: format!("the stack has {} layer(s)", self.layers.len()),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rustphoto::image::{Metadata, Pixel};
+    use crate::rustphoto::transforms::{Invert, MapPixels};
+
+    fn base() -> Image {
+        Image {
+            width: 3,
+            height: 2,
+            pixels: (0..6)
+                .map(|i| Pixel::new(i * 40, 100, 255 - i * 30))
+                .collect(),
+            metadata: Metadata::default(),
+        }
+    }
+
+    fn rgb(image: &Image) -> Vec<(u8, u8, u8)> {
+        image.pixels.iter().map(|p| (p.r, p.g, p.b)).collect()
+    }
+
+    // Adds 60 to red; with Invert it does not commute, so order shows
+    fn warm() -> Box<dyn Transformation> {
+        Box::new(MapPixels::new(|_, _, p: Pixel| {
+            Pixel::new(p.r.saturating_add(60), p.g, p.b)
+        }))
+    }
+
+    #[test]
+    fn toggling_a_layer_off_matches_the_stack_without_it() {
+        let mut full = AdjustmentLayers::new();
+        full.add("warm".to_string(), warm());
+        full.add("invert".to_string(), Box::new(Invert::new()));
+        full.add("warm".to_string(), warm());
+
+        let mut without = AdjustmentLayers::new();
+        without.add("warm".to_string(), warm());
+        without.add("warm".to_string(), warm());
+
+        let all_on = full.render(&base()).unwrap();
+        assert!(!full.toggle(1).unwrap());
+        assert_eq!(
+            rgb(&full.render(&base()).unwrap()),
+            rgb(&without.render(&base()).unwrap())
+        );
+
+        assert!(full.toggle(1).unwrap());
+        assert_eq!(rgb(&full.render(&base()).unwrap()), rgb(&all_on));
+    }
+
+    #[test]
+    fn moving_and_removing_layers_changes_the_render() {
+        let mut stack = AdjustmentLayers::new();
+        stack.add("warm".to_string(), warm());
+        stack.add("invert".to_string(), Box::new(Invert::new()));
+
+        let mut reordered = AdjustmentLayers::new();
+        reordered.add("invert".to_string(), Box::new(Invert::new()));
+        reordered.add("warm".to_string(), warm());
+
+        stack.move_layer(1, 0).unwrap();
+        let names: Vec<&str> = stack.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["invert", "warm"]);
+        assert_eq!(
+            rgb(&stack.render(&base()).unwrap()),
+            rgb(&reordered.render(&base()).unwrap())
+        );
+
+        stack.remove(0).unwrap();
+        stack.remove(0).unwrap();
+        assert!(stack.is_empty());
+        assert_eq!(rgb(&stack.render(&base()).unwrap()), rgb(&base()));
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        let mut stack = AdjustmentLayers::new();
+        stack.add("invert".to_string(), Box::new(Invert::new()));
+
+        assert!(stack.toggle(1).is_err());
+        assert!(stack.remove(1).is_err());
+        assert!(stack.move_layer(0, 1).is_err());
+        assert_eq!(stack.len(), 1);
+    }
+}
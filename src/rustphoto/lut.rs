@@ -0,0 +1,299 @@
+// Color lookup tables in the Adobe/Resolve `.cube` format.
+//
+// # Format
+//
+// - `LUT_1D_SIZE n` or `LUT_3D_SIZE n` declares the table kind and size.
+// - `DOMAIN_MIN r g b` / `DOMAIN_MAX r g b` set the input range (default 0-1);
+//   `LUT_1D_INPUT_RANGE min max` / `LUT_3D_INPUT_RANGE min max` set the same
+//   range for all three channels.
+// - Other keyword lines (anything starting with a letter) are skipped.
+// - Data rows hold three floats each. For 3D tables red varies fastest:
+//   `table[r + g * n + b * n * n]`.
+//
+// 1D tables are interpolated linearly per channel, 3D tables trilinearly.
+
+use super::error::ProcessError;
+use super::image::{Image, Pixel};
+use super::transforms::Transformation;
+
+const MAX_1D_SIZE: usize = 65536;
+const MAX_3D_SIZE: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LutKind {
+    OneD,
+    ThreeD,
+}
+
+pub struct CubeLut {
+    kind: LutKind,
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    table: Vec<[f32; 3]>,
+}
+
+fn parse_triple(values: &[&str]) -> Option<[f32; 3]> {
+    if values.len() != 3 {
+        return None;
+    }
+
+    Some([
+        values[0].parse().ok()?,
+        values[1].parse().ok()?,
+        values[2].parse().ok()?,
+    ])
+}
+
+fn parse_range(values: &[&str]) -> Option<(f32, f32)> {
+    if values.len() != 2 {
+        return None;
+    }
+
+    Some((values[0].parse().ok()?, values[1].parse().ok()?))
+}
+
+impl CubeLut {
+    pub fn load(path: &str) -> Result<Self, ProcessError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| ProcessError::FileRead {
+            path: path.to_string(),
+            source: Box::new(e),
+        })?;
+
+        Self::parse(&contents).map_err(|details| ProcessError::LutParse {
+            path: path.to_string(),
+            details,
+        })
+    }
+
+    fn parse(contents: &str) -> Result<Self, String> {
+        let mut kind = None;
+        let mut size = 0;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = Vec::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let invalid = || format!("line {}: invalid entry '{}'", number + 1, line);
+
+            match parts[0] {
+                "TITLE" => {}
+                "LUT_1D_SIZE" | "LUT_3D_SIZE" => {
+                    if kind.is_some() {
+                        return Err(format!("line {}: size declared twice", number + 1));
+                    }
+                    size = parts
+                        .get(1)
+                        .and_then(|s| s.parse().ok())
+                        .ok_or_else(invalid)?;
+                    kind = Some(if parts[0] == "LUT_1D_SIZE" {
+                        LutKind::OneD
+                    } else {
+                        LutKind::ThreeD
+                    });
+                }
+                "DOMAIN_MIN" => domain_min = parse_triple(&parts[1..]).ok_or_else(invalid)?,
+                "DOMAIN_MAX" => domain_max = parse_triple(&parts[1..]).ok_or_else(invalid)?,
+                "LUT_1D_INPUT_RANGE" | "LUT_3D_INPUT_RANGE" => {
+                    let (min, max) = parse_range(&parts[1..]).ok_or_else(invalid)?;
+                    domain_min = [min; 3];
+                    domain_max = [max; 3];
+                }
+                keyword if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+                _ => table.push(parse_triple(&parts).ok_or_else(invalid)?),
+            }
+        }
+
+        let Some(kind) = kind else {
+            return Err("missing LUT_1D_SIZE or LUT_3D_SIZE".to_string());
+        };
+
+        let (max_size, expected) = match kind {
+            LutKind::OneD => (MAX_1D_SIZE, size),
+            LutKind::ThreeD => (MAX_3D_SIZE, size * size * size),
+        };
+
+        if !(2..=max_size).contains(&size) {
+            return Err(format!("size {} is outside 2-{}", size, max_size));
+        }
+
+        if table.len() != expected {
+            return Err(format!(
+                "expected {} data rows, found {}",
+                expected,
+                table.len()
+            ));
+        }
+
+        if (0..3).any(|c| domain_min[c] >= domain_max[c]) {
+            return Err("input range minimum must be below its maximum".to_string());
+        }
+
+        Ok(Self {
+            kind,
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    // Maps a 0-255 channel value to a fractional table coordinate.
+    fn coordinate(&self, value: u8, channel: usize) -> f32 {
+        let v = value as f32 / 255.0;
+        let t =
+            (v - self.domain_min[channel]) / (self.domain_max[channel] - self.domain_min[channel]);
+        t.clamp(0.0, 1.0) * (self.size - 1) as f32
+    }
+
+    fn lookup_1d(&self, p: &Pixel) -> [f32; 3] {
+        let mut out = [0.0; 3];
+
+        for (channel, value) in [p.r, p.g, p.b].into_iter().enumerate() {
+            let pos = self.coordinate(value, channel);
+            let lo = pos.floor() as usize;
+            let hi = (lo + 1).min(self.size - 1);
+            let t = pos - lo as f32;
+            out[channel] = self.table[lo][channel] * (1.0 - t) + self.table[hi][channel] * t;
+        }
+
+        out
+    }
+
+    fn lookup_3d(&self, p: &Pixel) -> [f32; 3] {
+        let n = self.size;
+        let pos = [
+            self.coordinate(p.r, 0),
+            self.coordinate(p.g, 1),
+            self.coordinate(p.b, 2),
+        ];
+        let lo = pos.map(|v| v.floor() as usize);
+        let hi = lo.map(|v| (v + 1).min(n - 1));
+        let t = [
+            pos[0] - lo[0] as f32,
+            pos[1] - lo[1] as f32,
+            pos[2] - lo[2] as f32,
+        ];
+
+        let at = |r: usize, g: usize, b: usize| self.table[r + g * n + b * n * n];
+        let mut out = [0.0; 3];
+
+        for (corner, weight) in [
+            (
+                (lo[0], lo[1], lo[2]),
+                (1.0 - t[0]) * (1.0 - t[1]) * (1.0 - t[2]),
+            ),
+            ((hi[0], lo[1], lo[2]), t[0] * (1.0 - t[1]) * (1.0 - t[2])),
+            ((lo[0], hi[1], lo[2]), (1.0 - t[0]) * t[1] * (1.0 - t[2])),
+            ((hi[0], hi[1], lo[2]), t[0] * t[1] * (1.0 - t[2])),
+            ((lo[0], lo[1], hi[2]), (1.0 - t[0]) * (1.0 - t[1]) * t[2]),
+            ((hi[0], lo[1], hi[2]), t[0] * (1.0 - t[1]) * t[2]),
+            ((lo[0], hi[1], hi[2]), (1.0 - t[0]) * t[1] * t[2]),
+            ((hi[0], hi[1], hi[2]), t[0] * t[1] * t[2]),
+        ] {
+            let value = at(corner.0, corner.1, corner.2);
+            for c in 0..3 {
+                out[c] += value[c] * weight;
+            }
+        }
+
+        out
+    }
+}
+
+impl Transformation for CubeLut {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                let [r, g, b] = match self.kind {
+                    LutKind::OneD => self.lookup_1d(p),
+                    LutKind::ThreeD => self.lookup_3d(p),
+                };
+                Pixel::from_f32(
+                    (r * 255.0).round(),
+                    (g * 255.0).round(),
+                    (b * 255.0).round(),
+                )
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rustphoto::image::Metadata;
+
+    fn identity_3d(header: &str) -> String {
+        let mut contents = format!("TITLE \"identity\"\n{}LUT_3D_SIZE 2\n", header);
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    contents.push_str(&format!("{} {} {}\n", r, g, b));
+                }
+            }
+        }
+        contents
+    }
+
+    fn apply(lut: &CubeLut, p: Pixel) -> (u8, u8, u8) {
+        let image = Image {
+            width: 1,
+            height: 1,
+            pixels: vec![p],
+            metadata: Metadata::default(),
+        };
+        let out = lut.apply(&image).unwrap().pixels[0];
+        (out.r, out.g, out.b)
+    }
+
+    #[test]
+    fn identity_cube_leaves_colors_unchanged() {
+        let lut = CubeLut::parse(&identity_3d("")).unwrap();
+
+        for p in [
+            Pixel::new(0, 0, 0),
+            Pixel::new(12, 140, 255),
+            Pixel::new(77, 200, 3),
+        ] {
+            assert_eq!(apply(&lut, p), (p.r, p.g, p.b));
+        }
+    }
+
+    #[test]
+    fn input_range_sets_the_domain() {
+        let lut = CubeLut::parse(&identity_3d("LUT_3D_INPUT_RANGE 0.0 2.0\n")).unwrap();
+        assert_eq!(lut.domain_min, [0.0; 3]);
+        assert_eq!(lut.domain_max, [2.0; 3]);
+
+        // White sits halfway through a 0-2 domain
+        assert_eq!(apply(&lut, Pixel::new(255, 255, 255)), (128, 128, 128));
+    }
+
+    #[test]
+    fn unknown_keywords_are_skipped() {
+        let contents = identity_3d("LUT_IN_VIDEO_RANGE\nCUSTOM_TAG 1 2 3\n");
+        assert!(CubeLut::parse(&contents).is_ok());
+    }
+
+    #[test]
+    fn wrong_row_count_is_rejected() {
+        let contents = identity_3d("").replace("1 1 1\n", "");
+        assert!(CubeLut::parse(&contents).is_err());
+    }
+}
@@ -1,4 +1,11 @@
 pub mod compression;
+pub mod config;
+pub mod dehaze;
 pub mod error;
+pub mod faces;
+pub mod font;
 pub mod image;
+pub mod layers;
+pub mod lut;
+pub mod presets;
 pub mod transforms;
@@ -0,0 +1,62 @@
+// Named presets expanding to fixed transformation pipelines.
+//
+// Each preset is a combination of existing pixel transformations tuned
+// for a common look. `PRESET_NAMES` lists everything `preset` accepts.
+
+use super::image::Pixel;
+use super::transforms::*;
+
+pub const PRESET_NAMES: &[&str] = &["vintage", "noir", "vivid", "cool", "warm"];
+
+pub fn preset(name: &str) -> Option<Pipeline> {
+    let pipeline = match name {
+        "vintage" => Pipeline::new()
+            .then(Vibrance::new(-0.4))
+            .then(Tint::new(Pixel::from_hex(0xA0785A), 0.3))
+            .then(Contrast::new(1.1)),
+        "noir" => Pipeline::new()
+            .then(Grayscale::new())
+            .then(Contrast::new(1.4)),
+        "vivid" => Pipeline::new()
+            .then(Vibrance::new(0.6))
+            .then(Contrast::new(1.15)),
+        "cool" => Pipeline::new().then(Tint::new(Pixel::from_hex(0x3070FF), 0.15)),
+        "warm" => Pipeline::new().then(Tint::new(Pixel::from_hex(0xFF9030), 0.15)),
+        _ => return None,
+    };
+
+    Some(pipeline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rustphoto::image::{Image, Metadata};
+
+    #[test]
+    fn noir_is_grayscale_with_more_contrast() {
+        let input = Image {
+            width: 2,
+            height: 1,
+            pixels: vec![Pixel::new(90, 60, 40), Pixel::new(170, 200, 190)],
+            metadata: Metadata::default(),
+        };
+
+        let gray = Grayscale::new().apply(&input).unwrap();
+        let noir = preset("noir").unwrap().apply(&input).unwrap();
+
+        for p in &noir.pixels {
+            assert!(p.r == p.g && p.g == p.b, "{:?} is not gray", p);
+        }
+        let spread = |image: &Image| image.pixels[1].r as i32 - image.pixels[0].r as i32;
+        assert!(spread(&noir) > spread(&gray));
+    }
+
+    #[test]
+    fn every_listed_name_resolves() {
+        for name in PRESET_NAMES {
+            assert!(preset(name).is_some(), "{} has no pipeline", name);
+        }
+        assert!(preset("sepia-ish").is_none());
+    }
+}
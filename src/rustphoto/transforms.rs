@@ -1,5 +1,7 @@
 use super::error::ProcessError;
-use super::image::{Image, Pixel};
+use super::font;
+use super::image::{BBox, Image, Pixel, linear_to_srgb, srgb_to_linear};
+use serde::Deserialize;
 
 // Image transformations module.
 //
@@ -26,6 +28,217 @@ pub trait Transformation {
     fn apply(&self, image: &Image) -> Result<Image, ProcessError>;
 }
 
+// Applies a sequence of transformations in order, feeding each result
+// into the next step.
+pub struct Pipeline {
+    steps: Vec<Box<dyn Transformation>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self { steps: Vec::new() }
+    }
+
+    pub fn then<T: Transformation + 'static>(mut self, step: T) -> Self {
+        self.steps.push(Box::new(step));
+        self
+    }
+}
+
+impl Transformation for Pipeline {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let mut current = Image {
+            width: image.width,
+            height: image.height,
+            pixels: image.pixels.clone(),
+            metadata: image.metadata,
+        };
+
+        for step in &self.steps {
+            current = step.apply(&current)?;
+        }
+
+        Ok(current)
+    }
+}
+
+// Applies `inner` `times` times in a row, feeding each result into the
+// next pass, e.g. to watch an iterative filter converge.
+pub struct Repeat {
+    inner: Box<dyn Transformation>,
+    times: i32,
+}
+
+impl Repeat {
+    pub fn new(inner: Box<dyn Transformation>, times: i32) -> Self {
+        Self { inner, times }
+    }
+}
+
+impl Transformation for Repeat {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.times < 1 {
+            return Err(ProcessError::InvalidInput {
+                operation: "repeat".to_string(),
+                details: format!("count must be at least 1, got {}", self.times),
+            });
+        }
+
+        let mut current = self.inner.apply(image)?;
+        for _ in 1..self.times {
+            current = self.inner.apply(&current)?;
+        }
+
+        Ok(current)
+    }
+}
+
+// Applies `inner` to a rectangular region only and pastes the result back
+// into a copy of the image. `inner` must preserve the region's dimensions.
+pub struct SubRegion {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    inner: Box<dyn Transformation>,
+}
+
+impl SubRegion {
+    pub fn new(x: i32, y: i32, width: i32, height: i32, inner: Box<dyn Transformation>) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            inner,
+        }
+    }
+}
+
+impl Transformation for SubRegion {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let region = Crop::new(self.x, self.y, self.width, self.height).apply(image)?;
+        let processed = self.inner.apply(&region)?;
+
+        if processed.width != region.width || processed.height != region.height {
+            return Err(ProcessError::InvalidInput {
+                operation: "region".to_string(),
+                details: format!(
+                    "inner transformation changed region size from {}x{} to {}x{}",
+                    region.width, region.height, processed.width, processed.height
+                ),
+            });
+        }
+
+        let mut pixels = image.pixels.clone();
+
+        for dy in 0..self.height {
+            for dx in 0..self.width {
+                let src_idx = (dy * self.width + dx) as usize;
+                let dst_idx = ((self.y + dy) * image.width + (self.x + dx)) as usize;
+                pixels[dst_idx] = processed.pixels[src_idx];
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Color space partial blends are computed in. Mixing sRGB values directly
+// darkens the midpoint: 50% black over white gives 128, which displays at
+// about 22% of white's light. Mixing in linear light gives 188 (50% light)
+// and avoids dark fringes along blended edges. GradientOverlay, MaskedBy,
+// LightLeak and DiffHeatmap take it through `with_space`.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompositeSpace {
+    #[default]
+    Srgb,
+    Linear,
+}
+
+impl CompositeSpace {
+    // Blends channel `a` toward `b` by `t` in [0, 1], returning 0-255.
+    fn mix(self, a: u8, b: f32, t: f32) -> f32 {
+        match self {
+            CompositeSpace::Srgb => a as f32 + (b - a as f32) * t,
+            CompositeSpace::Linear => {
+                let a = srgb_to_linear(a as f32 / 255.0);
+                let b = srgb_to_linear(b / 255.0);
+                linear_to_srgb(a + (b - a) * t) * 255.0
+            }
+        }
+    }
+}
+
+// Blends `effect` into the image through a mask computed by `mask_source`:
+// where the mask's luma is white the effect shows fully, where it is black
+// the original is kept. Both must preserve the image dimensions. For
+// example, an edge detector as the mask limits a blur to edges.
+pub struct MaskedBy {
+    mask_source: Box<dyn Transformation>,
+    effect: Box<dyn Transformation>,
+    space: CompositeSpace,
+}
+
+impl MaskedBy {
+    pub fn new(mask_source: Box<dyn Transformation>, effect: Box<dyn Transformation>) -> Self {
+        Self {
+            mask_source,
+            effect,
+            space: CompositeSpace::Srgb,
+        }
+    }
+
+    pub fn with_space(mut self, space: CompositeSpace) -> Self {
+        self.space = space;
+        self
+    }
+}
+
+impl Transformation for MaskedBy {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let mask = self.mask_source.apply(image)?;
+        let effected = self.effect.apply(image)?;
+
+        for (name, result) in [("mask", &mask), ("effect", &effected)] {
+            if result.width != image.width || result.height != image.height {
+                return Err(ProcessError::InvalidInput {
+                    operation: "masked".to_string(),
+                    details: format!(
+                        "{} changed image size from {}x{} to {}x{}",
+                        name, image.width, image.height, result.width, result.height
+                    ),
+                });
+            }
+        }
+
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .zip(&effected.pixels)
+            .zip(&mask.pixels)
+            .map(|((o, e), m)| {
+                let t = m.luma() / 255.0;
+                let mix = |a: u8, b: u8| self.space.mix(a, b as f32, t).round();
+                Pixel::from_f32(mix(o.r, e.r), mix(o.g, e.g), mix(o.b, e.b))
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
 // Geometric transformations
 
 pub struct Crop {
@@ -71,6 +284,7 @@ impl Transformation for Crop {
             width: self.width,
             height: self.height,
             pixels,
+            metadata: image.metadata,
         })
     }
 }
@@ -122,6 +336,7 @@ impl Transformation for Flip {
             width: image.width,
             height: image.height,
             pixels,
+            metadata: image.metadata,
         })
     }
 }
@@ -192,13 +407,120 @@ impl Transformation for Rotate {
             width,
             height,
             pixels,
+            metadata: image.metadata,
         })
     }
 }
 
+// Normalizes pixels stored with the EXIF orientation (1-8) recorded in the
+// image's metadata so they display upright, and resets the orientation to 1
+// so applying it twice is harmless.
+pub struct AutoOrient;
+
+impl AutoOrient {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Transformation for AutoOrient {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let orientation = image.metadata.orientation;
+        let pipeline = match orientation {
+            1 => Pipeline::new(),
+            2 => Pipeline::new().then(Flip::new(FlipAxis::Horizontal)),
+            3 => Pipeline::new().then(Rotate::new(RotateAngle::Deg180)),
+            4 => Pipeline::new().then(Flip::new(FlipAxis::Vertical)),
+            5 => Pipeline::new()
+                .then(Rotate::new(RotateAngle::Deg90))
+                .then(Flip::new(FlipAxis::Horizontal)),
+            6 => Pipeline::new().then(Rotate::new(RotateAngle::Deg90)),
+            7 => Pipeline::new()
+                .then(Rotate::new(RotateAngle::Deg270))
+                .then(Flip::new(FlipAxis::Horizontal)),
+            8 => Pipeline::new().then(Rotate::new(RotateAngle::Deg270)),
+            _ => {
+                return Err(ProcessError::InvalidInput {
+                    operation: "autoorient".to_string(),
+                    details: format!("EXIF orientation must be 1-8, got {}", orientation),
+                });
+            }
+        };
+
+        let mut oriented = pipeline.apply(image)?;
+        oriented.metadata.orientation = 1;
+        Ok(oriented)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Sampling {
+    Nearest,
+    // Averages every source pixel an output pixel covers, weighted by the
+    // covered area. Avoids aliasing when shrinking a lot.
+    Area,
+}
+
+// Scales `image` to `width` x `height` (both positive).
+fn resample(image: &Image, width: i32, height: i32, sampling: Sampling) -> Vec<Pixel> {
+    let x_ratio = image.width as f32 / width as f32;
+    let y_ratio = image.height as f32 / height as f32;
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+
+    match sampling {
+        Sampling::Nearest => {
+            for y in 0..height {
+                for x in 0..width {
+                    let src_x = ((x as f32 * x_ratio) as i32).min(image.width - 1);
+                    let src_y = ((y as f32 * y_ratio) as i32).min(image.height - 1);
+                    pixels.push(image.pixels[(src_y * image.width + src_x) as usize]);
+                }
+            }
+        }
+        Sampling::Area => {
+            // Source pixels overlapping [start, end) and how much of each is covered
+            let coverage = |start: f32, end: f32, limit: i32| {
+                let first = start.floor() as i32;
+                let last = (end.ceil() as i32).min(limit);
+                (first..last)
+                    .map(|i| (i, (end.min((i + 1) as f32) - start.max(i as f32)).max(0.0)))
+                    .collect::<Vec<(i32, f32)>>()
+            };
+
+            for y in 0..height {
+                let rows = coverage(y as f32 * y_ratio, (y + 1) as f32 * y_ratio, image.height);
+                for x in 0..width {
+                    let cols = coverage(x as f32 * x_ratio, (x + 1) as f32 * x_ratio, image.width);
+
+                    let (mut r, mut g, mut b, mut total) = (0.0, 0.0, 0.0, 0.0);
+                    for &(sy, wy) in &rows {
+                        for &(sx, wx) in &cols {
+                            let p = image.pixels[(sy * image.width + sx) as usize];
+                            let weight = wx * wy;
+                            r += p.r as f32 * weight;
+                            g += p.g as f32 * weight;
+                            b += p.b as f32 * weight;
+                            total += weight;
+                        }
+                    }
+
+                    pixels.push(Pixel::from_f32(
+                        (r / total).round(),
+                        (g / total).round(),
+                        (b / total).round(),
+                    ));
+                }
+            }
+        }
+    }
+
+    pixels
+}
+
 pub struct Fit {
     max_width: i32,
     max_height: i32,
+    sampling: Sampling,
 }
 
 impl Fit {
@@ -206,8 +528,14 @@ impl Fit {
         Self {
             max_width,
             max_height,
+            sampling: Sampling::Nearest,
         }
     }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
 }
 
 impl Transformation for Fit {
@@ -219,400 +547,6732 @@ impl Transformation for Fit {
         let new_width = (image.width as f32 * scale) as i32;
         let new_height = (image.height as f32 * scale) as i32;
 
-        let mut pixels = vec![Pixel::new(0, 0, 0); (new_width * new_height) as usize];
-
-        let x_ratio = image.width as f32 / new_width as f32;
-        let y_ratio = image.height as f32 / new_height as f32;
-
-        for dst_y in 0..new_height {
-            for dst_x in 0..new_width {
-                let src_x = (dst_x as f32 * x_ratio) as i32;
-                let src_y = (dst_y as f32 * y_ratio) as i32;
-
-                let src_idx = (src_y * image.width + src_x) as usize;
-                let dst_idx = (dst_y * new_width + dst_x) as usize;
-
-                pixels[dst_idx] = image.pixels[src_idx];
-            }
-        }
+        let pixels = if new_width > 0 && new_height > 0 {
+            resample(image, new_width, new_height, self.sampling)
+        } else {
+            Vec::new()
+        };
 
         Ok(Image {
             width: new_width,
             height: new_height,
             pixels,
+            metadata: image.metadata,
         })
     }
 }
 
-// Pixel-to-pixel transformations
+// Scales to exactly `width` x `height`. A letterboxed resize keeps the
+// aspect ratio instead: the image is scaled to fit inside the target and
+// centered on a canvas filled with `fill`.
+pub struct Resize {
+    width: i32,
+    height: i32,
+    fill: Option<Pixel>,
+    sampling: Sampling,
+}
 
-pub struct Invert;
+impl Resize {
+    pub fn new(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            fill: None,
+            sampling: Sampling::Nearest,
+        }
+    }
 
-impl Invert {
-    pub fn new() -> Self {
-        Self
+    pub fn letterboxed(width: i32, height: i32, fill: Pixel) -> Self {
+        Self {
+            width,
+            height,
+            fill: Some(fill),
+            sampling: Sampling::Nearest,
+        }
+    }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
     }
 }
 
-impl Transformation for Invert {
+impl Transformation for Resize {
     fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
-        let pixels: Vec<Pixel> = image
-            .pixels
-            .iter()
-            .map(|p| Pixel::new(255 - p.r, 255 - p.g, 255 - p.b))
-            .collect();
+        if self.width <= 0 || self.height <= 0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "resize".to_string(),
+                details: format!(
+                    "target size {}x{} must be positive",
+                    self.width, self.height
+                ),
+            });
+        }
+        check_not_empty("resize", image)?;
 
-        Ok(Image {
-            width: image.width,
-            height: image.height,
-            pixels,
-        })
-    }
-}
+        let (inner_width, inner_height) = match self.fill {
+            Some(_) => {
+                let scale = (self.width as f32 / image.width as f32)
+                    .min(self.height as f32 / image.height as f32);
+                (
+                    ((image.width as f32 * scale).round() as i32).clamp(1, self.width),
+                    ((image.height as f32 * scale).round() as i32).clamp(1, self.height),
+                )
+            }
+            None => (self.width, self.height),
+        };
 
-pub struct Grayscale;
+        let inner = resample(image, inner_width, inner_height, self.sampling);
+        let Some(fill) = self.fill else {
+            return Ok(Image {
+                width: self.width,
+                height: self.height,
+                pixels: inner,
+                metadata: image.metadata,
+            });
+        };
 
-impl Grayscale {
-    pub fn new() -> Self {
-        Self
-    }
-}
+        let offset_x = (self.width - inner_width) / 2;
+        let offset_y = (self.height - inner_height) / 2;
+        let mut pixels = vec![fill; (self.width * self.height) as usize];
 
-impl Transformation for Grayscale {
-    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
-        let pixels: Vec<Pixel> = image
-            .pixels
-            .iter()
-            .map(|p| {
-                let gray = ((p.r as u16 + p.g as u16 + p.b as u16) / 3) as u8;
-                Pixel::new(gray, gray, gray)
-            })
-            .collect();
+        for y in 0..inner_height {
+            let src = (y * inner_width) as usize;
+            let dst = ((y + offset_y) * self.width + offset_x) as usize;
+            pixels[dst..dst + inner_width as usize]
+                .copy_from_slice(&inner[src..src + inner_width as usize]);
+        }
 
         Ok(Image {
-            width: image.width,
-            height: image.height,
+            width: self.width,
+            height: self.height,
             pixels,
+            metadata: image.metadata,
         })
     }
 }
 
-pub struct Brightness {
-    factor: f32,
+// Fills an exact `width` x `height` frame without bars: a blurred copy
+// scaled to cover the frame (overflow cropped) forms the background and the
+// whole image, scaled to fit, sits centered on top.
+pub struct BlurFill {
+    width: i32,
+    height: i32,
+    blur_radius: i32,
 }
 
-impl Brightness {
-    pub fn new(factor: f32) -> Self {
-        Self { factor }
+impl BlurFill {
+    pub fn new(width: i32, height: i32, blur_radius: i32) -> Self {
+        Self {
+            width,
+            height,
+            blur_radius,
+        }
     }
 }
 
-impl Transformation for Brightness {
+impl Transformation for BlurFill {
     fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
-        let pixels: Vec<Pixel> = image
-            .pixels
-            .iter()
-            .map(|p| {
-                Pixel::from_f32(
-                    p.r as f32 * self.factor,
-                    p.g as f32 * self.factor,
-                    p.b as f32 * self.factor,
-                )
-            })
-            .collect();
+        if self.width <= 0 || self.height <= 0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "blurfill".to_string(),
+                details: format!(
+                    "target size {}x{} must be positive",
+                    self.width, self.height
+                ),
+            });
+        }
+        check_not_empty("blurfill", image)?;
+
+        let scale_x = self.width as f32 / image.width as f32;
+        let scale_y = self.height as f32 / image.height as f32;
+
+        let cover = scale_x.max(scale_y);
+        let cover_width = ((image.width as f32 * cover).ceil() as i32).max(self.width);
+        let cover_height = ((image.height as f32 * cover).ceil() as i32).max(self.height);
+        let background = Pipeline::new()
+            .then(Resize::new(cover_width, cover_height))
+            .then(Crop::new(
+                (cover_width - self.width) / 2,
+                (cover_height - self.height) / 2,
+                self.width,
+                self.height,
+            ))
+            .then(GaussianBlur::with_radius(self.blur_radius))
+            .apply(image)?;
+
+        let fit = scale_x.min(scale_y);
+        let fit_width = ((image.width as f32 * fit).round() as i32).clamp(1, self.width);
+        let fit_height = ((image.height as f32 * fit).round() as i32).clamp(1, self.height);
+        let foreground = Resize::new(fit_width, fit_height).apply(image)?;
+
+        let offset_x = (self.width - fit_width) / 2;
+        let offset_y = (self.height - fit_height) / 2;
+        let mut pixels = background.pixels;
+        for y in 0..fit_height {
+            let src = (y * fit_width) as usize;
+            let dst = ((y + offset_y) * self.width + offset_x) as usize;
+            pixels[dst..dst + fit_width as usize]
+                .copy_from_slice(&foreground.pixels[src..src + fit_width as usize]);
+        }
 
         Ok(Image {
-            width: image.width,
-            height: image.height,
+            width: self.width,
+            height: self.height,
             pixels,
+            metadata: image.metadata,
         })
     }
 }
 
-pub struct Contrast {
-    factor: f32,
+// Samples at fractional coordinates with bilinear interpolation, clamping
+// to the nearest edge pixel outside the image. `image` must not be empty:
+// transforms whose output size does not follow the input check with
+// `check_not_empty` first, the others never sample an empty image.
+fn sample_bilinear(image: &Image, x: f32, y: f32) -> Pixel {
+    let x = x.clamp(0.0, (image.width - 1) as f32);
+    let y = y.clamp(0.0, (image.height - 1) as f32);
+    let x0 = x.floor() as i32;
+    let y0 = y.floor() as i32;
+    let x1 = (x0 + 1).min(image.width - 1);
+    let y1 = (y0 + 1).min(image.height - 1);
+    let tx = x - x0 as f32;
+    let ty = y - y0 as f32;
+
+    let at = |px: i32, py: i32| image.pixels[(py * image.width + px) as usize];
+    let (p00, p10, p01, p11) = (at(x0, y0), at(x1, y0), at(x0, y1), at(x1, y1));
+
+    let mix = |a: u8, b: u8, c: u8, d: u8| {
+        let top = a as f32 * (1.0 - tx) + b as f32 * tx;
+        let bottom = c as f32 * (1.0 - tx) + d as f32 * tx;
+        (top * (1.0 - ty) + bottom * ty).round()
+    };
+
+    Pixel::from_f32(
+        mix(p00.r, p10.r, p01.r, p11.r),
+        mix(p00.g, p10.g, p01.g, p11.g),
+        mix(p00.b, p10.b, p01.b, p11.b),
+    )
 }
 
-impl Contrast {
-    pub fn new(factor: f32) -> Self {
-        Self { factor }
-    }
+// Renders a `width` x `height` remap of `image`: `source_of` gives the
+// source position for an output position. With `supersample` N > 1 each
+// output pixel averages an N x N grid of sub-pixel samples, the same as
+// rendering at N times the size and box-filtering down.
+fn render_supersampled(
+    image: &Image,
+    width: i32,
+    height: i32,
+    supersample: u8,
+    source_of: impl Fn(f32, f32) -> (f32, f32),
+) -> Vec<Pixel> {
+    shade_supersampled(width, height, supersample, |x, y| {
+        let (src_x, src_y) = source_of(x, y);
+        sample_bilinear(image, src_x, src_y)
+    })
 }
 
-impl Transformation for Contrast {
+// The sampling grid behind `render_supersampled`, for remaps where some
+// output positions have no source and get a fixed color instead.
+fn shade_supersampled(
+    width: i32,
+    height: i32,
+    supersample: u8,
+    shade: impl Fn(f32, f32) -> Pixel,
+) -> Vec<Pixel> {
+    let n = supersample.max(1) as i32;
+    let count = (n * n) as f32;
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            if n == 1 {
+                pixels.push(shade(x as f32, y as f32));
+                continue;
+            }
+
+            let (mut r, mut g, mut b) = (0.0, 0.0, 0.0);
+            for sy in 0..n {
+                for sx in 0..n {
+                    let px = x as f32 + (sx as f32 + 0.5) / n as f32 - 0.5;
+                    let py = y as f32 + (sy as f32 + 0.5) / n as f32 - 0.5;
+                    let p = shade(px, py);
+                    r += p.r as f32;
+                    g += p.g as f32;
+                    b += p.b as f32;
+                }
+            }
+
+            pixels.push(Pixel::from_f32(
+                (r / count).round(),
+                (g / count).round(),
+                (b / count).round(),
+            ));
+        }
+    }
+
+    pixels
+}
+
+fn check_not_empty(operation: &str, image: &Image) -> Result<(), ProcessError> {
+    if image.pixels.is_empty() {
+        return Err(ProcessError::InvalidInput {
+            operation: operation.to_string(),
+            details: "source image is empty".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+fn check_supersample(operation: &str, supersample: u8) -> Result<(), ProcessError> {
+    if ![1, 2, 4].contains(&supersample) {
+        return Err(ProcessError::InvalidInput {
+            operation: operation.to_string(),
+            details: format!("supersample must be 1, 2 or 4, got {}", supersample),
+        });
+    }
+
+    Ok(())
+}
+
+// Solves the 8x8 system for the homography mapping each `from` point to
+// the matching `to` point. Returns coefficients a..h of
+// x' = (a*x + b*y + c) / (g*x + h*y + 1), y' = (d*x + e*y + f) / (g*x + h*y + 1).
+fn solve_homography(from: &[(f32, f32); 4], to: &[(f32, f32); 4]) -> Option<[f64; 8]> {
+    let mut m = [[0.0f64; 9]; 8];
+
+    for i in 0..4 {
+        let (x, y) = (from[i].0 as f64, from[i].1 as f64);
+        let (u, v) = (to[i].0 as f64, to[i].1 as f64);
+        m[2 * i] = [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, u];
+        m[2 * i + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, v];
+    }
+
+    // Gaussian elimination with partial pivoting
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+        if m[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        m.swap(col, pivot);
+
+        let pivot_row = m[col];
+        for (row, values) in m.iter_mut().enumerate() {
+            if row != col {
+                let factor = values[col] / pivot_row[col];
+                for (value, p) in values.iter_mut().zip(pivot_row).skip(col) {
+                    *value -= factor * p;
+                }
+            }
+        }
+    }
+
+    Some(std::array::from_fn(|i| m[i][8] / m[i][i]))
+}
+
+// Maps the quadrilateral given by `src_corners` (top-left, top-right,
+// bottom-right, bottom-left) onto a `dst_width` x `dst_height` rectangle.
+pub struct Perspective {
+    src_corners: [(f32, f32); 4],
+    dst_width: i32,
+    dst_height: i32,
+    supersample: u8,
+}
+
+impl Perspective {
+    pub fn new(src_corners: [(f32, f32); 4], dst_width: i32, dst_height: i32) -> Self {
+        Self {
+            src_corners,
+            dst_width,
+            dst_height,
+            supersample: 1,
+        }
+    }
+
+    pub fn with_supersample(mut self, supersample: u8) -> Self {
+        self.supersample = supersample;
+        self
+    }
+}
+
+impl Transformation for Perspective {
     fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
-        let pixels: Vec<Pixel> = image
-            .pixels
-            .iter()
-            .map(|p| {
-                Pixel::from_f32(
-                    (p.r as f32 - 128.0) * self.factor + 128.0,
-                    (p.g as f32 - 128.0) * self.factor + 128.0,
-                    (p.b as f32 - 128.0) * self.factor + 128.0,
-                )
-            })
-            .collect();
+        if self.dst_width <= 0 || self.dst_height <= 0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "perspective".to_string(),
+                details: format!(
+                    "output size must be positive, got {}x{}",
+                    self.dst_width, self.dst_height
+                ),
+            });
+        }
+        check_supersample("perspective", self.supersample)?;
+        check_not_empty("perspective", image)?;
+
+        let right = (self.dst_width - 1) as f32;
+        let bottom = (self.dst_height - 1) as f32;
+        let dst_corners = [(0.0, 0.0), (right, 0.0), (right, bottom), (0.0, bottom)];
+
+        // Map output coordinates back to the source for sampling
+        let Some(h) = solve_homography(&dst_corners, &self.src_corners) else {
+            return Err(ProcessError::InvalidInput {
+                operation: "perspective".to_string(),
+                details: "corner points are degenerate".to_string(),
+            });
+        };
+
+        let pixels = render_supersampled(
+            image,
+            self.dst_width,
+            self.dst_height,
+            self.supersample,
+            |x, y| {
+                let (u, v) = (x as f64, y as f64);
+                let w = h[6] * u + h[7] * v + 1.0;
+                let src_x = (h[0] * u + h[1] * v + h[2]) / w;
+                let src_y = (h[3] * u + h[4] * v + h[5]) / w;
+                (src_x as f32, src_y as f32)
+            },
+        );
 
         Ok(Image {
-            width: image.width,
-            height: image.height,
+            width: self.dst_width,
+            height: self.dst_height,
             pixels,
+            metadata: image.metadata,
         })
     }
 }
 
-pub struct Tint {
-    color: Pixel,
-    intensity: f32,
+// Keystone correction: squeezes the image into a symmetric trapezoid whose
+// top edge is inset by `top_inset` pixels on each side and its bottom edge
+// by `bottom_inset`, as a perspective projection of the full frame. The
+// vacated corners are filled with `fill`. Supersampling also smooths the
+// slanted edges between the image and the fill.
+pub struct Keystone {
+    top_inset: i32,
+    bottom_inset: i32,
+    fill: Pixel,
+    supersample: u8,
 }
 
-impl Tint {
-    pub fn new(color: Pixel, intensity: f32) -> Self {
-        Self { color, intensity }
+impl Keystone {
+    pub fn new(top_inset: i32, bottom_inset: i32, fill: Pixel) -> Self {
+        Self {
+            top_inset,
+            bottom_inset,
+            fill,
+            supersample: 1,
+        }
+    }
+
+    pub fn with_supersample(mut self, supersample: u8) -> Self {
+        self.supersample = supersample;
+        self
     }
 }
 
-impl Transformation for Tint {
+impl Transformation for Keystone {
     fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
-        let blend =
-            |src: u8, tint: u8| src as f32 * (1.0 - self.intensity) + tint as f32 * self.intensity;
+        for inset in [self.top_inset, self.bottom_inset] {
+            if inset < 0 || inset * 2 >= image.width {
+                return Err(ProcessError::InvalidInput {
+                    operation: "keystone".to_string(),
+                    details: format!(
+                        "insets must be between 0 and half the width ({}), got {}",
+                        image.width, inset
+                    ),
+                });
+            }
+        }
+        check_supersample("keystone", self.supersample)?;
 
-        let pixels: Vec<Pixel> = image
-            .pixels
-            .iter()
-            .map(|p| {
-                Pixel::from_f32(
-                    blend(p.r, self.color.r),
-                    blend(p.g, self.color.g),
-                    blend(p.b, self.color.b),
-                )
-            })
-            .collect();
+        let right = (image.width - 1) as f32;
+        let bottom = (image.height - 1) as f32;
+        let (top, low) = (self.top_inset as f32, self.bottom_inset as f32);
+        let trapezoid = [
+            (top, 0.0),
+            (right - top, 0.0),
+            (right - low, bottom),
+            (low, bottom),
+        ];
+        let frame = [(0.0, 0.0), (right, 0.0), (right, bottom), (0.0, bottom)];
+
+        // Map output coordinates inside the trapezoid back to the full frame
+        let Some(h) = solve_homography(&trapezoid, &frame) else {
+            return Err(ProcessError::InvalidInput {
+                operation: "keystone".to_string(),
+                details: "insets produce a degenerate trapezoid".to_string(),
+            });
+        };
+
+        let pixels = shade_supersampled(image.width, image.height, self.supersample, |x, y| {
+            let (u, v) = (x as f64, y as f64);
+            let w = h[6] * u + h[7] * v + 1.0;
+            let src_x = ((h[0] * u + h[1] * v + h[2]) / w) as f32;
+            let src_y = ((h[3] * u + h[4] * v + h[5]) / w) as f32;
+
+            if src_x < -0.5 || src_x > right + 0.5 || src_y < -0.5 || src_y > bottom + 0.5 {
+                self.fill
+            } else {
+                sample_bilinear(image, src_x, src_y)
+            }
+        });
 
         Ok(Image {
             width: image.width,
             height: image.height,
             pixels,
+            metadata: image.metadata,
         })
     }
 }
 
-pub struct Colorize {
-    color: Pixel,
+// Radial lens distortion correction. The model maps a distorted offset from
+// the center to its corrected one as r_u = r_d * (1 + k1*r_d^2 + k2*r_d^4),
+// with r normalized so the corners are at 1. Each output pixel inverts this
+// by fixed-point iteration to find where to sample the source. Positive k1
+// corrects barrel distortion, negative k1 pincushion.
+const LENS_ITERATIONS: usize = 8;
+
+pub struct LensCorrect {
+    k1: f32,
+    k2: f32,
+    supersample: u8,
 }
 
-impl Colorize {
-    pub fn new(color: Pixel) -> Self {
-        Self { color }
+impl LensCorrect {
+    pub fn new(k1: f32, k2: f32) -> Self {
+        Self {
+            k1,
+            k2,
+            supersample: 1,
+        }
+    }
+
+    pub fn with_supersample(mut self, supersample: u8) -> Self {
+        self.supersample = supersample;
+        self
     }
 }
 
-impl Transformation for Colorize {
+impl Transformation for LensCorrect {
     fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
-        let pixels: Vec<Pixel> = image
-            .pixels
-            .iter()
-            .map(|p| {
-                let gray = (p.r as u16 + p.g as u16 + p.b as u16) / 3;
-                let factor = gray as f32 / 255.0;
-                Pixel::from_f32(
-                    self.color.r as f32 * factor,
-                    self.color.g as f32 * factor,
-                    self.color.b as f32 * factor,
-                )
-            })
-            .collect();
+        check_supersample("lenscorrect", self.supersample)?;
+
+        let center_x = (image.width - 1) as f32 / 2.0;
+        let center_y = (image.height - 1) as f32 / 2.0;
+        let norm2 = (center_x * center_x + center_y * center_y).max(1.0);
+
+        let pixels = render_supersampled(
+            image,
+            image.width,
+            image.height,
+            self.supersample,
+            |x, y| {
+                let ux = x - center_x;
+                let uy = y - center_y;
+
+                let (mut dx, mut dy) = (ux, uy);
+                for _ in 0..LENS_ITERATIONS {
+                    let r2 = (dx * dx + dy * dy) / norm2;
+                    let scale = 1.0 + self.k1 * r2 + self.k2 * r2 * r2;
+                    if scale.abs() < f32::EPSILON {
+                        break;
+                    }
+                    dx = ux / scale;
+                    dy = uy / scale;
+                }
+
+                (center_x + dx, center_y + dy)
+            },
+        );
 
         Ok(Image {
             width: image.width,
             height: image.height,
             pixels,
+            metadata: image.metadata,
         })
     }
 }
 
-// Kernel filters
-
-trait KernelTransformation {
-    fn kernel(&self) -> &Kernel;
+// Levels a tilted horizon. Horizontal-ish edges (Sobel, vertical gradient
+// dominant) vote in a Hough accumulator over angles within +/-`max_angle`
+// degrees in 0.1 degree steps; the angle whose strongest line collects the
+// most votes wins. The image is rotated by the opposite angle and cropped,
+// keeping the aspect ratio, to the largest rectangle free of empty corners.
+pub struct AutoStraighten {
+    max_angle: f32,
+    supersample: u8,
 }
 
-impl<T: KernelTransformation> Transformation for T {
-    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
-        let kernel = self.kernel();
-        let mut pixels = vec![Pixel::new(0, 0, 0); (image.width * image.height) as usize];
+impl AutoStraighten {
+    pub fn new(max_angle: f32) -> Self {
+        Self {
+            max_angle,
+            supersample: 1,
+        }
+    }
 
-        for y in 0..image.height {
-            for x in 0..image.width {
+    pub fn with_supersample(mut self, supersample: u8) -> Self {
+        self.supersample = supersample;
+        self
+    }
+
+    fn edge_points(image: &Image) -> Vec<(f32, f32)> {
+        let mut points = Vec::new();
+
+        for y in 1..image.height - 1 {
+            for x in 1..image.width - 1 {
                 let window = KernelWindow {
                     image,
                     center_x: x,
                     center_y: y,
                 };
-                let idx = (y * image.width + x) as usize;
-                pixels[idx] = window.apply_kernel(&kernel);
+                let (gx, gy) = window.sobel();
+
+                if gy.abs() > gx.abs() && (gx * gx + gy * gy).sqrt() > 128.0 {
+                    points.push((x as f32, y as f32));
+                }
             }
         }
 
-        Ok(Image {
-            width: image.width,
-            height: image.height,
-            pixels,
-        })
+        points
     }
-}
-
-struct Kernel {
-    size: i32,
-    values: Vec<f32>,
-}
 
-impl Kernel {
-    fn new(size: i32, values: Vec<f32>) -> Kernel {
-        assert!(size % 2 == 1, "Kernel size must be odd");
-        assert_eq!(values.len(), (size * size) as usize);
+    // Tilt of the dominant near-horizontal line in radians, positive when
+    // the line descends to the right.
+    fn detect_angle(&self, image: &Image) -> f32 {
+        let points = Self::edge_points(image);
+        let steps = (self.max_angle * 10.0).round() as i32;
+        let diagonal = ((image.width * image.width + image.height * image.height) as f32).sqrt();
+        let offset = diagonal.ceil() as i32;
 
-        Self { size, values }
-    }
+        let mut best = (0, 0.0f32);
+        for step in -steps..=steps {
+            let theta = (step as f32 / 10.0).to_radians();
+            let (sin, cos) = theta.sin_cos();
 
-    fn normalize(&mut self) {
-        let sum: f32 = self.values.iter().sum();
+            let mut bins = vec![0; (offset * 2 + 1) as usize];
+            for &(x, y) in &points {
+                let distance = (y * cos - x * sin).round() as i32 + offset;
+                bins[distance as usize] += 1;
+            }
 
-        if sum.abs() > f32::EPSILON {
-            for val in &mut self.values {
-                *val /= sum;
+            let votes = bins.into_iter().max().unwrap_or(0);
+            if votes > best.0 || (votes == best.0 && theta.abs() < best.1.abs()) {
+                best = (votes, theta);
             }
         }
-    }
 
-    fn normalized(mut self) -> Self {
-        self.normalize();
-        self
+        best.1
     }
+}
 
-    fn get(&self, dx: i32, dy: i32) -> f32 {
-        let center = self.size / 2;
-        let x = center + dx;
-        let y = center + dy;
-        self.values[(y * self.size + x) as usize]
+impl Transformation for AutoStraighten {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if !(0.0..=45.0).contains(&self.max_angle) {
+            return Err(ProcessError::InvalidInput {
+                operation: "autostraighten".to_string(),
+                details: format!("max_angle must be 0-45 degrees, got {}", self.max_angle),
+            });
+        }
+        check_supersample("autostraighten", self.supersample)?;
+        check_not_empty("autostraighten", image)?;
+
+        let theta = self.detect_angle(image);
+        let (sin, cos) = theta.sin_cos();
+        let (w, h) = (image.width as f32, image.height as f32);
+        let scale = (w / (w * cos + h * sin.abs())).min(h / (w * sin.abs() + h * cos));
+        let new_width = ((w * scale).floor() as i32).max(1);
+        let new_height = ((h * scale).floor() as i32).max(1);
+
+        let (cx, cy) = ((w - 1.0) / 2.0, (h - 1.0) / 2.0);
+        let (ncx, ncy) = ((new_width - 1) as f32 / 2.0, (new_height - 1) as f32 / 2.0);
+
+        let pixels = render_supersampled(image, new_width, new_height, self.supersample, |x, y| {
+            let (dx, dy) = (x - ncx, y - ncy);
+            (cx + dx * cos - dy * sin, cy + dx * sin + dy * cos)
+        });
+
+        Ok(Image {
+            width: new_width,
+            height: new_height,
+            pixels,
+            metadata: image.metadata,
+        })
     }
 }
 
-struct KernelWindow<'a> {
-    image: &'a Image,
-    center_x: i32,
-    center_y: i32,
+// "Little planet" stereographic projection of an equirectangular panorama
+// into an `output_size` square. The bottom row of the source (nadir) lands
+// in the center and the horizon on a circle at half the radius; the angle
+// around the center walks the source horizontally, with straight down
+// showing the middle column.
+pub struct TinyPlanet {
+    output_size: i32,
+    supersample: u8,
 }
 
-impl<'a> KernelWindow<'a> {
-    fn get(&self, dx: i32, dy: i32) -> Pixel {
-        let x = (self.center_x + dx).clamp(0, self.image.width - 1);
-        let y = (self.center_y + dy).clamp(0, self.image.height - 1);
-        self.image.pixels[(y * self.image.width + x) as usize]
+impl TinyPlanet {
+    pub fn new(output_size: i32) -> Self {
+        Self {
+            output_size,
+            supersample: 1,
+        }
     }
 
-    fn apply_kernel(&self, kernel: &Kernel) -> Pixel {
-        let offset = kernel.size / 2;
+    pub fn with_supersample(mut self, supersample: u8) -> Self {
+        self.supersample = supersample;
+        self
+    }
+}
 
-        let mut r: f32 = 0.0;
-        let mut g: f32 = 0.0;
-        let mut b: f32 = 0.0;
+impl Transformation for TinyPlanet {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.output_size <= 0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "tinyplanet".to_string(),
+                details: format!("size must be positive, got {}", self.output_size),
+            });
+        }
+        check_supersample("tinyplanet", self.supersample)?;
+        check_not_empty("tinyplanet", image)?;
 
-        for dy in -offset..=offset {
-            for dx in -offset..=offset {
-                let pixel = self.get(dx, dy);
-                let k = kernel.get(dx, dy);
+        let size = self.output_size;
+        let center = (size - 1) as f32 / 2.0;
+        let radius = (size as f32 / 2.0).max(1.0);
+        let max_x = (image.width - 1) as f32;
+        let max_y = (image.height - 1) as f32;
 
-                r += pixel.r as f32 * k;
-                g += pixel.g as f32 * k;
-                b += pixel.b as f32 * k;
-            }
-        }
+        let pixels = render_supersampled(image, size, size, self.supersample, |x, y| {
+            let dx = x - center;
+            let dy = y - center;
+            let rho = (dx * dx + dy * dy).sqrt() / radius;
 
-        Pixel::from_f32(r, g, b)
+            // Polar angle from the nadir: 0 at the center, 90 degrees at rho 0.5
+            let theta = 2.0 * (2.0 * rho).atan();
+            let phi = dx.atan2(dy);
+
+            let src_x = (phi / std::f32::consts::TAU + 0.5) * max_x;
+            let src_y = max_y - theta / std::f32::consts::PI * max_y;
+            (src_x, src_y)
+        });
+
+        Ok(Image {
+            width: size,
+            height: size,
+            pixels,
+            metadata: image.metadata,
+        })
     }
 }
 
-pub struct GaussianBlur {
-    kernel: Kernel,
+// Radial symmetry around the image center: the circle is split into
+// `segments` equal slices, and every slice shows the first half-slice
+// (starting at 3 o'clock) together with its mirror image, so neighboring
+// slices meet seamlessly. One segment leaves the image unchanged.
+pub struct Kaleidoscope {
+    segments: i32,
+    supersample: u8,
 }
 
-impl GaussianBlur {
-    pub fn new() -> Self {
-        // 3x3 Gaussian kernel:
-        // 1  2  1
-        // 2  4  2
-        // 1  2  1
-        let kernel = Kernel::new(3, vec![1.0, 2.0, 1.0, 2.0, 4.0, 2.0, 1.0, 2.0, 1.0]).normalized();
-        Self { kernel }
+impl Kaleidoscope {
+    pub fn new(segments: i32) -> Self {
+        Self {
+            segments,
+            supersample: 1,
+        }
     }
-}
 
-impl KernelTransformation for GaussianBlur {
-    fn kernel(&self) -> &Kernel {
-        &self.kernel
+    pub fn with_supersample(mut self, supersample: u8) -> Self {
+        self.supersample = supersample;
+        self
     }
 }
 
-pub struct Sharpen {
-    kernel: Kernel,
-}
+impl Transformation for Kaleidoscope {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.segments < 1 {
+            return Err(ProcessError::InvalidInput {
+                operation: "kaleidoscope".to_string(),
+                details: format!("segments must be at least 1, got {}", self.segments),
+            });
+        }
+        check_supersample("kaleidoscope", self.supersample)?;
 
-impl Sharpen {
-    pub fn new() -> Self {
-        // 3x3 sharpen kernel:
-        //  0 -1  0
-        // -1  5 -1
-        //  0 -1  0
-        let kernel = Kernel::new(3, vec![0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0]);
-        Self { kernel }
+        let center_x = (image.width - 1) as f32 / 2.0;
+        let center_y = (image.height - 1) as f32 / 2.0;
+        let slice = std::f32::consts::TAU / self.segments as f32;
+
+        let pixels = render_supersampled(
+            image,
+            image.width,
+            image.height,
+            self.supersample,
+            |x, y| {
+                let dx = x - center_x;
+                let dy = y - center_y;
+                if self.segments == 1 {
+                    return (x, y);
+                }
+
+                let radius = (dx * dx + dy * dy).sqrt();
+                let mut angle = dy.atan2(dx).rem_euclid(slice);
+                if angle > slice / 2.0 {
+                    angle = slice - angle;
+                }
+
+                (
+                    center_x + radius * angle.cos(),
+                    center_y + radius * angle.sin(),
+                )
+            },
+        );
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
     }
 }
 
-impl KernelTransformation for Sharpen {
-    fn kernel(&self) -> &Kernel {
-        &self.kernel
+// Makes an image tile seamlessly: wraps it by half its size so the
+// original edges meet in the middle, then cross-fades a `blend`-pixel band
+// around those seams with the unshifted image, whose interior is continuous.
+// Each axis is blended on its own, so where a vertical band crosses the top
+// and bottom edges it still reads rows from the wrapped image, and vice
+// versa, keeping the tile borders continuous everywhere.
+pub struct MakeSeamless {
+    blend: i32,
+}
+
+impl MakeSeamless {
+    pub fn new(blend: i32) -> Self {
+        Self { blend }
     }
 }
 
-pub struct EdgeDetect {
-    kernel: Kernel,
+impl Transformation for MakeSeamless {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let shift_x = image.width / 2;
+        let shift_y = image.height / 2;
+        let seam_x = image.width - shift_x;
+        let seam_y = image.height - shift_y;
+        // Bands must fade out before reaching the tile borders
+        let band_x = self.blend.clamp(1, (shift_x - 1).max(1)) as f32;
+        let band_y = self.blend.clamp(1, (shift_y - 1).max(1)) as f32;
+
+        // Share of the unshifted image along one axis
+        let weight = |pos: i32, seam: i32, band: f32| {
+            (1.0 - (pos - seam).abs() as f32 / band).clamp(0.0, 1.0)
+        };
+
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let wx = weight(x, seam_x, band_x);
+                let wy = weight(y, seam_y, band_y);
+                let (mut r, mut g, mut b) = (0.0, 0.0, 0.0);
+
+                for (dx, w_x) in [(0, wx), (shift_x, 1.0 - wx)] {
+                    for (dy, w_y) in [(0, wy), (shift_y, 1.0 - wy)] {
+                        let w = w_x * w_y;
+                        if w == 0.0 {
+                            continue;
+                        }
+                        let src_x = (x + dx) % image.width;
+                        let src_y = (y + dy) % image.height;
+                        let p = image.pixels[(src_y * image.width + src_x) as usize];
+                        r += p.r as f32 * w;
+                        g += p.g as f32 * w;
+                        b += p.b as f32 * w;
+                    }
+                }
+
+                pixels.push(Pixel::from_f32(r.round(), g.round(), b.round()));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
 }
 
-impl EdgeDetect {
-    pub fn new() -> Self {
-        // 3x3 edge detection kernel:
-        // -1 -1 -1
-        // -1  8 -1
-        // -1 -1 -1
-        let kernel = Kernel::new(3, vec![-1.0, -1.0, -1.0, -1.0, 8.0, -1.0, -1.0, -1.0, -1.0]);
-        Self { kernel }
+// Grows the image by `pad` pixels on every side, filling the border with a
+// reflection of the content (the edge row or column itself is not
+// repeated). Borders wider than the image keep bouncing back and forth.
+pub struct MirrorPad {
+    pad: i32,
+}
+
+impl MirrorPad {
+    pub fn new(pad: i32) -> Self {
+        Self { pad }
     }
 }
 
-impl KernelTransformation for EdgeDetect {
-    fn kernel(&self) -> &Kernel {
-        &self.kernel
+// Reflects `i` into 0..len without repeating the edge: -1 maps to 1 and
+// len maps to len - 2.
+fn mirror_index(i: i32, len: i32) -> i32 {
+    if len == 1 {
+        return 0;
     }
+
+    let period = 2 * (len - 1);
+    let m = i.rem_euclid(period);
+    if m >= len { period - m } else { m }
 }
 
-pub struct Emboss {
-    kernel: Kernel,
+impl Transformation for MirrorPad {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.pad < 0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "mirrorpad".to_string(),
+                details: format!("pad must not be negative, got {}", self.pad),
+            });
+        }
+        // There is nothing to reflect into a border
+        if self.pad > 0 {
+            check_not_empty("mirrorpad", image)?;
+        }
+
+        let width = image.width + 2 * self.pad;
+        let height = image.height + 2 * self.pad;
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+
+        for y in 0..height {
+            let src_y = mirror_index(y - self.pad, image.height);
+            for x in 0..width {
+                let src_x = mirror_index(x - self.pad, image.width);
+                pixels.push(image.pixels[(src_y * image.width + src_x) as usize]);
+            }
+        }
+
+        Ok(Image {
+            width,
+            height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
 }
 
-impl Emboss {
+// Pixel-to-pixel transformations
+
+pub struct Invert;
+
+impl Invert {
     pub fn new() -> Self {
-        // 3x3 emboss kernel:
-        // -2 -1  0
-        // -1  1  1
-        //  0  1  2
-        let kernel = Kernel::new(3, vec![-2.0, -1.0, 0.0, -1.0, 1.0, 1.0, 0.0, 1.0, 2.0]);
-        Self { kernel }
+        Self
     }
 }
 
-impl KernelTransformation for Emboss {
-    fn kernel(&self) -> &Kernel {
-        &self.kernel
+impl Transformation for Invert {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| Pixel::new(255 - p.r, 255 - p.g, 255 - p.b))
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+pub struct Grayscale;
+
+impl Grayscale {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Transformation for Grayscale {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                let gray = ((p.r as u16 + p.g as u16 + p.b as u16) / 3) as u8;
+                Pixel::new(gray, gray, gray)
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+pub struct Brightness {
+    factor: f32,
+}
+
+impl Brightness {
+    pub fn new(factor: f32) -> Self {
+        Self { factor }
+    }
+}
+
+impl Transformation for Brightness {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                Pixel::from_f32(
+                    p.r as f32 * self.factor,
+                    p.g as f32 * self.factor,
+                    p.b as f32 * self.factor,
+                )
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+pub struct Contrast {
+    factor: f32,
+}
+
+impl Contrast {
+    pub fn new(factor: f32) -> Self {
+        Self { factor }
+    }
+}
+
+impl Transformation for Contrast {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                Pixel::from_f32(
+                    (p.r as f32 - 128.0) * self.factor + 128.0,
+                    (p.g as f32 - 128.0) * self.factor + 128.0,
+                    (p.b as f32 - 128.0) * self.factor + 128.0,
+                )
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+pub struct Tint {
+    color: Pixel,
+    intensity: f32,
+}
+
+impl Tint {
+    pub fn new(color: Pixel, intensity: f32) -> Self {
+        Self { color, intensity }
+    }
+}
+
+impl Transformation for Tint {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let blend =
+            |src: u8, tint: u8| src as f32 * (1.0 - self.intensity) + tint as f32 * self.intensity;
+
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                Pixel::from_f32(
+                    blend(p.r, self.color.r),
+                    blend(p.g, self.color.g),
+                    blend(p.b, self.color.b),
+                )
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+pub struct Colorize {
+    color: Pixel,
+}
+
+impl Colorize {
+    pub fn new(color: Pixel) -> Self {
+        Self { color }
+    }
+}
+
+impl Transformation for Colorize {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                let gray = (p.r as u16 + p.g as u16 + p.b as u16) / 3;
+                let factor = gray as f32 / 255.0;
+                Pixel::from_f32(
+                    self.color.r as f32 * factor,
+                    self.color.g as f32 * factor,
+                    self.color.b as f32 * factor,
+                )
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+pub struct Lightness {
+    delta: f32,
+}
+
+impl Lightness {
+    pub fn new(delta: f32) -> Self {
+        Self { delta }
+    }
+}
+
+impl Transformation for Lightness {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                let (h, s, l) = p.to_hsl();
+                Pixel::from_hsl(h, s, l + self.delta)
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+pub struct Vibrance {
+    amount: f32,
+}
+
+impl Vibrance {
+    pub fn new(amount: f32) -> Self {
+        Self { amount }
+    }
+}
+
+impl Transformation for Vibrance {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                let (h, s, l) = p.to_hsl();
+                // Muted pixels get the full boost, saturated ones almost none
+                let factor = 1.0 + self.amount * (1.0 - s);
+                Pixel::from_hsl(h, s * factor, l)
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// HSL adjustment limited to one hue band: pixels within `hue_range`
+// degrees of `target_hue` get `sat_delta` and `light_delta` added, with a
+// cosine falloff from full strength at the target to none at the band edge.
+// The effect is also scaled by saturation so grays, whose hue is
+// meaningless, stay untouched.
+pub struct SelectiveColor {
+    target_hue: f32,
+    hue_range: f32,
+    sat_delta: f32,
+    light_delta: f32,
+}
+
+impl SelectiveColor {
+    pub fn new(target_hue: f32, hue_range: f32, sat_delta: f32, light_delta: f32) -> Self {
+        Self {
+            target_hue,
+            hue_range,
+            sat_delta,
+            light_delta,
+        }
+    }
+}
+
+impl Transformation for SelectiveColor {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.hue_range <= 0.0 || self.hue_range > 180.0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "selective".to_string(),
+                details: format!("hue range must be in (0, 180], got {}", self.hue_range),
+            });
+        }
+
+        let target = self.target_hue.rem_euclid(360.0);
+
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                let (h, s, l) = p.to_hsl();
+                let diff = (h - target).abs();
+                let distance = diff.min(360.0 - diff);
+                if distance >= self.hue_range {
+                    return *p;
+                }
+
+                let falloff =
+                    0.5 * (1.0 + (std::f32::consts::PI * distance / self.hue_range).cos());
+                let weight = falloff * s;
+                Pixel::from_hsl(
+                    h,
+                    s + self.sat_delta * weight,
+                    l + self.light_delta * weight,
+                )
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+pub struct HistogramMatch {
+    reference: Image,
+}
+
+impl HistogramMatch {
+    pub fn new(reference: Image) -> Self {
+        Self { reference }
+    }
+}
+
+// Cumulative distribution of one channel, normalized to [0, 1].
+fn channel_cdf(image: &Image, channel: impl Fn(&Pixel) -> u8) -> [f32; 256] {
+    let mut histogram = [0u32; 256];
+    for p in &image.pixels {
+        histogram[channel(p) as usize] += 1;
+    }
+
+    let total = image.pixels.len().max(1) as f32;
+    let mut cdf = [0.0; 256];
+    let mut running = 0u32;
+    for (value, count) in histogram.iter().enumerate() {
+        running += count;
+        cdf[value] = running as f32 / total;
+    }
+
+    cdf
+}
+
+// Maps each source value to the smallest reference value whose cumulative
+// share reaches the source's cumulative share.
+fn matching_lut(source: &[f32; 256], reference: &[f32; 256]) -> [u8; 256] {
+    let mut lut = [0u8; 256];
+    let mut r = 0;
+
+    for (value, &share) in source.iter().enumerate() {
+        while r < 255 && reference[r] < share - f32::EPSILON {
+            r += 1;
+        }
+        lut[value] = r as u8;
+    }
+
+    lut
+}
+
+impl Transformation for HistogramMatch {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let lut_r = matching_lut(
+            &channel_cdf(image, |p| p.r),
+            &channel_cdf(&self.reference, |p| p.r),
+        );
+        let lut_g = matching_lut(
+            &channel_cdf(image, |p| p.g),
+            &channel_cdf(&self.reference, |p| p.g),
+        );
+        let lut_b = matching_lut(
+            &channel_cdf(image, |p| p.b),
+            &channel_cdf(&self.reference, |p| p.b),
+        );
+
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                Pixel::new(
+                    lut_r[p.r as usize],
+                    lut_g[p.g as usize],
+                    lut_b[p.b as usize],
+                )
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Stretches levels to the full 0-255 range after ignoring the darkest and
+// brightest `clip` fraction of pixels. Per channel, each of R, G and B gets
+// its own range, which also removes color casts; otherwise all channels
+// share the range found in luma.
+pub struct AutoLevels {
+    per_channel: bool,
+    clip: f32,
+}
+
+impl AutoLevels {
+    pub fn new(per_channel: bool, clip: f32) -> Self {
+        Self { per_channel, clip }
+    }
+
+    fn stretch_lut(&self, cdf: &[f32; 256]) -> [u8; 256] {
+        let low = cdf.iter().position(|&share| share > self.clip).unwrap_or(0);
+        let high = cdf
+            .iter()
+            .position(|&share| share >= 1.0 - self.clip)
+            .unwrap_or(255);
+
+        let mut lut = [0u8; 256];
+        for (value, entry) in lut.iter_mut().enumerate() {
+            *entry = if high > low {
+                let scaled = (value as f32 - low as f32) * 255.0 / (high - low) as f32;
+                scaled.round().clamp(0.0, 255.0) as u8
+            } else {
+                value as u8
+            };
+        }
+
+        lut
+    }
+}
+
+impl Transformation for AutoLevels {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if !(0.0..0.5).contains(&self.clip) {
+            return Err(ProcessError::InvalidInput {
+                operation: "autolevels".to_string(),
+                details: format!("clip must be in [0, 0.5), got {}", self.clip),
+            });
+        }
+
+        let (lut_r, lut_g, lut_b) = if self.per_channel {
+            (
+                self.stretch_lut(&channel_cdf(image, |p| p.r)),
+                self.stretch_lut(&channel_cdf(image, |p| p.g)),
+                self.stretch_lut(&channel_cdf(image, |p| p.b)),
+            )
+        } else {
+            let lut = self.stretch_lut(&channel_cdf(image, |p| p.luma().round() as u8));
+            (lut, lut, lut)
+        };
+
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                Pixel::new(
+                    lut_r[p.r as usize],
+                    lut_g[p.g as usize],
+                    lut_b[p.b as usize],
+                )
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Reinhard global tone mapping. Linear luminance is scaled so the
+// log-average luminance of the image lands on `key` (0.18 is the classic
+// middle gray), then compressed with L / (1 + L), which leaves shadows
+// nearly linear and rolls highlights off without ever reaching white.
+// Each pixel's linear RGB is scaled by its luminance ratio to keep hue.
+pub struct ToneMapReinhard {
+    key: f32,
+}
+
+impl ToneMapReinhard {
+    pub fn new(key: f32) -> Self {
+        Self { key }
+    }
+}
+
+impl Transformation for ToneMapReinhard {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.key <= 0.0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "tonemap".to_string(),
+                details: format!("key must be positive, got {}", self.key),
+            });
+        }
+
+        let linear: Vec<[f32; 3]> = image
+            .pixels
+            .iter()
+            .map(|p| [p.r, p.g, p.b].map(|v| srgb_to_linear(v as f32 / 255.0)))
+            .collect();
+        let luminance = |c: &[f32; 3]| 0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2];
+
+        // Small offset keeps log() finite for black pixels
+        let count = linear.len().max(1) as f32;
+        let log_sum: f32 = linear.iter().map(|c| (1e-4 + luminance(c)).ln()).sum();
+        let log_average = (log_sum / count).exp();
+
+        let pixels: Vec<Pixel> = linear
+            .iter()
+            .map(|c| {
+                let lum = luminance(c);
+                if lum <= 0.0 {
+                    return Pixel::new(0, 0, 0);
+                }
+
+                let scaled = self.key * lum / log_average;
+                let ratio = scaled / (1.0 + scaled) / lum;
+                let [r, g, b] =
+                    c.map(|v| (linear_to_srgb((v * ratio).clamp(0.0, 1.0)) * 255.0).round());
+                Pixel::from_f32(r, g, b)
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Approximate sRGB color (0-255 per channel) of a blackbody radiator at
+// `kelvin`, after Tanner Helland's curve fit. Valid for 1000-40000 K.
+fn blackbody_rgb(kelvin: f32) -> [f32; 3] {
+    let t = kelvin / 100.0;
+
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.6987 * (t - 60.0).powf(-0.1332048)
+    };
+    let green = if t <= 66.0 {
+        99.4708 * t.ln() - 161.1196
+    } else {
+        288.1222 * (t - 60.0).powf(-0.07551485)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.5177 * (t - 10.0).ln() - 305.0448
+    };
+
+    [red, green, blue].map(|v| v.clamp(0.0, 255.0))
+}
+
+// Color temperature in Kelvin: channels are multiplied by the blackbody
+// color at `kelvin` relative to 6500 K (daylight), so 6500 is neutral,
+// lower values warm the image and higher values cool it. `tint` (-1 to 1)
+// shifts green toward magenta when positive and the other way when negative.
+pub struct Temperature {
+    kelvin: f32,
+    tint: f32,
+}
+
+impl Temperature {
+    pub fn new(kelvin: f32, tint: f32) -> Self {
+        Self { kelvin, tint }
+    }
+}
+
+impl Transformation for Temperature {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if !(1000.0..=40000.0).contains(&self.kelvin) || !(-1.0..=1.0).contains(&self.tint) {
+            return Err(ProcessError::InvalidInput {
+                operation: "temperature".to_string(),
+                details: format!(
+                    "kelvin must be 1000-40000 and tint -1 to 1, got {} and {}",
+                    self.kelvin, self.tint
+                ),
+            });
+        }
+
+        let target = blackbody_rgb(self.kelvin);
+        let neutral = blackbody_rgb(6500.0);
+        let mut gains: [f32; 3] = std::array::from_fn(|i| target[i] / neutral[i]);
+        gains[1] *= 1.0 - 0.25 * self.tint;
+
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                Pixel::from_f32(
+                    (p.r as f32 * gains[0]).round(),
+                    (p.g as f32 * gains[1]).round(),
+                    (p.b as f32 * gains[2]).round(),
+                )
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ColorDistance {
+    // Euclidean distance between RGB triples (0-441)
+    Rgb,
+    // CIE76 DeltaE in Lab space, closer to perceived difference
+    Lab,
+}
+
+impl ColorDistance {
+    fn between(self, a: Pixel, b: Pixel) -> f32 {
+        match self {
+            ColorDistance::Rgb => a.distance(b),
+            ColorDistance::Lab => a.delta_e(b),
+        }
+    }
+}
+
+// Replaces pixels within `tolerance` of `from` with `to`. The match weight
+// falls linearly from 1 at an exact match to 0 at the tolerance edge, and
+// each pixel is blended toward `to` by that weight in Lab space so the
+// boundary of the replaced area stays soft.
+pub struct ReplaceColor {
+    from: Pixel,
+    to: Pixel,
+    tolerance: f32,
+    distance: ColorDistance,
+}
+
+impl ReplaceColor {
+    pub fn new(from: Pixel, to: Pixel, tolerance: f32, distance: ColorDistance) -> Self {
+        Self {
+            from,
+            to,
+            tolerance,
+            distance,
+        }
+    }
+
+    fn weight(&self, p: Pixel) -> f32 {
+        let distance = self.distance.between(p, self.from);
+
+        if distance > self.tolerance {
+            0.0
+        } else if self.tolerance <= 0.0 {
+            1.0
+        } else {
+            1.0 - distance / self.tolerance
+        }
+    }
+}
+
+impl Transformation for ReplaceColor {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let (to_l, to_a, to_b) = self.to.to_lab();
+
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|&p| {
+                let w = self.weight(p);
+                if w == 0.0 {
+                    return p;
+                }
+
+                let (l, a, b) = p.to_lab();
+                let mix = |from: f32, to: f32| from + (to - from) * w;
+                Pixel::from_lab(mix(l, to_l), mix(a, to_a), mix(b, to_b))
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum CbKind {
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+// Simulates dichromatic vision (Vienot et al. 1999): linear RGB is moved
+// into LMS cone space, the missing cone response is rebuilt from the other
+// two, and the result is converted back.
+pub struct ColorBlind {
+    kind: CbKind,
+}
+
+impl ColorBlind {
+    pub fn new(kind: CbKind) -> Self {
+        Self { kind }
+    }
+}
+
+const RGB_TO_LMS: [[f32; 3]; 3] = [
+    [17.8824, 43.5161, 4.11935],
+    [3.45565, 27.1554, 3.86714],
+    [0.0299566, 0.184309, 1.46709],
+];
+
+const LMS_TO_RGB: [[f32; 3]; 3] = [
+    [0.08094445, -0.1305044, 0.1167211],
+    [-0.01024853, 0.05401933, -0.1136147],
+    [-0.0003652969, -0.004121615, 0.6935114],
+];
+
+fn mat3_mul(m: &[[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    [
+        m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+        m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+        m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+    ]
+}
+
+impl Transformation for ColorBlind {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                let linear = [p.r, p.g, p.b].map(|v| srgb_to_linear(v as f32 / 255.0));
+                let [l, m, s] = mat3_mul(&RGB_TO_LMS, linear);
+
+                let lms = match self.kind {
+                    CbKind::Protanopia => [2.02344 * m - 2.52581 * s, m, s],
+                    CbKind::Deuteranopia => [l, 0.494207 * l + 1.24827 * s, s],
+                    CbKind::Tritanopia => [l, m, -0.395913 * l + 0.801109 * m],
+                };
+
+                let [r, g, b] = mat3_mul(&LMS_TO_RGB, lms)
+                    .map(|v| (linear_to_srgb(v.clamp(0.0, 1.0)) * 255.0).round());
+                Pixel::from_f32(r, g, b)
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Removes red-eye inside a circle: pixels where red clearly dominates
+// green and blue are replaced by their own luma, which is dark for a
+// saturated red. Other pixels in the circle are left untouched.
+pub struct RedEye {
+    x: i32,
+    y: i32,
+    radius: i32,
+}
+
+impl RedEye {
+    pub fn new(x: i32, y: i32, radius: i32) -> Self {
+        Self { x, y, radius }
+    }
+
+    fn is_red(p: Pixel) -> bool {
+        let r = p.r as f32;
+        let others = (p.g as f32 + p.b as f32) / 2.0;
+        r > 64.0 && r > others * 1.8
+    }
+}
+
+impl Transformation for RedEye {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let mut pixels = image.pixels.clone();
+
+        let x0 = (self.x - self.radius).max(0);
+        let x1 = (self.x + self.radius).min(image.width - 1);
+        let y0 = (self.y - self.radius).max(0);
+        let y1 = (self.y + self.radius).min(image.height - 1);
+
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let (dx, dy) = (x - self.x, y - self.y);
+                if dx * dx + dy * dy > self.radius * self.radius {
+                    continue;
+                }
+
+                let idx = (y * image.width + x) as usize;
+                let p = pixels[idx];
+                if Self::is_red(p) {
+                    let luma = p.luma().round() as u8;
+                    pixels[idx] = Pixel::new(luma, luma, luma);
+                }
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum HeatmapPalette {
+    Jet,
+    Viridis,
+    Grayscale,
+    Inferno,
+}
+
+impl HeatmapPalette {
+    // Color stops as (position in [0, 1], hex color).
+    fn stops(self) -> &'static [(f32, u32)] {
+        match self {
+            HeatmapPalette::Jet => &[
+                (0.0, 0x00007F),
+                (0.125, 0x0000FF),
+                (0.375, 0x00FFFF),
+                (0.625, 0xFFFF00),
+                (0.875, 0xFF0000),
+                (1.0, 0x7F0000),
+            ],
+            HeatmapPalette::Viridis => &[
+                (0.0, 0x440154),
+                (0.125, 0x472C7A),
+                (0.25, 0x3B518B),
+                (0.375, 0x2C718E),
+                (0.5, 0x21908D),
+                (0.625, 0x27AD81),
+                (0.75, 0x5CC863),
+                (0.875, 0xAADC32),
+                (1.0, 0xFDE725),
+            ],
+            HeatmapPalette::Grayscale => &[(0.0, 0x000000), (1.0, 0xFFFFFF)],
+            HeatmapPalette::Inferno => &[
+                (0.0, 0x000004),
+                (0.125, 0x1B0C41),
+                (0.25, 0x4A0C6B),
+                (0.375, 0x781C6D),
+                (0.5, 0xA52C60),
+                (0.625, 0xCF4446),
+                (0.75, 0xED6925),
+                (0.875, 0xFB9B06),
+                (1.0, 0xFCFFA4),
+            ],
+        }
+    }
+
+    // Expands the stops into a 256-entry lookup table.
+    fn lut(self) -> Vec<Pixel> {
+        let stops: Vec<(f32, Pixel)> = self
+            .stops()
+            .iter()
+            .map(|&(pos, hex)| (pos, Pixel::from_hex(hex)))
+            .collect();
+        gradient_lut(&stops)
+    }
+}
+
+// Expands sorted color stops (position in [0, 1], color) into a 256-entry
+// lookup table, interpolating linearly between neighboring stops.
+fn gradient_lut(stops: &[(f32, Pixel)]) -> Vec<Pixel> {
+    (0..256)
+        .map(|i| {
+            let t = i as f32 / 255.0;
+            let upper = stops
+                .iter()
+                .position(|&(pos, _)| pos >= t)
+                .unwrap_or(stops.len() - 1);
+            if upper == 0 {
+                return stops[0].1;
+            }
+
+            let (p0, a) = stops[upper - 1];
+            let (p1, b) = stops[upper];
+            let f = (t - p0) / (p1 - p0);
+            Pixel::from_f32(
+                a.r as f32 + (b.r as f32 - a.r as f32) * f + 0.5,
+                a.g as f32 + (b.g as f32 - a.g as f32) * f + 0.5,
+                a.b as f32 + (b.b as f32 - a.b as f32) * f + 0.5,
+            )
+        })
+        .collect()
+}
+
+// False-colors the image by mapping each pixel's luma through a palette,
+// dark to the palette's low end and bright to its high end.
+pub struct Heatmap {
+    palette: HeatmapPalette,
+}
+
+impl Heatmap {
+    pub fn new(palette: HeatmapPalette) -> Self {
+        Self { palette }
+    }
+}
+
+impl Transformation for Heatmap {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let lut = self.palette.lut();
+
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| lut[p.luma().round().clamp(0.0, 255.0) as usize])
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// False colors for zones 0-X, cool shadows through green midtones to
+// warm highlights, with magenta flagging paper white.
+const ZONE_COLORS: [u32; 11] = [
+    0x1A0033, 0x3B1F9E, 0x2E5FE0, 0x1F9BD6, 0x22B89A, 0x4CC24A, 0xB5D334, 0xF2D33A, 0xF59A2C,
+    0xE8502A, 0xFF4FD8,
+];
+
+// Ansel Adams zone system diagnostic: luma is split into eleven equal
+// bands, zone 0 (pure black) to zone X (pure white) with mid-gray in
+// zone V, and each pixel is painted its zone's color.
+pub struct ZoneOverlay;
+
+impl ZoneOverlay {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Transformation for ZoneOverlay {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                let zone = (p.luma() / 255.0 * 10.0).round().clamp(0.0, 10.0) as usize;
+                Pixel::from_hex(ZONE_COLORS[zone])
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Gradient map: each pixel's luma is looked up in a gradient built from
+// `stops` (position in [0, 1], color). Positions must be non-decreasing and
+// run from 0.0 to 1.0; a repeated position makes a hard color edge.
+pub struct GradientMap {
+    stops: Vec<(f32, Pixel)>,
+}
+
+impl GradientMap {
+    pub fn new(stops: Vec<(f32, Pixel)>) -> Self {
+        Self { stops }
+    }
+}
+
+impl Transformation for GradientMap {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let invalid = |details: String| ProcessError::InvalidInput {
+            operation: "gradientmap".to_string(),
+            details,
+        };
+
+        if self.stops.len() < 2 {
+            return Err(invalid(format!(
+                "need at least 2 stops, got {}",
+                self.stops.len()
+            )));
+        }
+        if self.stops.windows(2).any(|w| w[1].0 < w[0].0) {
+            return Err(invalid("stop positions must be sorted".to_string()));
+        }
+        let (first, last) = (self.stops[0].0, self.stops[self.stops.len() - 1].0);
+        if first != 0.0 || last != 1.0 {
+            return Err(invalid(format!(
+                "stops must cover 0.0-1.0, got {}-{}",
+                first, last
+            )));
+        }
+
+        let lut = gradient_lut(&self.stops);
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| lut[p.luma().round().clamp(0.0, 255.0) as usize])
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Converts a scanned color negative to a positive: each channel is divided
+// by the film base color (the orange mask) and then inverted. Without an
+// explicit base, the average of the brightest 1% of pixels is used, since
+// unexposed film is the brightest part of a negative scan.
+pub struct FilmNegative {
+    base_color: Option<Pixel>,
+}
+
+impl FilmNegative {
+    pub fn new(base_color: Pixel) -> Self {
+        Self {
+            base_color: Some(base_color),
+        }
+    }
+
+    pub fn auto() -> Self {
+        Self { base_color: None }
+    }
+
+    fn detect_base(image: &Image) -> Pixel {
+        let mut by_luma: Vec<Pixel> = image.pixels.clone();
+        by_luma.sort_by(|a, b| b.luma().total_cmp(&a.luma()));
+
+        let count = (by_luma.len() / 100).max(1);
+        let brightest = &by_luma[..count];
+        let sum = |channel: fn(&Pixel) -> u8| {
+            brightest.iter().map(|p| channel(p) as f32).sum::<f32>() / count as f32
+        };
+
+        Pixel::from_f32(sum(|p| p.r), sum(|p| p.g), sum(|p| p.b))
+    }
+}
+
+impl Transformation for FilmNegative {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        // No pixels to detect the film base from, and none to convert
+        if image.pixels.is_empty() {
+            return Ok(Image {
+                width: image.width,
+                height: image.height,
+                pixels: Vec::new(),
+                metadata: image.metadata,
+            });
+        }
+
+        let base = self.base_color.unwrap_or_else(|| Self::detect_base(image));
+        let scale = |value: u8, base: u8| {
+            let normalized = value as f32 / (base.max(1) as f32);
+            255.0 - normalized.min(1.0) * 255.0
+        };
+
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| {
+                Pixel::from_f32(
+                    scale(p.r, base.r).round(),
+                    scale(p.g, base.g).round(),
+                    scale(p.b, base.b).round(),
+                )
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Applies a caller-supplied closure to every pixel along with its (x, y)
+// coordinates, for point operations that don't warrant their own type.
+// The crate is a binary with no library target, so doctests cannot run;
+// the usage example, a vignette, is the unit test
+// `map_pixels_builds_a_vignette` below instead. Rows go through `Image::process_rows`, so the closure
+// must be `Sync` and runs on several threads with the `parallel` feature.
+pub struct MapPixels<F>
+where
+    F: Fn(i32, i32, Pixel) -> Pixel + Sync,
+{
+    f: F,
+}
+
+impl<F> MapPixels<F>
+where
+    F: Fn(i32, i32, Pixel) -> Pixel + Sync,
+{
+    pub fn new(f: F) -> Self {
+        Self { f }
+    }
+}
+
+impl<F> Transformation for MapPixels<F>
+where
+    F: Fn(i32, i32, Pixel) -> Pixel + Sync,
+{
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        Ok(image.process_rows(|y, row| {
+            row.iter()
+                .enumerate()
+                .map(|(x, &p)| (self.f)(x as i32, y, p))
+                .collect()
+        }))
+    }
+}
+
+// Per-channel point operations that AdjustmentStack can fuse. Each maps a
+// channel value exactly as the matching standalone transform does.
+enum PointOp {
+    Brightness(f32),
+    Contrast(f32),
+    Invert,
+}
+
+impl PointOp {
+    fn map(&self, value: u8) -> u8 {
+        let v = value as f32;
+        let out = match self {
+            PointOp::Brightness(factor) => v * factor,
+            PointOp::Contrast(factor) => (v - 128.0) * factor + 128.0,
+            PointOp::Invert => 255.0 - v,
+        };
+        out.clamp(0.0, 255.0) as u8
+    }
+}
+
+// Collects point adjustments and fuses them into one 256-entry lookup
+// table, so a chain of N adjustments costs a single pass over the pixels
+// instead of N passes and N intermediate images. The result matches
+// applying the same steps one after another.
+pub struct AdjustmentStack {
+    ops: Vec<PointOp>,
+}
+
+impl AdjustmentStack {
+    pub fn new() -> Self {
+        Self { ops: Vec::new() }
+    }
+
+    pub fn brightness(mut self, factor: f32) -> Self {
+        self.ops.push(PointOp::Brightness(factor));
+        self
+    }
+
+    pub fn contrast(mut self, factor: f32) -> Self {
+        self.ops.push(PointOp::Contrast(factor));
+        self
+    }
+
+    pub fn invert(mut self) -> Self {
+        self.ops.push(PointOp::Invert);
+        self
+    }
+
+    pub fn build(self) -> FusedAdjustment {
+        let mut lut = [0u8; 256];
+        for (value, entry) in lut.iter_mut().enumerate() {
+            *entry = self.ops.iter().fold(value as u8, |v, op| op.map(v));
+        }
+
+        FusedAdjustment { lut }
+    }
+}
+
+// A composed AdjustmentStack: one lookup per channel per pixel.
+pub struct FusedAdjustment {
+    lut: [u8; 256],
+}
+
+impl Transformation for FusedAdjustment {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let lut = &self.lut;
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .map(|p| Pixel::new(lut[p.r as usize], lut[p.g as usize], lut[p.b as usize]))
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Kernel filters
+
+trait KernelTransformation {
+    fn kernel(&self) -> &Kernel;
+}
+
+impl<T: KernelTransformation> Transformation for T {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let kernel = self.kernel();
+        let mut pixels = vec![Pixel::new(0, 0, 0); (image.width * image.height) as usize];
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let window = KernelWindow {
+                    image,
+                    center_x: x,
+                    center_y: y,
+                };
+                let idx = (y * image.width + x) as usize;
+                pixels[idx] = window.apply_kernel(kernel);
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Square convolution kernel with odd side length, stored row-major.
+pub struct Kernel {
+    size: i32,
+    values: Vec<f32>,
+}
+
+impl Kernel {
+    fn new(size: i32, values: Vec<f32>) -> Kernel {
+        assert!(size % 2 == 1, "Kernel size must be odd");
+        assert_eq!(values.len(), (size * size) as usize);
+
+        Self { size, values }
+    }
+
+    // Builds a kernel from row-major values, which must form an odd square
+    // (9 values for 3x3, 25 for 5x5, ...).
+    pub fn from_values(values: Vec<f32>) -> Result<Kernel, ProcessError> {
+        let size = (values.len() as f32).sqrt().round() as i32;
+        if size % 2 == 0 || (size * size) as usize != values.len() {
+            return Err(ProcessError::InvalidInput {
+                operation: "kernel".to_string(),
+                details: format!("{} values do not form an odd square", values.len()),
+            });
+        }
+
+        Ok(Self::new(size, values))
+    }
+
+    // 1x1 kernel that leaves the channel unchanged.
+    pub fn identity() -> Kernel {
+        Self::new(1, vec![1.0])
+    }
+
+    fn normalize(&mut self) {
+        let sum: f32 = self.values.iter().sum();
+
+        if sum.abs() > f32::EPSILON {
+            for val in &mut self.values {
+                *val /= sum;
+            }
+        }
+    }
+
+    fn normalized(mut self) -> Self {
+        self.normalize();
+        self
+    }
+
+    // Normalized 2D Gaussian covering offsets -radius..=radius.
+    fn gaussian(radius: i32, sigma: f32) -> Kernel {
+        let size = radius * 2 + 1;
+        let mut values = Vec::with_capacity((size * size) as usize);
+
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let d2 = (dx * dx + dy * dy) as f32;
+                values.push((-d2 / (2.0 * sigma * sigma)).exp());
+            }
+        }
+
+        Kernel::new(size, values).normalized()
+    }
+
+    fn get(&self, dx: i32, dy: i32) -> f32 {
+        let center = self.size / 2;
+        let x = center + dx;
+        let y = center + dy;
+        self.values[(y * self.size + x) as usize]
+    }
+}
+
+struct KernelWindow<'a> {
+    image: &'a Image,
+    center_x: i32,
+    center_y: i32,
+}
+
+impl<'a> KernelWindow<'a> {
+    fn get(&self, dx: i32, dy: i32) -> Pixel {
+        let x = (self.center_x + dx).clamp(0, self.image.width - 1);
+        let y = (self.center_y + dy).clamp(0, self.image.height - 1);
+        self.image.pixels[(y * self.image.width + x) as usize]
+    }
+
+    fn apply_kernel(&self, kernel: &Kernel) -> Pixel {
+        let offset = kernel.size / 2;
+
+        let mut r: f32 = 0.0;
+        let mut g: f32 = 0.0;
+        let mut b: f32 = 0.0;
+
+        for dy in -offset..=offset {
+            for dx in -offset..=offset {
+                let pixel = self.get(dx, dy);
+                let k = kernel.get(dx, dy);
+
+                r += pixel.r as f32 * k;
+                g += pixel.g as f32 * k;
+                b += pixel.b as f32 * k;
+            }
+        }
+
+        Pixel::from_f32(r, g, b)
+    }
+
+    // Sobel gradient (gx, gy) of luma at the window center.
+    fn sobel(&self) -> (f32, f32) {
+        let l = |dx, dy| self.get(dx, dy).luma();
+
+        let gx = (l(1, -1) + 2.0 * l(1, 0) + l(1, 1)) - (l(-1, -1) + 2.0 * l(-1, 0) + l(-1, 1));
+        let gy = (l(-1, 1) + 2.0 * l(0, 1) + l(1, 1)) - (l(-1, -1) + 2.0 * l(0, -1) + l(1, -1));
+
+        (gx, gy)
+    }
+
+    fn apply_channel(&self, kernel: &Kernel, channel: impl Fn(Pixel) -> u8) -> u8 {
+        let offset = kernel.size / 2;
+        let mut sum: f32 = 0.0;
+
+        for dy in -offset..=offset {
+            for dx in -offset..=offset {
+                sum += channel(self.get(dx, dy)) as f32 * kernel.get(dx, dy);
+            }
+        }
+
+        sum.clamp(0.0, 255.0) as u8
+    }
+}
+
+pub struct GaussianBlur {
+    kernel: Kernel,
+}
+
+impl GaussianBlur {
+    pub fn new() -> Self {
+        // 3x3 Gaussian kernel:
+        // 1  2  1
+        // 2  4  2
+        // 1  2  1
+        let kernel = Kernel::new(3, vec![1.0, 2.0, 1.0, 2.0, 4.0, 2.0, 1.0, 2.0, 1.0]).normalized();
+        Self { kernel }
+    }
+
+    // Larger blur with sigma = radius / 3. The kernel is (2r+1)^2, so cost
+    // grows with the square of the radius.
+    pub fn with_radius(radius: i32) -> Self {
+        let radius = radius.max(0);
+        let sigma = (radius as f32 / 3.0).max(0.5);
+        Self {
+            kernel: Kernel::gaussian(radius, sigma),
+        }
+    }
+
+    // Blur with an explicit sigma; the kernel extends to 3 sigma.
+    pub fn with_sigma(sigma: f32) -> Self {
+        let sigma = sigma.max(0.1);
+        Self {
+            kernel: Kernel::gaussian((sigma * 3.0).ceil() as i32, sigma),
+        }
+    }
+}
+
+impl KernelTransformation for GaussianBlur {
+    fn kernel(&self) -> &Kernel {
+        &self.kernel
+    }
+}
+
+pub struct Sharpen {
+    kernel: Kernel,
+}
+
+impl Sharpen {
+    pub fn new() -> Self {
+        // 3x3 sharpen kernel:
+        //  0 -1  0
+        // -1  5 -1
+        //  0 -1  0
+        let kernel = Kernel::new(3, vec![0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0]);
+        Self { kernel }
+    }
+}
+
+impl KernelTransformation for Sharpen {
+    fn kernel(&self) -> &Kernel {
+        &self.kernel
+    }
+}
+
+pub struct EdgeDetect {
+    kernel: Kernel,
+}
+
+impl EdgeDetect {
+    pub fn new() -> Self {
+        // 3x3 edge detection kernel:
+        // -1 -1 -1
+        // -1  8 -1
+        // -1 -1 -1
+        let kernel = Kernel::new(3, vec![-1.0, -1.0, -1.0, -1.0, 8.0, -1.0, -1.0, -1.0, -1.0]);
+        Self { kernel }
+    }
+}
+
+impl KernelTransformation for EdgeDetect {
+    fn kernel(&self) -> &Kernel {
+        &self.kernel
+    }
+}
+
+pub struct Emboss {
+    kernel: Kernel,
+}
+
+impl Emboss {
+    pub fn new() -> Self {
+        // 3x3 emboss kernel:
+        // -2 -1  0
+        // -1  1  1
+        //  0  1  2
+        let kernel = Kernel::new(3, vec![-2.0, -1.0, 0.0, -1.0, 1.0, 1.0, 0.0, 1.0, 2.0]);
+        Self { kernel }
+    }
+}
+
+impl KernelTransformation for Emboss {
+    fn kernel(&self) -> &Kernel {
+        &self.kernel
+    }
+}
+
+// Convolves red, green and blue with their own kernels, e.g. sharpening
+// one channel while blurring another. Build with `PerChannelConvolve::builder()`;
+// channels without a kernel are left unchanged.
+pub struct PerChannelConvolve {
+    r: Kernel,
+    g: Kernel,
+    b: Kernel,
+}
+
+impl PerChannelConvolve {
+    pub fn builder() -> PerChannelConvolveBuilder {
+        PerChannelConvolveBuilder {
+            r: None,
+            g: None,
+            b: None,
+        }
+    }
+}
+
+pub struct PerChannelConvolveBuilder {
+    r: Option<Kernel>,
+    g: Option<Kernel>,
+    b: Option<Kernel>,
+}
+
+impl PerChannelConvolveBuilder {
+    pub fn red(mut self, kernel: Kernel) -> Self {
+        self.r = Some(kernel);
+        self
+    }
+
+    pub fn green(mut self, kernel: Kernel) -> Self {
+        self.g = Some(kernel);
+        self
+    }
+
+    pub fn blue(mut self, kernel: Kernel) -> Self {
+        self.b = Some(kernel);
+        self
+    }
+
+    pub fn build(self) -> PerChannelConvolve {
+        PerChannelConvolve {
+            r: self.r.unwrap_or_else(Kernel::identity),
+            g: self.g.unwrap_or_else(Kernel::identity),
+            b: self.b.unwrap_or_else(Kernel::identity),
+        }
+    }
+}
+
+impl Transformation for PerChannelConvolve {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let window = KernelWindow {
+                    image,
+                    center_x: x,
+                    center_y: y,
+                };
+
+                pixels.push(Pixel::new(
+                    window.apply_channel(&self.r, |p| p.r),
+                    window.apply_channel(&self.g, |p| p.g),
+                    window.apply_channel(&self.b, |p| p.b),
+                ));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Unsharp masking on luma only: the image is split into YCbCr, Y is
+// sharpened as Y + amount * (Y - blur(Y)) with a Gaussian of `radius`, and
+// the untouched Cb/Cr are recombined. Color noise and fringes are not
+// amplified the way RGB sharpening does.
+pub struct LumaSharpen {
+    amount: f32,
+    radius: i32,
+}
+
+impl LumaSharpen {
+    pub fn new(amount: f32, radius: i32) -> Self {
+        Self { amount, radius }
+    }
+}
+
+impl Transformation for LumaSharpen {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.radius < 1 || self.amount < 0.0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "lumasharpen".to_string(),
+                details: format!(
+                    "radius must be at least 1 and amount non-negative, got {} and {}",
+                    self.radius, self.amount
+                ),
+            });
+        }
+
+        let ycbcr: Vec<(f32, f32, f32)> = image.pixels.iter().map(|p| p.to_ycbcr()).collect();
+
+        // Blur Y in floating point; a u8 blur would drift flat areas by rounding
+        let kernel = Kernel::gaussian(self.radius, (self.radius as f32 / 3.0).max(0.5));
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let mut blurred = 0.0;
+                for dy in -self.radius..=self.radius {
+                    for dx in -self.radius..=self.radius {
+                        let sx = (x + dx).clamp(0, image.width - 1);
+                        let sy = (y + dy).clamp(0, image.height - 1);
+                        blurred += ycbcr[(sy * image.width + sx) as usize].0 * kernel.get(dx, dy);
+                    }
+                }
+
+                let (luma, cb, cr) = ycbcr[(y * image.width + x) as usize];
+                let sharpened = luma + self.amount * (luma - blurred);
+                pixels.push(Pixel::from_ycbcr(sharpened, cb, cr));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// The counterpart of LumaSharpen: Cb and Cr are blurred with a Gaussian of
+// `radius` while Y is kept as is. The eye resolves far less color detail
+// than brightness, so this removes chroma noise without softening edges.
+pub struct ChromaBlur {
+    radius: i32,
+}
+
+impl ChromaBlur {
+    pub fn new(radius: i32) -> Self {
+        Self { radius }
+    }
+}
+
+impl Transformation for ChromaBlur {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.radius < 1 {
+            return Err(ProcessError::InvalidInput {
+                operation: "chromablur".to_string(),
+                details: format!("radius must be at least 1, got {}", self.radius),
+            });
+        }
+
+        let ycbcr: Vec<(f32, f32, f32)> = image.pixels.iter().map(|p| p.to_ycbcr()).collect();
+
+        let kernel = Kernel::gaussian(self.radius, (self.radius as f32 / 3.0).max(0.5));
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let (mut cb, mut cr) = (0.0, 0.0);
+                for dy in -self.radius..=self.radius {
+                    for dx in -self.radius..=self.radius {
+                        let sx = (x + dx).clamp(0, image.width - 1);
+                        let sy = (y + dy).clamp(0, image.height - 1);
+                        let (_, scb, scr) = ycbcr[(sy * image.width + sx) as usize];
+                        let weight = kernel.get(dx, dy);
+                        cb += scb * weight;
+                        cr += scr * weight;
+                    }
+                }
+
+                let luma = ycbcr[(y * image.width + x) as usize].0;
+                pixels.push(Pixel::from_ycbcr(luma, cb, cr));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Focus peaking: a Laplacian high-pass on luma measures local sharpness.
+// Pixels above `threshold` are painted `color`; the rest show the image in
+// grayscale so the highlights stand out.
+pub struct FocusPeaking {
+    color: Pixel,
+    threshold: f32,
+}
+
+impl FocusPeaking {
+    pub fn new(color: Pixel, threshold: f32) -> Self {
+        Self { color, threshold }
+    }
+}
+
+impl Transformation for FocusPeaking {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let window = KernelWindow {
+                    image,
+                    center_x: x,
+                    center_y: y,
+                };
+
+                let center = window.get(0, 0).luma();
+                let mut neighbors = 0.0;
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        if dx != 0 || dy != 0 {
+                            neighbors += window.get(dx, dy).luma();
+                        }
+                    }
+                }
+                let sharpness = (center * 8.0 - neighbors).abs();
+
+                pixels.push(if sharpness > self.threshold {
+                    self.color
+                } else {
+                    let gray = center.round();
+                    Pixel::from_f32(gray, gray, gray)
+                });
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Neighborhood transformations
+
+pub struct NormalMap {
+    strength: f32,
+}
+
+impl NormalMap {
+    pub fn new(strength: f32) -> Self {
+        Self { strength }
+    }
+}
+
+impl Transformation for NormalMap {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let mut pixels = vec![Pixel::new(0, 0, 0); (image.width * image.height) as usize];
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let window = KernelWindow {
+                    image,
+                    center_x: x,
+                    center_y: y,
+                };
+                let height = |dx: i32, dy: i32| window.get(dx, dy).luma() / 255.0;
+
+                // Sobel gradients of the heightfield
+                let dx = (height(1, -1) + 2.0 * height(1, 0) + height(1, 1))
+                    - (height(-1, -1) + 2.0 * height(-1, 0) + height(-1, 1));
+                let dy = (height(-1, 1) + 2.0 * height(0, 1) + height(1, 1))
+                    - (height(-1, -1) + 2.0 * height(0, -1) + height(1, -1));
+
+                let nx = -dx * self.strength;
+                let ny = -dy * self.strength;
+                let len = (nx * nx + ny * ny + 1.0).sqrt();
+                let encode = |n: f32| ((n / len) * 0.5 + 0.5) * 255.0;
+
+                let idx = (y * image.width + x) as usize;
+                pixels[idx] =
+                    Pixel::from_f32(encode(nx).round(), encode(ny).round(), encode(1.0).round());
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Shaded relief: luma is a heightfield scaled by `depth`, and each pixel is
+// lit by a distant light at `azimuth` degrees (counterclockwise from the
+// right, as on a map) and `elevation` degrees above the horizon. The output
+// is the Lambertian shade of the surface normal (from Sobel gradients), so
+// flat areas take the gray sin(elevation) and slopes facing the light
+// brighten while slopes facing away darken.
+pub struct Relief {
+    azimuth: f32,
+    elevation: f32,
+    depth: f32,
+}
+
+impl Relief {
+    pub fn new(azimuth: f32, elevation: f32, depth: f32) -> Self {
+        Self {
+            azimuth,
+            elevation,
+            depth,
+        }
+    }
+}
+
+impl Transformation for Relief {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if !(0.0..=90.0).contains(&self.elevation) || self.depth < 0.0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "relief".to_string(),
+                details: format!(
+                    "elevation must be 0-90 degrees and depth non-negative, got {} and {}",
+                    self.elevation, self.depth
+                ),
+            });
+        }
+
+        let (azimuth, elevation) = (self.azimuth.to_radians(), self.elevation.to_radians());
+        // Image y points down, so a light "above" the center has negative y
+        let light = [
+            elevation.cos() * azimuth.cos(),
+            -elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+        ];
+
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let window = KernelWindow {
+                    image,
+                    center_x: x,
+                    center_y: y,
+                };
+                let (gx, gy) = window.sobel();
+
+                // Sobel sums 4 differences of 2 pixels each, i.e. 8x the slope
+                let nx = -gx / (8.0 * 255.0) * self.depth;
+                let ny = -gy / (8.0 * 255.0) * self.depth;
+                let len = (nx * nx + ny * ny + 1.0).sqrt();
+
+                let shade = ((nx * light[0] + ny * light[1] + light[2]) / len).max(0.0);
+                let value = (shade * 255.0).round();
+                pixels.push(Pixel::from_f32(value, value, value));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Binary morphology on luma: pixels brighter than 127 are foreground.
+// Erosion keeps a pixel only if its whole (2r+1)x(2r+1) neighborhood is
+// foreground, dilation if any neighbor is. Output is pure black/white.
+fn morphology(image: &Image, radius: i32, require_all: bool) -> Image {
+    let is_foreground = |p: Pixel| p.luma() > 127.0;
+    let mut pixels = vec![Pixel::new(0, 0, 0); (image.width * image.height) as usize];
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let window = KernelWindow {
+                image,
+                center_x: x,
+                center_y: y,
+            };
+
+            let mut neighbors = (-radius..=radius)
+                .flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)))
+                .map(|(dx, dy)| is_foreground(window.get(dx, dy)));
+
+            let foreground = if require_all {
+                neighbors.all(|f| f)
+            } else {
+                neighbors.any(|f| f)
+            };
+
+            if foreground {
+                pixels[(y * image.width + x) as usize] = Pixel::new(255, 255, 255);
+            }
+        }
+    }
+
+    Image {
+        width: image.width,
+        height: image.height,
+        pixels,
+        metadata: image.metadata,
+    }
+}
+
+pub struct Erode {
+    radius: i32,
+}
+
+impl Erode {
+    pub fn new(radius: i32) -> Self {
+        Self { radius }
+    }
+}
+
+impl Transformation for Erode {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        Ok(morphology(image, self.radius, true))
+    }
+}
+
+pub struct Dilate {
+    radius: i32,
+}
+
+impl Dilate {
+    pub fn new(radius: i32) -> Self {
+        Self { radius }
+    }
+}
+
+impl Transformation for Dilate {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        Ok(morphology(image, self.radius, false))
+    }
+}
+
+// Opening: erosion followed by dilation. Removes specks smaller than the
+// structuring element while keeping larger shapes.
+pub struct Open {
+    pipeline: Pipeline,
+}
+
+impl Open {
+    pub fn new(radius: i32) -> Self {
+        let pipeline = Pipeline::new()
+            .then(Erode::new(radius))
+            .then(Dilate::new(radius));
+        Self { pipeline }
+    }
+}
+
+impl Transformation for Open {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        self.pipeline.apply(image)
+    }
+}
+
+// Closing: dilation followed by erosion. Fills small holes and gaps.
+pub struct Close {
+    pipeline: Pipeline,
+}
+
+impl Close {
+    pub fn new(radius: i32) -> Self {
+        let pipeline = Pipeline::new()
+            .then(Dilate::new(radius))
+            .then(Erode::new(radius));
+        Self { pipeline }
+    }
+}
+
+impl Transformation for Close {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        self.pipeline.apply(image)
+    }
+}
+
+// Stylization
+
+pub struct Sketch {
+    intensity: f32,
+}
+
+impl Sketch {
+    pub fn new(intensity: f32) -> Self {
+        Self { intensity }
+    }
+}
+
+impl Transformation for Sketch {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let gray = Grayscale::new().apply(image)?;
+        let blurred_inverse = Pipeline::new()
+            .then(Invert::new())
+            .then(GaussianBlur::new())
+            .then(GaussianBlur::new())
+            .then(GaussianBlur::new())
+            .apply(&gray)?;
+
+        let pixels: Vec<Pixel> = gray
+            .pixels
+            .iter()
+            .zip(&blurred_inverse.pixels)
+            .map(|(g, b)| {
+                // Color dodge: flat areas burn out to white, edges leave lines
+                let base = g.r as f32;
+                let blend = b.r as f32;
+                let dodged = if blend >= 255.0 {
+                    255.0
+                } else {
+                    (base * 255.0 / (255.0 - blend)).min(255.0)
+                };
+                let value = 255.0 - (255.0 - dodged) * self.intensity;
+                Pixel::from_f32(value, value, value)
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Ink outlines from a difference of Gaussians: the grayscale image is
+// blurred at two sigmas and pixels where the fine blur is darker than the
+// coarse one by more than `threshold` become black lines on white.
+pub struct DoG {
+    sigma1: f32,
+    sigma2: f32,
+    threshold: f32,
+}
+
+impl DoG {
+    pub fn new(sigma1: f32, sigma2: f32, threshold: f32) -> Self {
+        Self {
+            sigma1,
+            sigma2,
+            threshold,
+        }
+    }
+}
+
+impl Transformation for DoG {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.sigma1 <= 0.0 || self.sigma2 <= 0.0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "dog".to_string(),
+                details: format!(
+                    "sigmas must be positive, got {} and {}",
+                    self.sigma1, self.sigma2
+                ),
+            });
+        }
+
+        let gray = Grayscale::new().apply(image)?;
+        let fine = GaussianBlur::with_sigma(self.sigma1).apply(&gray)?;
+        let coarse = GaussianBlur::with_sigma(self.sigma2).apply(&gray)?;
+
+        let pixels: Vec<Pixel> = fine
+            .pixels
+            .iter()
+            .zip(&coarse.pixels)
+            .map(|(a, b)| {
+                // Only the dark side of an edge, so lines stay thin
+                let difference = b.r as f32 - a.r as f32;
+                let value = if difference > self.threshold { 0 } else { 255 };
+                Pixel::new(value, value, value)
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Cartoon look: colors are posterized to `levels` per channel and pixels
+// whose Sobel edge strength exceeds `edge_threshold` are drawn black.
+pub struct Cartoon {
+    levels: u8,
+    edge_threshold: f32,
+}
+
+impl Cartoon {
+    pub fn new(levels: u8, edge_threshold: f32) -> Self {
+        Self {
+            levels,
+            edge_threshold,
+        }
+    }
+}
+
+impl Transformation for Cartoon {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.levels < 2 {
+            return Err(ProcessError::InvalidInput {
+                operation: "cartoon".to_string(),
+                details: format!("levels must be at least 2, got {}", self.levels),
+            });
+        }
+
+        let step = 255.0 / (self.levels - 1) as f32;
+        let quantize = |v: u8| (v as f32 / step).round() * step;
+
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let window = KernelWindow {
+                    image,
+                    center_x: x,
+                    center_y: y,
+                };
+                let (gx, gy) = window.sobel();
+
+                pixels.push(if (gx * gx + gy * gy).sqrt() > self.edge_threshold {
+                    Pixel::new(0, 0, 0)
+                } else {
+                    let p = window.get(0, 0);
+                    Pixel::from_f32(quantize(p.r), quantize(p.g), quantize(p.b))
+                });
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Oil painting effect: each pixel takes the average color of the most
+// common intensity bin in its neighborhood.
+//
+// Cost is O(width * height * (2 * radius + 1)^2), so large radii get slow
+// quickly on big images.
+pub struct OilPaint {
+    radius: i32,
+    levels: u8,
+}
+
+impl OilPaint {
+    pub fn new(radius: i32, levels: u8) -> Self {
+        Self { radius, levels }
+    }
+}
+
+impl Transformation for OilPaint {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.levels == 0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "oilpaint".to_string(),
+                details: "levels must be at least 1".to_string(),
+            });
+        }
+
+        let levels = self.levels as usize;
+        let mut pixels = vec![Pixel::new(0, 0, 0); (image.width * image.height) as usize];
+        let mut counts = vec![0u32; levels];
+        let mut sums = vec![(0.0f32, 0.0f32, 0.0f32); levels];
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                counts.fill(0);
+                sums.fill((0.0, 0.0, 0.0));
+
+                let window = KernelWindow {
+                    image,
+                    center_x: x,
+                    center_y: y,
+                };
+
+                for dy in -self.radius..=self.radius {
+                    for dx in -self.radius..=self.radius {
+                        let p = window.get(dx, dy);
+                        let bin = ((p.luma() / 256.0 * levels as f32) as usize).min(levels - 1);
+                        counts[bin] += 1;
+                        sums[bin].0 += p.r as f32;
+                        sums[bin].1 += p.g as f32;
+                        sums[bin].2 += p.b as f32;
+                    }
+                }
+
+                let (bin, &count) = counts
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|&(_, count)| count)
+                    .unwrap_or((0, &1));
+                let (r, g, b) = sums[bin];
+                let count = count as f32;
+
+                let idx = (y * image.width + x) as usize;
+                pixels[idx] = Pixel::from_f32(r / count, g / count, b / count);
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Most samples taken along one pixel's zoom streak
+const ZOOM_MAX_SAMPLES: i32 = 64;
+
+// Zoom (radial) blur: each pixel averages samples along the line from
+// itself toward the center (`x`, `y`), covering `strength` (0-1) of the
+// distance. Streaks grow with distance, so the center stays sharp while
+// the edges smear outward. A strength of 0 leaves the image unchanged.
+pub struct ZoomBlur {
+    x: i32,
+    y: i32,
+    strength: f32,
+}
+
+impl ZoomBlur {
+    pub fn new(x: i32, y: i32, strength: f32) -> Self {
+        Self { x, y, strength }
+    }
+}
+
+impl Transformation for ZoomBlur {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if !(0.0..=1.0).contains(&self.strength) {
+            return Err(ProcessError::InvalidInput {
+                operation: "zoomblur".to_string(),
+                details: format!("strength must be 0.0-1.0, got {}", self.strength),
+            });
+        }
+
+        let (cx, cy) = (self.x as f32, self.y as f32);
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let dx = (cx - x as f32) * self.strength;
+                let dy = (cy - y as f32) * self.strength;
+                // About one sample per pixel of streak length
+                let samples = ((dx * dx + dy * dy).sqrt().ceil() as i32).clamp(1, ZOOM_MAX_SAMPLES);
+
+                let (mut r, mut g, mut b) = (0.0, 0.0, 0.0);
+                for i in 0..samples {
+                    let t = i as f32 / samples as f32;
+                    let p = sample_bilinear(image, x as f32 + dx * t, y as f32 + dy * t);
+                    r += p.r as f32;
+                    g += p.g as f32;
+                    b += p.b as f32;
+                }
+
+                let n = samples as f32;
+                pixels.push(Pixel::from_f32(
+                    (r / n).round(),
+                    (g / n).round(),
+                    (b / n).round(),
+                ));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Shifts red right and blue left by `shift` pixels, keeping green fixed,
+// like the color fringing of a cheap lens.
+pub struct ChromaticAberration {
+    shift: i32,
+}
+
+impl ChromaticAberration {
+    pub fn new(shift: i32) -> Self {
+        Self { shift }
+    }
+}
+
+impl Transformation for ChromaticAberration {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let window = KernelWindow {
+                    image,
+                    center_x: x,
+                    center_y: y,
+                };
+
+                pixels.push(Pixel::new(
+                    window.get(-self.shift, 0).r,
+                    window.get(0, 0).g,
+                    window.get(self.shift, 0).b,
+                ));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// CRT look: every `spacing`-th row is darkened by `intensity`. The optional
+// sub-pixel mask cycles red, green and blue columns, slightly dimming the
+// other two channels in each.
+pub struct Scanlines {
+    intensity: f32,
+    spacing: i32,
+    subpixel: bool,
+}
+
+impl Scanlines {
+    pub fn new(intensity: f32, spacing: i32) -> Self {
+        Self {
+            intensity,
+            spacing,
+            subpixel: false,
+        }
+    }
+
+    pub fn with_subpixel(mut self) -> Self {
+        self.subpixel = true;
+        self
+    }
+}
+
+impl Transformation for Scanlines {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.spacing <= 0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "scanlines".to_string(),
+                details: format!("spacing must be positive, got {}", self.spacing),
+            });
+        }
+
+        let intensity = self.intensity.clamp(0.0, 1.0);
+        let mask_dim = 1.0 - intensity * 0.25;
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+
+        for y in 0..image.height {
+            let row_scale = if y % self.spacing == 0 {
+                1.0 - intensity
+            } else {
+                1.0
+            };
+
+            for x in 0..image.width {
+                let p = image.pixels[(y * image.width + x) as usize];
+                let mut scale = [row_scale; 3];
+                if self.subpixel {
+                    for (channel, s) in scale.iter_mut().enumerate() {
+                        if channel != (x % 3) as usize {
+                            *s *= mask_dim;
+                        }
+                    }
+                }
+
+                pixels.push(if scale == [1.0; 3] {
+                    p
+                } else {
+                    Pixel::from_f32(
+                        (p.r as f32 * scale[0]).round(),
+                        (p.g as f32 * scale[1]).round(),
+                        (p.b as f32 * scale[2]).round(),
+                    )
+                });
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Small deterministic PRNG (SplitMix64) so seeded effects reproduce exactly
+// without an external dependency.
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // Uniform in [low, high]
+    fn range(&mut self, low: i32, high: i32) -> i32 {
+        low + (self.next_u64() % (high - low + 1) as u64) as i32
+    }
+}
+
+// Shifts random horizontal bands sideways (wrapping) and occasionally
+// rotates their color channels. Output depends only on the seed.
+pub struct Glitch {
+    intensity: f32,
+    seed: u64,
+}
+
+impl Glitch {
+    pub fn new(intensity: f32, seed: u64) -> Self {
+        Self { intensity, seed }
+    }
+}
+
+impl Transformation for Glitch {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let mut rng = Rng::new(self.seed);
+        let mut pixels = image.pixels.clone();
+
+        // Nothing to shift, and wrapping below needs a nonzero width
+        if image.width == 0 {
+            return Ok(Image {
+                width: image.width,
+                height: image.height,
+                pixels,
+                metadata: image.metadata,
+            });
+        }
+
+        let intensity = self.intensity.clamp(0.0, 1.0);
+        let max_band = (image.height / 10).max(1);
+        let max_shift = (image.width as f32 * intensity / 4.0) as i32;
+
+        let mut y = 0;
+        while y < image.height {
+            let band = rng.range(1, max_band).min(image.height - y);
+            let shift = if rng.next_f32() < intensity {
+                rng.range(-max_shift, max_shift)
+            } else {
+                0
+            };
+            let swap = rng.next_f32() < intensity / 3.0;
+
+            for row in y..y + band {
+                for x in 0..image.width {
+                    let src_x = (x - shift).rem_euclid(image.width);
+                    let p = image.pixels[(row * image.width + src_x) as usize];
+                    pixels[(row * image.width + x) as usize] =
+                        if swap { Pixel::new(p.g, p.b, p.r) } else { p };
+                }
+            }
+
+            y += band;
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Film grain: uniform noise is generated on a grid `size` times coarser
+// than the image and upscaled bilinearly, so grains clump instead of
+// varying per pixel. The same offset is added to all channels, shifting
+// luma only. `amount` is the peak offset as a fraction of full scale.
+pub struct FilmGrain {
+    amount: f32,
+    size: f32,
+    seed: u64,
+}
+
+impl FilmGrain {
+    pub fn new(amount: f32, size: f32, seed: u64) -> Self {
+        Self { amount, size, seed }
+    }
+}
+
+impl Transformation for FilmGrain {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.size < 1.0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "grain".to_string(),
+                details: format!("size must be at least 1, got {}", self.size),
+            });
+        }
+
+        let mut rng = Rng::new(self.seed);
+        let grid_width = (image.width as f32 / self.size).ceil() as i32 + 1;
+        let grid_height = (image.height as f32 / self.size).ceil() as i32 + 1;
+        let grid: Vec<f32> = (0..grid_width * grid_height)
+            .map(|_| rng.next_f32() * 2.0 - 1.0)
+            .collect();
+
+        let noise_at = |x: i32, y: i32| -> f32 {
+            let gx = x as f32 / self.size;
+            let gy = y as f32 / self.size;
+            let x0 = gx.floor() as i32;
+            let y0 = gy.floor() as i32;
+            let (fx, fy) = (gx - x0 as f32, gy - y0 as f32);
+            let at = |cx: i32, cy: i32| {
+                grid[(cy.min(grid_height - 1) * grid_width + cx.min(grid_width - 1)) as usize]
+            };
+
+            let top = at(x0, y0) * (1.0 - fx) + at(x0 + 1, y0) * fx;
+            let bottom = at(x0, y0 + 1) * (1.0 - fx) + at(x0 + 1, y0 + 1) * fx;
+            top * (1.0 - fy) + bottom * fy
+        };
+
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let p = image.pixels[(y * image.width + x) as usize];
+                let delta = noise_at(x, y) * self.amount * 255.0;
+                pixels.push(Pixel::from_f32(
+                    p.r as f32 + delta,
+                    p.g as f32 + delta,
+                    p.b as f32 + delta,
+                ));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SortDirection {
+    Horizontal,
+    Vertical,
+}
+
+// Pixel sorting glitch: along each row (or column), contiguous runs of
+// pixels with luma at or above `threshold` are sorted by luma, darkest
+// first. Pixels below the threshold break runs and stay where they are.
+pub struct PixelSort {
+    threshold: f32,
+    direction: SortDirection,
+}
+
+impl PixelSort {
+    pub fn new(threshold: f32, direction: SortDirection) -> Self {
+        Self {
+            threshold,
+            direction,
+        }
+    }
+}
+
+impl Transformation for PixelSort {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if !(0.0..=255.0).contains(&self.threshold) {
+            return Err(ProcessError::InvalidInput {
+                operation: "pixelsort".to_string(),
+                details: format!("threshold must be 0-255, got {}", self.threshold),
+            });
+        }
+
+        // (line count, line length, index step between lines, step along a line)
+        let (lines, length, line_step, step) = match self.direction {
+            SortDirection::Horizontal => (image.height, image.width, image.width, 1),
+            SortDirection::Vertical => (image.width, image.height, 1, image.width),
+        };
+
+        let mut pixels = image.pixels.clone();
+        let mut run: Vec<Pixel> = Vec::with_capacity(length as usize);
+        for line in 0..lines {
+            let index = |i: i32| (line * line_step + i * step) as usize;
+
+            let mut i = 0;
+            while i < length {
+                if pixels[index(i)].luma() < self.threshold {
+                    i += 1;
+                    continue;
+                }
+
+                let start = i;
+                run.clear();
+                while i < length && pixels[index(i)].luma() >= self.threshold {
+                    run.push(pixels[index(i)]);
+                    i += 1;
+                }
+
+                run.sort_by(|a, b| a.luma().total_cmp(&b.luma()));
+                for (k, &p) in run.iter().enumerate() {
+                    pixels[index(start + k as i32)] = p;
+                }
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Tilt-shift miniature: rows within `focus_height / 2` of `focus_y` stay
+// sharp, and the blur radius grows linearly from 0 at the band's edges to
+// `max_blur` at the top and bottom rows. The blur is a DepthBlur over a
+// generated depth map. An optional vibrance boost adds the saturated
+// toy-model look.
+pub struct TiltShift {
+    focus_y: i32,
+    focus_height: i32,
+    max_blur: i32,
+    vibrance: f32,
+}
+
+impl TiltShift {
+    pub fn new(focus_y: i32, focus_height: i32, max_blur: i32) -> Self {
+        Self {
+            focus_y,
+            focus_height,
+            max_blur,
+            vibrance: 0.0,
+        }
+    }
+
+    pub fn with_vibrance(mut self, vibrance: f32) -> Self {
+        self.vibrance = vibrance;
+        self
+    }
+}
+
+impl Transformation for TiltShift {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if !(0..image.height).contains(&self.focus_y) || self.focus_height < 0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "tiltshift".to_string(),
+                details: format!(
+                    "focus row must be inside 0-{} and band height non-negative, got {} and {}",
+                    image.height - 1,
+                    self.focus_y,
+                    self.focus_height
+                ),
+            });
+        }
+
+        let band_top = (self.focus_y - self.focus_height / 2).max(0);
+        let band_bottom = (self.focus_y + self.focus_height / 2).min(image.height - 1);
+        let depth_of_row = |y: i32| -> f32 {
+            if y < band_top {
+                (band_top - y) as f32 / band_top as f32
+            } else if y > band_bottom {
+                (y - band_bottom) as f32 / (image.height - 1 - band_bottom) as f32
+            } else {
+                0.0
+            }
+        };
+
+        let mut depth = Vec::with_capacity(image.pixels.len());
+        for y in 0..image.height {
+            let value = (depth_of_row(y) * 255.0).round() as u8;
+            depth.extend(std::iter::repeat_n(
+                Pixel::new(value, value, value),
+                image.width as usize,
+            ));
+        }
+        let depth_map = Image {
+            width: image.width,
+            height: image.height,
+            pixels: depth,
+            metadata: image.metadata,
+        };
+
+        let blurred = DepthBlur::new(depth_map, 0.0, self.max_blur).apply(image)?;
+        if self.vibrance == 0.0 {
+            return Ok(blurred);
+        }
+
+        Vibrance::new(self.vibrance).apply(&blurred)
+    }
+}
+
+// Steganography
+
+// Hides `text` in the least significant bit of every channel, three bits
+// per pixel in row-major order. The payload is a 32-bit big-endian byte
+// length followed by the UTF-8 bytes, so each channel changes by at most 1.
+pub struct EmbedMessage {
+    text: String,
+}
+
+impl EmbedMessage {
+    pub fn new(text: String) -> Self {
+        Self { text }
+    }
+}
+
+const MESSAGE_HEADER_BITS: usize = 32;
+
+fn message_capacity_bits(image: &Image) -> usize {
+    image.pixels.len() * 3
+}
+
+impl Transformation for EmbedMessage {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let bytes = self.text.as_bytes();
+        let needed = MESSAGE_HEADER_BITS + bytes.len() * 8;
+        if needed > message_capacity_bits(image) {
+            return Err(ProcessError::InvalidInput {
+                operation: "embed".to_string(),
+                details: format!(
+                    "message needs {} bits but the image only holds {}",
+                    needed,
+                    message_capacity_bits(image)
+                ),
+            });
+        }
+
+        let payload: Vec<u8> = (bytes.len() as u32)
+            .to_be_bytes()
+            .into_iter()
+            .chain(bytes.iter().copied())
+            .collect();
+        let bits = payload
+            .iter()
+            .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1));
+
+        let mut channels: Vec<u8> = image.pixels.iter().flat_map(|p| [p.r, p.g, p.b]).collect();
+        for (channel, bit) in channels.iter_mut().zip(bits) {
+            *channel = (*channel & !1) | bit;
+        }
+
+        let pixels: Vec<Pixel> = channels
+            .chunks_exact(3)
+            .map(|c| Pixel::new(c[0], c[1], c[2]))
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Reads back a message written by `EmbedMessage`.
+pub fn extract_message(image: &Image) -> Result<String, ProcessError> {
+    let mut bits = image
+        .pixels
+        .iter()
+        .flat_map(|p| [p.r & 1, p.g & 1, p.b & 1]);
+    let mut read_byte = || (0..8).fold(0u8, |byte, _| (byte << 1) | bits.next().unwrap_or(0));
+
+    let header = [read_byte(), read_byte(), read_byte(), read_byte()];
+    let length = u32::from_be_bytes(header) as usize;
+
+    let available = message_capacity_bits(image).saturating_sub(MESSAGE_HEADER_BITS) / 8;
+    if message_capacity_bits(image) < MESSAGE_HEADER_BITS || length > available {
+        return Err(ProcessError::InvalidInput {
+            operation: "extract".to_string(),
+            details: "no embedded message found".to_string(),
+        });
+    }
+
+    let bytes: Vec<u8> = (0..length).map(|_| read_byte()).collect();
+
+    String::from_utf8(bytes).map_err(|_| ProcessError::InvalidInput {
+        operation: "extract".to_string(),
+        details: "embedded message is not valid UTF-8".to_string(),
+    })
+}
+
+// Multi-image transformations
+
+fn check_same_dimensions(
+    operation: &str,
+    image: &Image,
+    other: &Image,
+) -> Result<(), ProcessError> {
+    if image.width != other.width || image.height != other.height {
+        return Err(ProcessError::InvalidInput {
+            operation: operation.to_string(),
+            details: format!(
+                "dimensions {}x{} do not match {}x{}",
+                other.width, other.height, image.width, image.height
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+// Blends the input with the other exposures of a bracket, weighting each
+// pixel by how close its luma is to mid-gray (well-exposedness).
+pub struct ExposureFuse {
+    images: Vec<Image>,
+}
+
+impl ExposureFuse {
+    pub fn new(images: Vec<Image>) -> Self {
+        Self { images }
+    }
+}
+
+impl Transformation for ExposureFuse {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        for other in &self.images {
+            check_same_dimensions("fuse", image, other)?;
+        }
+
+        let sigma = 0.2f32;
+        let weight = |p: &Pixel| {
+            let l = p.luma() / 255.0 - 0.5;
+            (-(l * l) / (2.0 * sigma * sigma)).exp() + 1e-6
+        };
+
+        let pixels: Vec<Pixel> = (0..image.pixels.len())
+            .map(|i| {
+                let (mut r, mut g, mut b, mut total) = (0.0, 0.0, 0.0, 0.0);
+
+                for p in std::iter::once(&image.pixels[i])
+                    .chain(self.images.iter().map(|other| &other.pixels[i]))
+                {
+                    let w = weight(p);
+                    r += p.r as f32 * w;
+                    g += p.g as f32 * w;
+                    b += p.b as f32 * w;
+                    total += w;
+                }
+
+                Pixel::from_f32(r / total, g / total, b / total)
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Visual diff against `other`: the input is shown in grayscale and blended
+// toward red in proportion to the mean absolute channel difference times
+// `scale`, so changed regions glow red and identical ones stay gray.
+pub struct DiffHeatmap {
+    other: Image,
+    scale: f32,
+    space: CompositeSpace,
+}
+
+impl DiffHeatmap {
+    pub fn new(other: Image, scale: f32) -> Self {
+        Self {
+            other,
+            scale,
+            space: CompositeSpace::Srgb,
+        }
+    }
+
+    pub fn with_space(mut self, space: CompositeSpace) -> Self {
+        self.space = space;
+        self
+    }
+}
+
+impl Transformation for DiffHeatmap {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        check_same_dimensions("diffmap", image, &self.other)?;
+
+        let pixels: Vec<Pixel> = image
+            .pixels
+            .iter()
+            .zip(&self.other.pixels)
+            .map(|(a, b)| {
+                let diff = (a.r.abs_diff(b.r) as f32
+                    + a.g.abs_diff(b.g) as f32
+                    + a.b.abs_diff(b.b) as f32)
+                    / 3.0;
+                let t = (diff * self.scale / 255.0).clamp(0.0, 1.0);
+                let gray = a.luma().round() as u8;
+
+                Pixel::from_f32(
+                    self.space.mix(gray, 255.0, t).round(),
+                    self.space.mix(gray, 0.0, t).round(),
+                    self.space.mix(gray, 0.0, t).round(),
+                )
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Depth of field from a depth map (luma 0-255 read as depth 0.0-1.0, same
+// size as the image). Each pixel is box-blurred with a radius of
+// |depth - focus_depth| * max_radius, so the focal plane stays sharp and
+// blur grows with distance from it. Box means come from a summed-area
+// table, so large radii cost no more than small ones.
+pub struct DepthBlur {
+    depth_map: Image,
+    focus_depth: f32,
+    max_radius: i32,
+}
+
+impl DepthBlur {
+    pub fn new(depth_map: Image, focus_depth: f32, max_radius: i32) -> Self {
+        Self {
+            depth_map,
+            focus_depth,
+            max_radius,
+        }
+    }
+}
+
+impl Transformation for DepthBlur {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        check_same_dimensions("depthblur", image, &self.depth_map)?;
+        if !(0.0..=1.0).contains(&self.focus_depth) || self.max_radius < 0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "depthblur".to_string(),
+                details: format!(
+                    "focus must be 0.0-1.0 and max radius non-negative, got {} and {}",
+                    self.focus_depth, self.max_radius
+                ),
+            });
+        }
+
+        let (width, height) = (image.width, image.height);
+        let stride = (width + 1) as usize;
+        let mut integral = vec![[0u64; 3]; stride * (height + 1) as usize];
+        for y in 0..height as usize {
+            let mut row = [0u64; 3];
+            for x in 0..width as usize {
+                let p = image.pixels[y * width as usize + x];
+                row[0] += p.r as u64;
+                row[1] += p.g as u64;
+                row[2] += p.b as u64;
+                let above = integral[y * stride + x + 1];
+                integral[(y + 1) * stride + x + 1] = std::array::from_fn(|c| above[c] + row[c]);
+            }
+        }
+
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+        for y in 0..height {
+            for x in 0..width {
+                let index = (y * width + x) as usize;
+                let depth = self.depth_map.pixels[index].luma() / 255.0;
+                let radius = ((depth - self.focus_depth).abs() * self.max_radius as f32).round();
+                let radius = radius as i32;
+                if radius == 0 {
+                    pixels.push(image.pixels[index]);
+                    continue;
+                }
+
+                let x0 = (x - radius).max(0) as usize;
+                let y0 = (y - radius).max(0) as usize;
+                let x1 = (x + radius + 1).min(width) as usize;
+                let y1 = (y + radius + 1).min(height) as usize;
+                let area = ((x1 - x0) * (y1 - y0)) as f32;
+
+                let [r, g, b] = std::array::from_fn(|c| {
+                    let sum = integral[y1 * stride + x1][c] + integral[y0 * stride + x0][c]
+                        - integral[y0 * stride + x1][c]
+                        - integral[y1 * stride + x0][c];
+                    (sum as f32 / area).round()
+                });
+                pixels.push(Pixel::from_f32(r, g, b));
+            }
+        }
+
+        Ok(Image {
+            width,
+            height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Warps the image by a displacement map of the same size: red moves the
+// sample position horizontally and green vertically, each by
+// scale * (value - 128) / 128 pixels, so (128, 128) leaves a pixel in place.
+// Samples are bilinear and clamp to the edge. When supersampling, the map
+// itself is read bilinearly at each sub-pixel position.
+pub struct DisplacementMap {
+    map: Image,
+    scale: f32,
+    supersample: u8,
+}
+
+impl DisplacementMap {
+    pub fn new(map: Image, scale: f32) -> Self {
+        Self {
+            map,
+            scale,
+            supersample: 1,
+        }
+    }
+
+    pub fn with_supersample(mut self, supersample: u8) -> Self {
+        self.supersample = supersample;
+        self
+    }
+}
+
+impl Transformation for DisplacementMap {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        check_same_dimensions("displace", image, &self.map)?;
+        check_supersample("displace", self.supersample)?;
+
+        let offset = |v: u8| self.scale * (v as f32 - 128.0) / 128.0;
+        let pixels = render_supersampled(
+            image,
+            image.width,
+            image.height,
+            self.supersample,
+            |x, y| {
+                let m = sample_bilinear(&self.map, x, y);
+                (x + offset(m.r), y + offset(m.g))
+            },
+        );
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Long-exposure simulation: the per-pixel mean of the input and `frames`,
+// smoothing moving water or crowds in a burst shot from a tripod.
+pub struct AverageStack {
+    frames: Vec<Image>,
+}
+
+impl AverageStack {
+    pub fn new(frames: Vec<Image>) -> Self {
+        Self { frames }
+    }
+}
+
+impl Transformation for AverageStack {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        for frame in &self.frames {
+            check_same_dimensions("avgstack", image, frame)?;
+        }
+
+        let count = (self.frames.len() + 1) as f32;
+        let pixels: Vec<Pixel> = (0..image.pixels.len())
+            .map(|i| {
+                let (mut r, mut g, mut b) = (0.0, 0.0, 0.0);
+
+                for p in std::iter::once(&image.pixels[i])
+                    .chain(self.frames.iter().map(|frame| &frame.pixels[i]))
+                {
+                    r += p.r as f32;
+                    g += p.g as f32;
+                    b += p.b as f32;
+                }
+
+                Pixel::from_f32(
+                    (r / count).round(),
+                    (g / count).round(),
+                    (b / count).round(),
+                )
+            })
+            .collect();
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Lighten stacking: the per-channel maximum of the input and `frames`, so
+// moving lights (star trails, car lights) leave continuous streaks.
+pub struct MaxStack {
+    frames: Vec<Image>,
+}
+
+impl MaxStack {
+    pub fn new(frames: Vec<Image>) -> Self {
+        Self { frames }
+    }
+}
+
+impl Transformation for MaxStack {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        for frame in &self.frames {
+            check_same_dimensions("maxstack", image, frame)?;
+        }
+
+        let mut pixels = image.pixels.clone();
+        for frame in &self.frames {
+            for (p, f) in pixels.iter_mut().zip(&frame.pixels) {
+                *p = Pixel::new(p.r.max(f.r), p.g.max(f.g), p.b.max(f.b));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Drawing
+
+// Draws one-pixel rectangle outlines, clipped to the image bounds.
+pub struct DrawBoxes {
+    boxes: Vec<BBox>,
+    color: Pixel,
+}
+
+impl DrawBoxes {
+    pub fn new(boxes: Vec<BBox>, color: Pixel) -> Self {
+        Self { boxes, color }
+    }
+}
+
+impl Transformation for DrawBoxes {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let mut pixels = image.pixels.clone();
+        let mut plot = |x: i32, y: i32| {
+            if x >= 0 && x < image.width && y >= 0 && y < image.height {
+                pixels[(y * image.width + x) as usize] = self.color;
+            }
+        };
+
+        for bbox in &self.boxes {
+            let right = bbox.x + bbox.width - 1;
+            let bottom = bbox.y + bbox.height - 1;
+
+            for x in bbox.x..=right {
+                plot(x, bbox.y);
+                plot(x, bottom);
+            }
+            for y in bbox.y..=bottom {
+                plot(bbox.x, y);
+                plot(right, y);
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Extends the canvas downward by a `height`-pixel bar of `bg` and writes
+// `text` centered on it in `fg` with the built-in 5x7 font, scaled up by
+// a whole factor to fill about 60% of the bar. Text wider than the image
+// is drawn at the largest scale that fits, or clipped at scale 1.
+pub struct CaptionBar {
+    text: String,
+    height: i32,
+    bg: Pixel,
+    fg: Pixel,
+}
+
+impl CaptionBar {
+    pub fn new(text: String, height: i32, bg: Pixel, fg: Pixel) -> Self {
+        Self {
+            text,
+            height,
+            bg,
+            fg,
+        }
+    }
+}
+
+impl Transformation for CaptionBar {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.height < 1 {
+            return Err(ProcessError::InvalidInput {
+                operation: "caption".to_string(),
+                details: format!("bar height must be positive, got {}", self.height),
+            });
+        }
+
+        let width = image.width;
+        let height = image.height + self.height;
+        let mut pixels = image.pixels.clone();
+        pixels.resize((width * height) as usize, self.bg);
+
+        let text_width = font::text_width(&self.text);
+        if text_width == 0 {
+            return Ok(Image {
+                width,
+                height,
+                pixels,
+                metadata: image.metadata,
+            });
+        }
+
+        let scale = (self.height * 6 / 10 / font::GLYPH_HEIGHT)
+            .min(width / text_width)
+            .max(1);
+        let left = (width - text_width * scale) / 2;
+        let top = image.height + (self.height - font::GLYPH_HEIGHT * scale) / 2;
+
+        for (i, c) in self.text.chars().enumerate() {
+            let glyph_left = left + i as i32 * font::GLYPH_ADVANCE * scale;
+            for gy in 0..font::GLYPH_HEIGHT {
+                for gx in 0..font::GLYPH_WIDTH {
+                    if !font::glyph_pixel(c, gx, gy) {
+                        continue;
+                    }
+
+                    for y in top + gy * scale..top + (gy + 1) * scale {
+                        for x in glyph_left + gx * scale..glyph_left + (gx + 1) * scale {
+                            if x >= 0 && x < width && y >= image.height && y < height {
+                                pixels[(y * width + x) as usize] = self.fg;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Image {
+            width,
+            height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Overlays
+
+#[derive(Debug, Clone, Copy)]
+pub enum GradientKind {
+    LinearHorizontal,
+    LinearVertical,
+    Radial,
+}
+
+// Blends a generated gradient over the image. Linear gradients run from
+// the left/top edge to the right/bottom edge, radial ones from the center
+// to the corners.
+pub struct GradientOverlay {
+    start: Pixel,
+    end: Pixel,
+    kind: GradientKind,
+    opacity: f32,
+    space: CompositeSpace,
+}
+
+impl GradientOverlay {
+    pub fn new(start: Pixel, end: Pixel, kind: GradientKind, opacity: f32) -> Self {
+        Self {
+            start,
+            end,
+            kind,
+            opacity,
+            space: CompositeSpace::Srgb,
+        }
+    }
+
+    pub fn with_space(mut self, space: CompositeSpace) -> Self {
+        self.space = space;
+        self
+    }
+}
+
+impl Transformation for GradientOverlay {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let span_x = (image.width - 1).max(1) as f32;
+        let span_y = (image.height - 1).max(1) as f32;
+        let center_x = (image.width - 1) as f32 / 2.0;
+        let center_y = (image.height - 1) as f32 / 2.0;
+        let max_radius = (center_x * center_x + center_y * center_y).sqrt().max(1.0);
+
+        let lerp = |a: u8, b: u8, t: f32| a as f32 + (b as f32 - a as f32) * t;
+        let blend = |src: u8, over: f32| self.space.mix(src, over, self.opacity);
+
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let t = match self.kind {
+                    GradientKind::LinearHorizontal => x as f32 / span_x,
+                    GradientKind::LinearVertical => y as f32 / span_y,
+                    GradientKind::Radial => {
+                        let dx = x as f32 - center_x;
+                        let dy = y as f32 - center_y;
+                        (dx * dx + dy * dy).sqrt() / max_radius
+                    }
+                };
+
+                let p = image.pixels[(y * image.width + x) as usize];
+                pixels.push(Pixel::from_f32(
+                    blend(p.r, lerp(self.start.r, self.end.r, t)).round(),
+                    blend(p.g, lerp(self.start.g, self.end.g, t)).round(),
+                    blend(p.b, lerp(self.start.b, self.end.b, t)).round(),
+                ));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LeakPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+// Film light leak: a soft glow of `color` spreading from a corner or edge,
+// screen-blended so it only brightens. The seed jitters the glow's origin
+// along the edge and its reach, so repeated leaks don't look identical.
+// Each pixel is mixed toward its full-strength screen by the glow weight.
+pub struct LightLeak {
+    color: Pixel,
+    position: LeakPosition,
+    intensity: f32,
+    seed: u64,
+    space: CompositeSpace,
+}
+
+impl LightLeak {
+    pub fn new(color: Pixel, position: LeakPosition, intensity: f32, seed: u64) -> Self {
+        Self {
+            color,
+            position,
+            intensity,
+            seed,
+            space: CompositeSpace::Srgb,
+        }
+    }
+
+    pub fn with_space(mut self, space: CompositeSpace) -> Self {
+        self.space = space;
+        self
+    }
+}
+
+impl Transformation for LightLeak {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        let mut rng = Rng::new(self.seed);
+        let w = (image.width - 1) as f32;
+        let h = (image.height - 1) as f32;
+        let jitter = rng.next_f32() * 0.4 + 0.3;
+
+        let (origin_x, origin_y) = match self.position {
+            LeakPosition::TopLeft => (0.0, 0.0),
+            LeakPosition::TopRight => (w, 0.0),
+            LeakPosition::BottomLeft => (0.0, h),
+            LeakPosition::BottomRight => (w, h),
+            LeakPosition::Top => (w * jitter, 0.0),
+            LeakPosition::Bottom => (w * jitter, h),
+            LeakPosition::Left => (0.0, h * jitter),
+            LeakPosition::Right => (w, h * jitter),
+        };
+        let diagonal = (w * w + h * h).sqrt().max(1.0);
+        let reach = diagonal * (0.5 + rng.next_f32() * 0.3);
+        let intensity = self.intensity.clamp(0.0, 1.0);
+
+        let screen = |base: u8, over: u8, weight: f32| {
+            let screened = 255.0 - (255.0 - base as f32) * (1.0 - over as f32 / 255.0);
+            self.space.mix(base, screened, weight)
+        };
+
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let dx = x as f32 - origin_x;
+                let dy = y as f32 - origin_y;
+                let falloff = (1.0 - (dx * dx + dy * dy).sqrt() / reach).max(0.0);
+                let weight = falloff * falloff * intensity;
+
+                let p = image.pixels[(y * image.width + x) as usize];
+                pixels.push(Pixel::from_f32(
+                    screen(p.r, self.color.r, weight).round(),
+                    screen(p.g, self.color.g, weight).round(),
+                    screen(p.b, self.color.b, weight).round(),
+                ));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Radial light: brightness is multiplied by 1 + intensity * w, where
+// w = (1 - d / radius)^falloff inside the radius and 0 outside, so the
+// center gets the full boost and pixels beyond `radius` are unchanged.
+// Negative intensity darkens instead.
+pub struct Spotlight {
+    x: i32,
+    y: i32,
+    radius: f32,
+    intensity: f32,
+    falloff: f32,
+}
+
+impl Spotlight {
+    pub fn new(x: i32, y: i32, radius: f32, intensity: f32, falloff: f32) -> Self {
+        Self {
+            x,
+            y,
+            radius,
+            intensity,
+            falloff,
+        }
+    }
+}
+
+impl Transformation for Spotlight {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.radius <= 0.0 || self.falloff <= 0.0 {
+            return Err(ProcessError::InvalidInput {
+                operation: "spotlight".to_string(),
+                details: format!(
+                    "radius and falloff must be positive, got {} and {}",
+                    self.radius, self.falloff
+                ),
+            });
+        }
+
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let p = image.pixels[(y * image.width + x) as usize];
+                let dx = (x - self.x) as f32;
+                let dy = (y - self.y) as f32;
+                let distance = (dx * dx + dy * dy).sqrt();
+
+                if distance >= self.radius {
+                    pixels.push(p);
+                    continue;
+                }
+
+                let weight = (1.0 - distance / self.radius).powf(self.falloff);
+                let factor = (1.0 + self.intensity * weight).max(0.0);
+                pixels.push(Pixel::from_f32(
+                    (p.r as f32 * factor).round(),
+                    (p.g as f32 * factor).round(),
+                    (p.b as f32 * factor).round(),
+                ));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum VignetteShape {
+    Circle,
+    Ellipse,
+    Rectangle,
+}
+
+// Darkens everything outside a centered shape by `darkness` (0-1). The
+// shape is inset `feather` pixels from the frame and the darkening ramps
+// up smoothly over `feather` pixels beyond its edge, so the frame's
+// corners always get the full amount. The circle fits the shorter side,
+// the ellipse and rectangle fit the whole (inset) frame.
+pub struct ShapeVignette {
+    shape: VignetteShape,
+    feather: i32,
+    darkness: f32,
+}
+
+impl ShapeVignette {
+    pub fn new(shape: VignetteShape, feather: i32, darkness: f32) -> Self {
+        Self {
+            shape,
+            feather,
+            darkness,
+        }
+    }
+}
+
+impl Transformation for ShapeVignette {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.feather < 0 || !(0.0..=1.0).contains(&self.darkness) {
+            return Err(ProcessError::InvalidInput {
+                operation: "shapevignette".to_string(),
+                details: format!(
+                    "feather must be non-negative and darkness 0.0-1.0, got {} and {}",
+                    self.feather, self.darkness
+                ),
+            });
+        }
+
+        let center_x = (image.width - 1) as f32 / 2.0;
+        let center_y = (image.height - 1) as f32 / 2.0;
+        let feather = self.feather as f32;
+        let half_width = (image.width as f32 / 2.0 - feather).max(0.0);
+        let half_height = (image.height as f32 / 2.0 - feather).max(0.0);
+
+        // Distance in pixels outside the shape, 0 inside. For the ellipse it
+        // is measured along the ray from the center, which is close enough
+        // for a soft edge.
+        let outside = |dx: f32, dy: f32| -> f32 {
+            match self.shape {
+                VignetteShape::Circle => {
+                    let radius = half_width.min(half_height);
+                    ((dx * dx + dy * dy).sqrt() - radius).max(0.0)
+                }
+                VignetteShape::Ellipse => {
+                    let (nx, ny) = (dx / half_width.max(0.5), dy / half_height.max(0.5));
+                    let r = (nx * nx + ny * ny).sqrt();
+                    if r <= 1.0 {
+                        0.0
+                    } else {
+                        (dx * dx + dy * dy).sqrt() * (1.0 - 1.0 / r)
+                    }
+                }
+                VignetteShape::Rectangle => {
+                    let ox = (dx.abs() - half_width).max(0.0);
+                    let oy = (dy.abs() - half_height).max(0.0);
+                    (ox * ox + oy * oy).sqrt()
+                }
+            }
+        };
+
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let p = image.pixels[(y * image.width + x) as usize];
+                let distance = outside(x as f32 - center_x, y as f32 - center_y);
+                if distance <= 0.0 {
+                    pixels.push(p);
+                    continue;
+                }
+
+                let t = if feather > 0.0 {
+                    (distance / feather).min(1.0)
+                } else {
+                    1.0
+                };
+                // Smoothstep, so the ramp has no visible edge at either end
+                let weight = t * t * (3.0 - 2.0 * t);
+                let factor = 1.0 - self.darkness * weight;
+                pixels.push(Pixel::from_f32(
+                    (p.r as f32 * factor).round(),
+                    (p.g as f32 * factor).round(),
+                    (p.b as f32 * factor).round(),
+                ));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Dithering
+
+// Ordered dithering with a Bayer threshold matrix. Every pixel is
+// quantized independently, so the result is deterministic and tiles can
+// be processed separately.
+pub struct BayerDither {
+    levels: u8,
+    matrix_size: u8,
+}
+
+impl BayerDither {
+    pub fn new(levels: u8, matrix_size: u8) -> Self {
+        Self {
+            levels,
+            matrix_size,
+        }
+    }
+}
+
+// Builds an n x n Bayer index matrix (n a power of two) by recursive
+// expansion from the 2x2 base.
+fn bayer_matrix(size: usize) -> Vec<u32> {
+    let mut matrix = vec![0u32];
+    let mut n = 1;
+
+    while n < size {
+        let mut next = vec![0u32; 4 * n * n];
+        for y in 0..n {
+            for x in 0..n {
+                let v = 4 * matrix[y * n + x];
+                next[y * 2 * n + x] = v;
+                next[y * 2 * n + x + n] = v + 2;
+                next[(y + n) * 2 * n + x] = v + 3;
+                next[(y + n) * 2 * n + x + n] = v + 1;
+            }
+        }
+        matrix = next;
+        n *= 2;
+    }
+
+    matrix
+}
+
+impl Transformation for BayerDither {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if !matches!(self.matrix_size, 2 | 4 | 8) {
+            return Err(ProcessError::InvalidInput {
+                operation: "bayer".to_string(),
+                details: format!("matrix size must be 2, 4, or 8, got {}", self.matrix_size),
+            });
+        }
+
+        if self.levels < 2 {
+            return Err(ProcessError::InvalidInput {
+                operation: "bayer".to_string(),
+                details: "levels must be at least 2".to_string(),
+            });
+        }
+
+        let n = self.matrix_size as i32;
+        let matrix = bayer_matrix(n as usize);
+        let steps = (self.levels - 1) as f32;
+
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let p = image.pixels[(y * image.width + x) as usize];
+                let threshold =
+                    (matrix[((y % n) * n + x % n) as usize] as f32 + 0.5) / (n * n) as f32;
+                let quantize = |v: u8| {
+                    let level = (v as f32 / 255.0 * steps + threshold - 0.5)
+                        .round()
+                        .clamp(0.0, steps);
+                    (level * 255.0 / steps).round()
+                };
+                pixels.push(Pixel::from_f32(quantize(p.r), quantize(p.g), quantize(p.b)));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+// Newspaper-style halftone on luma: the image is covered by a grid of
+// `dot_size` cells rotated by `angle` degrees, and each cell gets a black
+// dot whose area matches the darkness at the cell center, on white. Dot
+// edges are anti-aliased over one pixel.
+pub struct Halftone {
+    dot_size: i32,
+    angle: f32,
+}
+
+impl Halftone {
+    pub fn new(dot_size: i32, angle: f32) -> Self {
+        Self { dot_size, angle }
+    }
+}
+
+impl Transformation for Halftone {
+    fn apply(&self, image: &Image) -> Result<Image, ProcessError> {
+        if self.dot_size < 2 {
+            return Err(ProcessError::InvalidInput {
+                operation: "halftone".to_string(),
+                details: format!("dot size must be at least 2, got {}", self.dot_size),
+            });
+        }
+
+        let size = self.dot_size as f32;
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let (fx, fy) = (x as f32, y as f32);
+
+                // Position in the rotated grid and the center of its cell
+                let u = fx * cos + fy * sin;
+                let v = -fx * sin + fy * cos;
+                let cell_u = ((u / size).floor() + 0.5) * size;
+                let cell_v = ((v / size).floor() + 0.5) * size;
+
+                let center_x = cell_u * cos - cell_v * sin;
+                let center_y = cell_u * sin + cell_v * cos;
+                let darkness = 1.0 - sample_bilinear(image, center_x, center_y).luma() / 255.0;
+
+                // Dot area equals the darkness share of the cell area
+                let radius = size * (darkness / std::f32::consts::PI).sqrt();
+                let distance = ((u - cell_u).powi(2) + (v - cell_v).powi(2)).sqrt();
+                let coverage = (radius - distance + 0.5).clamp(0.0, 1.0);
+
+                let value = (255.0 * (1.0 - coverage)).round();
+                pixels.push(Pixel::from_f32(value, value, value));
+            }
+        }
+
+        Ok(Image {
+            width: image.width,
+            height: image.height,
+            pixels,
+            metadata: image.metadata,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rustphoto::image::Metadata;
+
+    fn image(width: i32, height: i32, f: impl Fn(i32, i32) -> Pixel) -> Image {
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(f(x, y));
+            }
+        }
+
+        Image {
+            width,
+            height,
+            pixels,
+            metadata: Metadata::default(),
+        }
+    }
+
+    fn solid(width: i32, height: i32, color: Pixel) -> Image {
+        image(width, height, |_, _| color)
+    }
+
+    fn rgb(p: Pixel) -> (u8, u8, u8) {
+        (p.r, p.g, p.b)
+    }
+
+    #[test]
+    fn lightness_keeps_the_hue_of_a_saturated_pixel() {
+        let red = solid(1, 1, Pixel::new(255, 0, 0));
+
+        let lighter = Lightness::new(0.2).apply(&red).unwrap().pixels[0];
+        let (hue, saturation, _) = lighter.to_hsl();
+        assert!(hue.abs() < 1.0, "hue drifted to {}", hue);
+        assert!(saturation > 0.99);
+        assert!(lighter.g > 0 && lighter.g == lighter.b);
+
+        // Scaling RGB cannot lighten a channel that is already at 255
+        let brighter = Brightness::new(1.5).apply(&red).unwrap().pixels[0];
+        assert_eq!(rgb(brighter), (255, 0, 0));
+    }
+
+    #[test]
+    fn vibrance_boosts_muted_colors_more_than_saturated_ones() {
+        let muted = Pixel::from_hsl(200.0, 0.3, 0.5);
+        let vivid = Pixel::from_hsl(200.0, 0.9, 0.5);
+        let input = image(2, 1, |x, _| if x == 0 { muted } else { vivid });
+
+        let output = Vibrance::new(0.5).apply(&input).unwrap();
+        let muted_gain = output.pixels[0].to_hsl().1 - muted.to_hsl().1;
+        let vivid_gain = output.pixels[1].to_hsl().1 - vivid.to_hsl().1;
+        assert!(muted_gain > 0.0);
+        assert!(muted_gain > vivid_gain, "{} <= {}", muted_gain, vivid_gain);
+    }
+
+    #[test]
+    fn normal_map_of_a_flat_image_points_straight_up() {
+        let output = NormalMap::new(2.0)
+            .apply(&solid(4, 4, Pixel::new(120, 120, 120)))
+            .unwrap();
+
+        for p in output.pixels {
+            assert_eq!(rgb(p), (128, 128, 255));
+        }
+    }
+
+    #[test]
+    fn normal_map_tilts_against_a_rising_slope() {
+        let ramp = image(5, 5, |x, _| {
+            let v = (x * 50) as u8;
+            Pixel::new(v, v, v)
+        });
+
+        let center = NormalMap::new(1.0).apply(&ramp).unwrap().pixels[12];
+        assert!(center.r < 128);
+        assert_eq!(center.g, 128);
+    }
+
+    fn noise(width: i32, height: i32) -> Image {
+        image(width, height, |x, y| {
+            let v = (x * 37 + y * 101) as u32;
+            Pixel::new((v % 251) as u8, (v * 3 % 256) as u8, (v * 7 % 253) as u8)
+        })
+    }
+
+    #[test]
+    fn histogram_match_against_itself_is_identity() {
+        let input = noise(16, 16);
+
+        let output = HistogramMatch::new(noise(16, 16)).apply(&input).unwrap();
+        for (a, b) in input.pixels.iter().zip(&output.pixels) {
+            assert_eq!(rgb(*a), rgb(*b));
+        }
+    }
+
+    #[test]
+    fn histogram_match_takes_on_the_reference_range() {
+        let dark = image(16, 1, |x, _| {
+            let v = (x * 4) as u8;
+            Pixel::new(v, v, v)
+        });
+
+        let output = HistogramMatch::new(dark).apply(&noise(16, 16)).unwrap();
+        assert!(
+            output
+                .pixels
+                .iter()
+                .all(|p| p.r <= 60 && p.g <= 60 && p.b <= 60)
+        );
+    }
+
+    #[test]
+    fn sketch_of_a_flat_image_is_near_white() {
+        let output = Sketch::new(1.0)
+            .apply(&solid(8, 8, Pixel::new(100, 140, 60)))
+            .unwrap();
+
+        assert!(output.pixels.iter().all(|p| p.r >= 250 && p.r == p.b));
+    }
+
+    #[test]
+    fn sketch_draws_a_line_along_an_edge() {
+        let edge = image(12, 12, |x, _| {
+            if x < 6 {
+                Pixel::new(30, 30, 30)
+            } else {
+                Pixel::new(220, 220, 220)
+            }
+        });
+
+        let output = Sketch::new(1.0).apply(&edge).unwrap();
+        let darkest = output.pixels.iter().map(|p| p.r).min().unwrap();
+        assert!(darkest < 200);
+    }
+
+    #[test]
+    fn oil_paint_reduces_distinct_colors() {
+        let input = noise(16, 16);
+
+        let output = OilPaint::new(2, 4).apply(&input).unwrap();
+        assert!(output.unique_colors() < input.unique_colors());
+    }
+
+    #[test]
+    fn oil_paint_rejects_zero_levels() {
+        assert!(OilPaint::new(2, 0).apply(&noise(4, 4)).is_err());
+    }
+
+    #[test]
+    fn exposure_fuse_lands_closer_to_mid_gray_than_either_bracket() {
+        let under = solid(2, 2, Pixel::new(40, 40, 40));
+        let over = solid(2, 2, Pixel::new(230, 230, 230));
+
+        let fused = ExposureFuse::new(vec![over]).apply(&under).unwrap();
+        let luma = fused.pixels[0].luma();
+        assert!((luma - 128.0).abs() < (40.0f32 - 128.0).abs());
+        assert!((luma - 128.0).abs() < (230.0f32 - 128.0).abs());
+    }
+
+    #[test]
+    fn exposure_fuse_rejects_mismatched_sizes() {
+        let fuse = ExposureFuse::new(vec![solid(3, 2, Pixel::new(0, 0, 0))]);
+        assert!(fuse.apply(&solid(2, 2, Pixel::new(0, 0, 0))).is_err());
+    }
+
+    #[test]
+    fn replace_color_writes_the_target_on_an_exact_match() {
+        let input = image(3, 1, |x, _| match x {
+            0 => Pixel::new(200, 30, 30),
+            1 => Pixel::new(180, 40, 40),
+            _ => Pixel::new(0, 0, 255),
+        });
+        let replace = ReplaceColor::new(
+            Pixel::new(200, 30, 30),
+            Pixel::new(20, 200, 20),
+            40.0,
+            ColorDistance::Rgb,
+        );
+
+        let output = replace.apply(&input).unwrap();
+        assert_eq!(rgb(output.pixels[0]), (20, 200, 20));
+        // A near match is blended partway, a far one is left alone
+        let partial = output.pixels[1];
+        assert!(partial.g > 40 && partial.g < 200);
+        assert_eq!(rgb(output.pixels[2]), (0, 0, 255));
+    }
+
+    #[test]
+    fn lab_distance_matches_similar_colors() {
+        let replace = ReplaceColor::new(
+            Pixel::new(200, 30, 30),
+            Pixel::new(0, 0, 0),
+            5.0,
+            ColorDistance::Lab,
+        );
+
+        let output = replace
+            .apply(&solid(1, 1, Pixel::new(202, 31, 30)))
+            .unwrap();
+        assert!(output.pixels[0].r < 100);
+    }
+
+    fn white_at(width: i32, height: i32, on: impl Fn(i32, i32) -> bool) -> Image {
+        image(width, height, |x, y| {
+            if on(x, y) {
+                Pixel::new(255, 255, 255)
+            } else {
+                Pixel::new(0, 0, 0)
+            }
+        })
+    }
+
+    fn white_count(image: &Image) -> usize {
+        image.pixels.iter().filter(|p| p.r == 255).count()
+    }
+
+    #[test]
+    fn dilate_grows_a_dot_and_erode_shrinks_it_back() {
+        let dot = white_at(7, 7, |x, y| x == 3 && y == 3);
+
+        let dilated = Dilate::new(1).apply(&dot).unwrap();
+        assert_eq!(white_count(&dilated), 9);
+        assert_eq!(dilated.pixels[2 * 7 + 2].r, 255);
+
+        let eroded = Erode::new(1).apply(&dilated).unwrap();
+        assert_eq!(white_count(&eroded), 1);
+        assert_eq!(eroded.pixels[3 * 7 + 3].r, 255);
+    }
+
+    #[test]
+    fn open_removes_a_speck_but_keeps_a_block() {
+        let input = white_at(12, 12, |x, y| {
+            let speck = x == 1 && y == 1;
+            let block = (5..10).contains(&x) && (5..10).contains(&y);
+            speck || block
+        });
+
+        let opened = Open::new(1).apply(&input).unwrap();
+        assert_eq!(opened.pixels[12 + 1].r, 0);
+        assert_eq!(white_count(&opened), 25);
+    }
+
+    #[test]
+    fn close_fills_a_pinhole() {
+        let input = white_at(9, 9, |x, y| !(x == 4 && y == 4));
+
+        let closed = Close::new(1).apply(&input).unwrap();
+        assert_eq!(white_count(&closed), 81);
+    }
+
+    #[test]
+    fn bayer_dither_outputs_only_quantized_levels() {
+        let output = BayerDither::new(3, 4).apply(&noise(16, 16)).unwrap();
+
+        for p in &output.pixels {
+            for v in [p.r, p.g, p.b] {
+                assert!([0, 128, 255].contains(&v), "unexpected level {}", v);
+            }
+        }
+    }
+
+    #[test]
+    fn bayer_dither_is_deterministic_and_keeps_mean() {
+        let mid = solid(8, 8, Pixel::new(128, 128, 128));
+        let dither = BayerDither::new(2, 8);
+
+        let first = dither.apply(&mid).unwrap();
+        let second = dither.apply(&mid).unwrap();
+        assert!(
+            first
+                .pixels
+                .iter()
+                .zip(&second.pixels)
+                .all(|(a, b)| rgb(*a) == rgb(*b))
+        );
+        // Half the pattern lights up for mid-gray
+        assert_eq!(white_count(&first), 32);
+    }
+
+    #[test]
+    fn bayer_dither_rejects_bad_parameters() {
+        assert!(BayerDither::new(2, 3).apply(&noise(4, 4)).is_err());
+        assert!(BayerDither::new(1, 4).apply(&noise(4, 4)).is_err());
+    }
+
+    #[test]
+    fn linear_gradient_hits_both_colors_at_the_edges() {
+        let overlay = GradientOverlay::new(
+            Pixel::new(255, 0, 0),
+            Pixel::new(0, 0, 255),
+            GradientKind::LinearHorizontal,
+            1.0,
+        );
+
+        let output = overlay.apply(&solid(5, 2, Pixel::new(0, 0, 0))).unwrap();
+        assert_eq!(rgb(output.pixels[0]), (255, 0, 0));
+        assert_eq!(rgb(output.pixels[4]), (0, 0, 255));
+        assert_eq!(rgb(output.pixels[2]), (128, 0, 128));
+    }
+
+    #[test]
+    fn radial_gradient_starts_at_the_center() {
+        let overlay = GradientOverlay::new(
+            Pixel::new(255, 255, 255),
+            Pixel::new(0, 0, 0),
+            GradientKind::Radial,
+            0.5,
+        );
+
+        let output = overlay.apply(&solid(5, 5, Pixel::new(0, 0, 0))).unwrap();
+        assert_eq!(rgb(output.pixels[12]), (128, 128, 128));
+        assert_eq!(rgb(output.pixels[0]), (0, 0, 0));
+    }
+
+    #[test]
+    fn auto_orient_rotates_orientation_6_and_resets_the_tag() {
+        // 3x2 stored sideways; orientation 6 means rotate 90 degrees clockwise
+        let mut stored = image(3, 2, |x, y| Pixel::new((y * 3 + x) as u8, 0, 0));
+        stored.metadata.orientation = 6;
+
+        let upright = AutoOrient::new().apply(&stored).unwrap();
+        assert_eq!((upright.width, upright.height), (2, 3));
+        let values: Vec<u8> = upright.pixels.iter().map(|p| p.r).collect();
+        assert_eq!(values, [3, 0, 4, 1, 5, 2]);
+        assert_eq!(upright.metadata.orientation, 1);
+
+        // Running it again is a no-op
+        let again = AutoOrient::new().apply(&upright).unwrap();
+        assert_eq!(again.pixels.iter().map(|p| p.r).collect::<Vec<_>>(), values);
+    }
+
+    #[test]
+    fn auto_orient_rejects_an_invalid_tag() {
+        let mut stored = solid(2, 2, Pixel::new(0, 0, 0));
+        stored.metadata.orientation = 9;
+        assert!(AutoOrient::new().apply(&stored).is_err());
+    }
+
+    #[test]
+    fn seamless_makes_opposite_edges_match() {
+        let ramp = image(16, 16, |x, y| Pixel::new((x * 15) as u8, (y * 15) as u8, 0));
+        let edge_gap = |image: &Image| {
+            let last = image.width - 1;
+            (0..image.height)
+                .map(|y| {
+                    let left = image.pixels[(y * image.width) as usize];
+                    let right = image.pixels[(y * image.width + last) as usize];
+                    (left.r as i32 - right.r as i32).abs()
+                })
+                .max()
+                .unwrap()
+        };
+
+        let tiled = MakeSeamless::new(4).apply(&ramp).unwrap();
+        assert_eq!(edge_gap(&ramp), 225);
+        assert!(edge_gap(&tiled) <= 15, "gap {}", edge_gap(&tiled));
+
+        let top_bottom_gap = (0..16)
+            .map(|x| {
+                let top = tiled.pixels[x as usize];
+                let bottom = tiled.pixels[(15 * 16 + x) as usize];
+                (top.g as i32 - bottom.g as i32).abs()
+            })
+            .max()
+            .unwrap();
+        assert!(top_bottom_gap <= 15, "gap {}", top_bottom_gap);
+    }
+
+    #[test]
+    fn sub_region_only_changes_pixels_inside_the_region() {
+        let input = solid(6, 5, Pixel::new(10, 20, 30));
+
+        let output = SubRegion::new(1, 2, 3, 2, Box::new(Invert::new()))
+            .apply(&input)
+            .unwrap();
+        for y in 0..5 {
+            for x in 0..6 {
+                let inside = (1..4).contains(&x) && (2..4).contains(&y);
+                let expected = if inside {
+                    (245, 235, 225)
+                } else {
+                    (10, 20, 30)
+                };
+                assert_eq!(rgb(output.pixels[(y * 6 + x) as usize]), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn sub_region_rejects_a_size_changing_command() {
+        let region = SubRegion::new(0, 0, 4, 4, Box::new(Crop::new(0, 0, 2, 2)));
+        assert!(region.apply(&solid(6, 6, Pixel::new(0, 0, 0))).is_err());
+    }
+
+    #[test]
+    fn perspective_with_the_image_corners_is_identity() {
+        let input = noise(9, 7);
+        let corners = [(0.0, 0.0), (8.0, 0.0), (8.0, 6.0), (0.0, 6.0)];
+
+        let output = Perspective::new(corners, 9, 7).apply(&input).unwrap();
+        for (a, b) in input.pixels.iter().zip(&output.pixels) {
+            assert_eq!(rgb(*a), rgb(*b));
+        }
+    }
+
+    #[test]
+    fn sampling_transforms_handle_an_empty_source() {
+        let empty = image(0, 0, |_, _| Pixel::new(0, 0, 0));
+        let corners = [(0.0, 0.0), (8.0, 0.0), (8.0, 6.0), (0.0, 6.0)];
+
+        // Output size is independent of the input: rejected
+        assert!(Perspective::new(corners, 4, 4).apply(&empty).is_err());
+        assert!(Resize::new(3, 3).apply(&empty).is_err());
+        assert!(
+            Resize::letterboxed(8, 8, Pixel::new(0, 0, 0))
+                .apply(&empty)
+                .is_err()
+        );
+        assert!(BlurFill::new(5, 3, 2).apply(&empty).is_err());
+        assert!(TinyPlanet::new(8).apply(&empty).is_err());
+        assert!(AutoStraighten::new(10.0).apply(&empty).is_err());
+        assert!(
+            Keystone::new(0, 0, Pixel::new(0, 0, 0))
+                .apply(&empty)
+                .is_err()
+        );
+
+        // Output size follows the input: nothing to sample
+        let same_size: Vec<Box<dyn Transformation>> = vec![
+            Box::new(LensCorrect::new(0.1, 0.0)),
+            Box::new(Kaleidoscope::new(6)),
+            Box::new(ZoomBlur::new(0, 0, 0.5)),
+            Box::new(Halftone::new(4, 45.0)),
+            Box::new(DisplacementMap::new(
+                image(0, 0, |_, _| Pixel::new(0, 0, 0)),
+                4.0,
+            )),
+        ];
+        for transform in same_size {
+            assert!(transform.apply(&empty).unwrap().pixels.is_empty());
+        }
+    }
+
+    #[test]
+    fn chromatic_aberration_puts_red_and_blue_fringes_on_opposite_sides() {
+        // White bar at x 3..9 on black
+        let bar = white_at(12, 3, |x, _| (3..9).contains(&x));
+
+        let output = ChromaticAberration::new(2).apply(&bar).unwrap();
+        let row: Vec<(u8, u8, u8)> = output.pixels[12..24].iter().map(|p| rgb(*p)).collect();
+        assert_eq!(row[2], (0, 0, 255));
+        assert_eq!(row[9], (255, 0, 0));
+        assert_eq!(row[6], (255, 255, 255));
+    }
+
+    #[test]
+    fn glitch_is_deterministic_for_a_seed() {
+        let input = noise(32, 32);
+
+        let first = Glitch::new(0.8, 42).apply(&input).unwrap();
+        let second = Glitch::new(0.8, 42).apply(&input).unwrap();
+        let other_seed = Glitch::new(0.8, 43).apply(&input).unwrap();
+
+        let same = |a: &Image, b: &Image| {
+            a.pixels
+                .iter()
+                .zip(&b.pixels)
+                .all(|(p, q)| rgb(*p) == rgb(*q))
+        };
+        assert!(same(&first, &second));
+        assert!(!same(&first, &other_seed));
+        assert!(!same(&first, &input));
+    }
+
+    #[test]
+    fn glitch_with_zero_intensity_is_identity() {
+        let input = noise(16, 16);
+
+        let output = Glitch::new(0.0, 7).apply(&input).unwrap();
+        for (a, b) in input.pixels.iter().zip(&output.pixels) {
+            assert_eq!(rgb(*a), rgb(*b));
+        }
+    }
+
+    #[test]
+    fn glitch_handles_zero_width() {
+        let empty = image(0, 4, |_, _| Pixel::new(0, 0, 0));
+        let output = Glitch::new(1.0, 1).apply(&empty).unwrap();
+        assert_eq!((output.width, output.height), (0, 4));
+    }
+
+    #[test]
+    fn protanopia_brings_red_and_green_closer() {
+        let red = Pixel::new(220, 40, 40);
+        let green = Pixel::new(40, 160, 40);
+        let input = image(2, 1, |x, _| if x == 0 { red } else { green });
+
+        let output = ColorBlind::new(CbKind::Protanopia).apply(&input).unwrap();
+        let before = red.delta_e(green);
+        let after = output.pixels[0].delta_e(output.pixels[1]);
+        assert!(after < before / 2.0, "{} vs {}", after, before);
+    }
+
+    #[test]
+    fn color_blindness_keeps_grays() {
+        let gray = solid(1, 1, Pixel::new(128, 128, 128));
+
+        for kind in [CbKind::Protanopia, CbKind::Deuteranopia, CbKind::Tritanopia] {
+            let p = ColorBlind::new(kind).apply(&gray).unwrap().pixels[0];
+            for v in [p.r, p.g, p.b] {
+                assert!((v as i32 - 128).abs() <= 2, "{:?} gave {:?}", kind, p);
+            }
+        }
+    }
+
+    #[test]
+    fn red_eye_darkens_the_pupil_and_keeps_the_rest() {
+        let skin = Pixel::new(225, 170, 140);
+        let red = Pixel::new(200, 30, 30);
+        // Red pupil of radius 2 at (5, 5) on skin, plus a red spot outside the circle
+        let face = image(12, 12, |x, y| {
+            let (dx, dy) = (x - 5, y - 5);
+            if dx * dx + dy * dy <= 4 || (x == 11 && y == 11) {
+                red
+            } else {
+                skin
+            }
+        });
+
+        let output = RedEye::new(5, 5, 3).apply(&face).unwrap();
+        let pupil = output.pixels[5 * 12 + 5];
+        assert!(pupil.r < 100 && pupil.r == pupil.g);
+        assert_eq!(rgb(output.pixels[5 * 12 + 8]), rgb(skin));
+        assert_eq!(rgb(output.pixels[11 * 12 + 11]), rgb(red));
+    }
+
+    // Correlation between horizontally adjacent values of the red channel
+    fn neighbor_correlation(image: &Image) -> f32 {
+        let mut pairs = Vec::new();
+        for y in 0..image.height {
+            for x in 0..image.width - 1 {
+                let idx = (y * image.width + x) as usize;
+                pairs.push((image.pixels[idx].r as f32, image.pixels[idx + 1].r as f32));
+            }
+        }
+
+        let n = pairs.len() as f32;
+        let mean_a = pairs.iter().map(|p| p.0).sum::<f32>() / n;
+        let mean_b = pairs.iter().map(|p| p.1).sum::<f32>() / n;
+        let cov: f32 = pairs.iter().map(|p| (p.0 - mean_a) * (p.1 - mean_b)).sum();
+        let var_a: f32 = pairs.iter().map(|p| (p.0 - mean_a).powi(2)).sum();
+        let var_b: f32 = pairs.iter().map(|p| (p.1 - mean_b).powi(2)).sum();
+        cov / (var_a * var_b).sqrt()
+    }
+
+    #[test]
+    fn coarse_grain_clumps_more_than_fine_grain() {
+        let gray = solid(64, 64, Pixel::new(128, 128, 128));
+
+        let fine = FilmGrain::new(0.2, 1.0, 5).apply(&gray).unwrap();
+        let coarse = FilmGrain::new(0.2, 4.0, 5).apply(&gray).unwrap();
+        assert!(neighbor_correlation(&fine).abs() < 0.2);
+        assert!(neighbor_correlation(&coarse) > 0.6);
+
+        // Luma-only: every channel gets the same offset
+        assert!(coarse.pixels.iter().all(|p| p.r == p.g && p.g == p.b));
+    }
+
+    #[test]
+    fn heatmap_maps_black_and_white_to_the_palette_ends() {
+        let input = image(2, 1, |x, _| {
+            if x == 0 {
+                Pixel::new(0, 0, 0)
+            } else {
+                Pixel::new(255, 255, 255)
+            }
+        });
+
+        for palette in [
+            HeatmapPalette::Jet,
+            HeatmapPalette::Viridis,
+            HeatmapPalette::Grayscale,
+            HeatmapPalette::Inferno,
+        ] {
+            let stops = palette.stops();
+            let output = Heatmap::new(palette).apply(&input).unwrap();
+            let low = Pixel::from_hex(stops[0].1);
+            let high = Pixel::from_hex(stops[stops.len() - 1].1);
+            assert_eq!(rgb(output.pixels[0]), rgb(low), "{:?}", palette);
+            assert_eq!(rgb(output.pixels[1]), rgb(high), "{:?}", palette);
+        }
+    }
+
+    #[test]
+    fn letterbox_adds_bars_and_keeps_the_aspect_ratio() {
+        let wide = solid(8, 4, Pixel::new(200, 100, 50));
+        let fill = Pixel::new(0, 0, 0);
+
+        let output = Resize::letterboxed(8, 8, fill).apply(&wide).unwrap();
+        assert_eq!((output.width, output.height), (8, 8));
+        for y in 0..8 {
+            let expected = if (2..6).contains(&y) {
+                (200, 100, 50)
+            } else {
+                (0, 0, 0)
+            };
+            for x in 0..8 {
+                assert_eq!(rgb(output.pixels[(y * 8 + x) as usize]), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn plain_resize_stretches_to_the_target() {
+        let output = Resize::new(3, 5).apply(&noise(8, 4)).unwrap();
+        assert_eq!((output.width, output.height), (3, 5));
+        assert!(Resize::new(0, 5).apply(&noise(8, 4)).is_err());
+    }
+
+    #[test]
+    fn per_channel_autolevels_removes_a_color_cast() {
+        // A blue-tinted ramp: blue spans a narrower range than red and green
+        let tinted = image(256, 1, |x, _| {
+            Pixel::new(x as u8, x as u8, 100 + (x as u8) / 4)
+        });
+
+        let output = AutoLevels::new(true, 0.0).apply(&tinted).unwrap();
+        let first = rgb(output.pixels[0]);
+        let last = rgb(output.pixels[255]);
+        assert_eq!(first, (0, 0, 0));
+        assert_eq!(last, (255, 255, 255));
+
+        // Sharing the luma range keeps the cast
+        let shared = AutoLevels::new(false, 0.0).apply(&tinted).unwrap();
+        let (r, _, b) = rgb(shared.pixels[0]);
+        assert!(b > r);
+    }
+
+    #[test]
+    fn autolevels_rejects_an_out_of_range_clip() {
+        assert!(AutoLevels::new(true, 0.5).apply(&noise(4, 4)).is_err());
+    }
+
+    #[test]
+    fn embedded_message_round_trips_with_tiny_changes() {
+        let source = noise(16, 16);
+        let embedded = EmbedMessage::new("hello, é world".to_string())
+            .apply(&source)
+            .unwrap();
+
+        assert_eq!(extract_message(&embedded).unwrap(), "hello, é world");
+        for (a, b) in source.pixels.iter().zip(&embedded.pixels) {
+            assert!(a.r.abs_diff(b.r) <= 1);
+            assert!(a.g.abs_diff(b.g) <= 1);
+            assert!(a.b.abs_diff(b.b) <= 1);
+        }
+    }
+
+    #[test]
+    fn embed_rejects_a_message_that_does_not_fit() {
+        // 4 pixels hold 12 bits, less than the 32-bit header
+        assert!(
+            EmbedMessage::new("x".to_string())
+                .apply(&noise(2, 2))
+                .is_err()
+        );
+        assert!(extract_message(&noise(2, 2)).is_err());
+    }
+
+    #[test]
+    fn blurfill_covers_the_frame_without_bars() {
+        let color = Pixel::new(30, 160, 90);
+        let output = BlurFill::new(8, 8, 2).apply(&solid(8, 4, color)).unwrap();
+
+        assert_eq!((output.width, output.height), (8, 8));
+        // The corners come from the blurred background, not a flat fill
+        for index in [0, 7, 56, 63] {
+            let (r, g, b) = rgb(output.pixels[index]);
+            assert!(r.abs_diff(30) <= 1 && g.abs_diff(160) <= 1 && b.abs_diff(90) <= 1);
+        }
+    }
+
+    #[test]
+    fn blurfill_keeps_the_whole_image_centered() {
+        let source = image(4, 2, |x, _| {
+            if x < 2 {
+                Pixel::new(255, 0, 0)
+            } else {
+                Pixel::new(0, 0, 255)
+            }
+        });
+        let output = BlurFill::new(4, 4, 1).apply(&source).unwrap();
+
+        // The foreground occupies rows 1-2 untouched
+        for x in 0..4 {
+            let expected = rgb(source.pixels[x]);
+            assert_eq!(rgb(output.pixels[4 + x]), expected);
+            assert_eq!(rgb(output.pixels[8 + x]), expected);
+        }
+        assert!(BlurFill::new(0, 4, 1).apply(&source).is_err());
+    }
+
+    #[test]
+    fn autostraighten_levels_a_tilted_horizon() {
+        // Bright sky over dark ground, the horizon descending 5 degrees
+        let slope = 5.0f32.to_radians().tan();
+        let tilted = image(160, 100, |x, y| {
+            let horizon = 50.0 + (x as f32 - 80.0) * slope;
+            if (y as f32) < horizon {
+                Pixel::new(255, 255, 255)
+            } else {
+                Pixel::new(0, 0, 0)
+            }
+        });
+
+        let straighten = AutoStraighten::new(10.0);
+        let detected = straighten.detect_angle(&tilted).to_degrees();
+        assert!((detected - 5.0).abs() <= 0.3, "detected {}", detected);
+
+        let output = straighten.apply(&tilted).unwrap();
+        let horizon_row = |x: i32| {
+            (0..output.height)
+                .find(|&y| output.pixels[(y * output.width + x) as usize].luma() < 128.0)
+                .unwrap()
+        };
+        let left = horizon_row(2);
+        let right = horizon_row(output.width - 3);
+        assert!((left - right).abs() <= 1, "left {} right {}", left, right);
+    }
+
+    #[test]
+    fn per_channel_convolve_only_touches_channels_with_kernels() {
+        let sharpen = Kernel::from_values(vec![0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0]);
+        let convolve = PerChannelConvolve::builder()
+            .red(sharpen.unwrap())
+            .green(Kernel::identity())
+            .build();
+
+        // A dim red spot on a dimmer background, green varying everywhere
+        let source = image(5, 5, |x, y| {
+            let red = if (x, y) == (2, 2) { 100 } else { 60 };
+            Pixel::new(red, (x * 40 + y * 10) as u8, 30)
+        });
+        let output = convolve.apply(&source).unwrap();
+
+        for (a, b) in source.pixels.iter().zip(&output.pixels) {
+            assert_eq!(a.g, b.g);
+            assert_eq!(a.b, b.b);
+        }
+        // 5 * 100 - 4 * 60 = 260, clamped
+        assert_eq!(output.pixels[12].r, 255);
+        // Neighbors of the spot get darker: 5 * 60 - 3 * 60 - 100 = 20
+        assert_eq!(output.pixels[7].r, 20);
+    }
+
+    #[test]
+    fn kernel_values_must_form_an_odd_square() {
+        assert!(Kernel::from_values(vec![1.0; 4]).is_err());
+        assert!(Kernel::from_values(vec![1.0; 8]).is_err());
+        assert!(Kernel::from_values(vec![1.0; 25]).is_ok());
+    }
+
+    #[test]
+    fn film_negative_with_a_neutral_base_matches_invert() {
+        let source = noise(8, 8);
+        let negative = FilmNegative::new(Pixel::new(255, 255, 255))
+            .apply(&source)
+            .unwrap();
+        let inverted = Invert::new().apply(&source).unwrap();
+
+        for (a, b) in negative.pixels.iter().zip(&inverted.pixels) {
+            assert_eq!(rgb(*a), rgb(*b));
+        }
+    }
+
+    #[test]
+    fn film_negative_removes_the_orange_mask() {
+        let base = Pixel::new(230, 150, 90);
+        // Unexposed film plus a neutral mid-gray patch at half the base density
+        let scan = image(
+            10,
+            10,
+            |x, _| {
+                if x < 5 { base } else { Pixel::new(115, 75, 45) }
+            },
+        );
+
+        for transform in [FilmNegative::new(base), FilmNegative::auto()] {
+            let output = transform.apply(&scan).unwrap();
+            assert_eq!(rgb(output.pixels[0]), (0, 0, 0));
+            let (r, g, b) = rgb(output.pixels[9]);
+            assert_eq!((r, g), (b, b));
+            assert!(r.abs_diff(128) <= 1);
+        }
+    }
+
+    #[test]
+    fn film_negative_returns_an_empty_image_unchanged() {
+        let empty = image(0, 0, |_, _| Pixel::new(0, 0, 0));
+        for transform in [
+            FilmNegative::auto(),
+            FilmNegative::new(Pixel::new(230, 140, 90)),
+        ] {
+            assert!(transform.apply(&empty).unwrap().pixels.is_empty());
+        }
+    }
+
+    #[test]
+    fn selective_color_only_adjusts_the_target_hue() {
+        let red = Pixel::new(200, 40, 40);
+        let blue = Pixel::new(40, 60, 200);
+        let gray = Pixel::new(120, 120, 120);
+        let source = image(3, 1, |x, _| [red, blue, gray][x as usize]);
+
+        let output = SelectiveColor::new(230.0, 40.0, -0.5, 0.0)
+            .apply(&source)
+            .unwrap();
+
+        assert_eq!(rgb(output.pixels[0]), rgb(red));
+        assert_eq!(rgb(output.pixels[2]), rgb(gray));
+        let (_, before, _) = blue.to_hsl();
+        let (_, after, _) = output.pixels[1].to_hsl();
+        assert!(after < before - 0.2);
+    }
+
+    #[test]
+    fn selective_color_rejects_a_bad_range() {
+        assert!(
+            SelectiveColor::new(0.0, 0.0, 0.1, 0.0)
+                .apply(&noise(2, 2))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn dog_draws_a_line_at_an_edge_only() {
+        let step = image(20, 20, |x, _| {
+            if x < 10 {
+                Pixel::new(0, 0, 0)
+            } else {
+                Pixel::new(255, 255, 255)
+            }
+        });
+
+        let output = DoG::new(1.0, 2.0, 10.0).apply(&step).unwrap();
+        let row = &output.pixels[200..220];
+
+        // A black line on the dark side of the edge, white everywhere else
+        assert_eq!(rgb(row[9]), (0, 0, 0));
+        for (x, p) in row.iter().enumerate() {
+            let (r, g, b) = rgb(*p);
+            assert!(r == g && g == b && (r == 0 || r == 255));
+            if !(3..10).contains(&x) {
+                assert_eq!(r, 255, "x = {}", x);
+            }
+        }
+        assert!(DoG::new(0.0, 3.0, 5.0).apply(&step).is_err());
+    }
+
+    #[test]
+    fn cartoon_quantizes_flats_and_outlines_edges() {
+        let source = image(10, 10, |x, _| {
+            if x < 5 {
+                Pixel::new(100, 30, 200)
+            } else {
+                Pixel::new(240, 220, 20)
+            }
+        });
+
+        let output = Cartoon::new(3, 200.0).apply(&source).unwrap();
+
+        // Levels 0, 127.5 and 255: flats snap to the nearest level
+        assert_eq!(rgb(output.pixels[51]), (127, 0, 255));
+        assert_eq!(rgb(output.pixels[58]), (255, 255, 0));
+        // The boundary columns are drawn black
+        assert_eq!(rgb(output.pixels[54]), (0, 0, 0));
+        assert_eq!(rgb(output.pixels[55]), (0, 0, 0));
+        assert!(Cartoon::new(1, 200.0).apply(&source).is_err());
+    }
+
+    #[test]
+    fn tiny_planet_is_square_with_the_nadir_in_the_center() {
+        // Sky on top, ground below, one marked pixel at the bottom center
+        let panorama = image(21, 11, |x, y| match (x, y) {
+            (10, 10) => Pixel::new(0, 255, 0),
+            (_, 10) => Pixel::new(255, 0, 0),
+            (_, y) if y < 5 => Pixel::new(0, 0, 255),
+            _ => Pixel::new(128, 128, 128),
+        });
+
+        let output = TinyPlanet::new(15).apply(&panorama).unwrap();
+        assert_eq!((output.width, output.height), (15, 15));
+        assert_eq!(rgb(output.pixels[7 * 15 + 7]), (0, 255, 0));
+        // The corners are far from the nadir and show the sky
+        assert_eq!(rgb(output.pixels[0]), (0, 0, 255));
+        assert_eq!(rgb(output.pixels[15 * 15 - 1]), (0, 0, 255));
+    }
+
+    #[test]
+    fn scanlines_darken_only_rows_at_the_spacing() {
+        let source = solid(4, 7, Pixel::new(200, 100, 50));
+        let output = Scanlines::new(0.5, 3).apply(&source).unwrap();
+
+        for y in 0..7 {
+            let expected = if y % 3 == 0 {
+                (100, 50, 25)
+            } else {
+                (200, 100, 50)
+            };
+            for x in 0..4 {
+                assert_eq!(rgb(output.pixels[(y * 4 + x) as usize]), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn scanline_subpixel_mask_keeps_one_channel_per_column() {
+        let source = solid(3, 2, Pixel::new(200, 200, 200));
+        let output = Scanlines::new(0.4, 2)
+            .with_subpixel()
+            .apply(&source)
+            .unwrap();
+
+        // Row 1 is between scanlines: only the mask (1 - 0.4 / 4) applies
+        assert_eq!(rgb(output.pixels[3]), (200, 180, 180));
+        assert_eq!(rgb(output.pixels[4]), (180, 200, 180));
+        assert_eq!(rgb(output.pixels[5]), (180, 180, 200));
+        assert!(Scanlines::new(0.4, 0).apply(&source).is_err());
+    }
+
+    #[test]
+    fn diff_heatmap_marks_only_changed_pixels_red() {
+        let before = noise(6, 6);
+        let identical = DiffHeatmap::new(noise(6, 6), 4.0).apply(&before).unwrap();
+        for p in &identical.pixels {
+            assert!(p.r == p.g && p.g == p.b);
+        }
+
+        let mut changed = noise(6, 6);
+        changed.pixels[14] = Pixel::new(
+            255 - changed.pixels[14].r,
+            255 - changed.pixels[14].g,
+            255 - changed.pixels[14].b,
+        );
+        let output = DiffHeatmap::new(changed, 4.0).apply(&before).unwrap();
+        let (r, g, b) = rgb(output.pixels[14]);
+        assert!(r > 200 && g < 40 && b < 40, "{:?}", (r, g, b));
+        assert!(output.pixels[13].r == output.pixels[13].g);
+
+        assert!(DiffHeatmap::new(noise(5, 6), 1.0).apply(&before).is_err());
+    }
+
+    #[test]
+    fn light_leak_tints_its_corner_and_spares_the_opposite_one() {
+        let source = solid(20, 20, Pixel::new(100, 100, 100));
+        let orange = Pixel::new(255, 140, 0);
+
+        let output = LightLeak::new(orange, LeakPosition::TopLeft, 1.0, 7)
+            .apply(&source)
+            .unwrap();
+        let (r, g, b) = rgb(output.pixels[0]);
+        assert!(r > 200 && g > 100 && b == 100, "{:?}", (r, g, b));
+        assert_eq!(rgb(output.pixels[399]), (100, 100, 100));
+
+        let none = LightLeak::new(orange, LeakPosition::TopLeft, 0.0, 7)
+            .apply(&source)
+            .unwrap();
+        assert!(none.pixels.iter().all(|p| rgb(*p) == (100, 100, 100)));
+    }
+
+    #[test]
+    fn area_downscale_averages_a_checkerboard_to_gray() {
+        let checkerboard = white_at(8, 8, |x, y| (x + y) % 2 == 0);
+
+        let area = Resize::new(4, 4)
+            .with_sampling(Sampling::Area)
+            .apply(&checkerboard)
+            .unwrap();
+        assert!(area.pixels.iter().all(|p| rgb(*p) == (128, 128, 128)));
+
+        let nearest = Resize::new(4, 4).apply(&checkerboard).unwrap();
+        assert!(nearest.pixels.iter().all(|p| p.r == 0 || p.r == 255));
+    }
+
+    #[test]
+    fn lens_correct_with_zero_coefficients_is_identity() {
+        let source = noise(9, 7);
+        let output = LensCorrect::new(0.0, 0.0).apply(&source).unwrap();
+        for (a, b) in source.pixels.iter().zip(&output.pixels) {
+            assert_eq!(rgb(*a), rgb(*b));
+        }
+    }
+
+    #[test]
+    fn lens_correct_straightens_a_barrel_bowed_line() {
+        let k1 = 0.6;
+        let (cx, cy) = (30.0f32, 20.0f32);
+        let norm2 = cx * cx + cy * cy;
+        // A straight line 16 pixels above center, as a barrel lens shows it
+        let bowed = image(61, 41, |x, y| {
+            let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+            let undistorted_y = dy * (1.0 + k1 * (dx * dx + dy * dy) / norm2);
+            if (undistorted_y + 16.0).abs() < 0.8 {
+                Pixel::new(0, 0, 0)
+            } else {
+                Pixel::new(255, 255, 255)
+            }
+        });
+
+        // Spread of the line's darkness-weighted row across the columns
+        let bow = |image: &Image| {
+            let rows: Vec<f32> = (5..56)
+                .map(|x| {
+                    let (mut sum, mut weight) = (0.0, 0.0);
+                    for y in 0..image.height {
+                        let dark = 255.0 - image.pixels[(y * image.width + x) as usize].luma();
+                        sum += y as f32 * dark;
+                        weight += dark;
+                    }
+                    sum / weight
+                })
+                .collect();
+            let max = rows.iter().copied().fold(f32::MIN, f32::max);
+            let min = rows.iter().copied().fold(f32::MAX, f32::min);
+            max - min
+        };
+
+        let corrected = LensCorrect::new(k1, 0.0).apply(&bowed).unwrap();
+        assert!(bow(&bowed) > 2.0);
+        assert!(bow(&corrected) < 1.0, "still bowed by {}", bow(&corrected));
+    }
+
+    #[test]
+    fn focus_peaking_marks_sharp_edges_only() {
+        // A hard edge on the left half, a gentle ramp on the right
+        let source = image(20, 6, |x, _| {
+            let value = match x {
+                0..5 => 0,
+                5..10 => 255,
+                _ => (x - 10) * 10,
+            };
+            Pixel::new(value as u8, value as u8, value as u8)
+        });
+        let magenta = Pixel::new(255, 0, 255);
+
+        let output = FocusPeaking::new(magenta, 100.0).apply(&source).unwrap();
+        let row = &output.pixels[40..60];
+        assert_eq!(rgb(row[4]), (255, 0, 255));
+        assert_eq!(rgb(row[5]), (255, 0, 255));
+        for p in &row[12..19] {
+            let (r, g, b) = rgb(*p);
+            assert!(r == g && g == b);
+        }
+        assert_eq!(rgb(row[1]), (0, 0, 0));
+    }
+
+    #[test]
+    fn spotlight_brightens_its_center_and_leaves_the_outside() {
+        let source = solid(21, 21, Pixel::new(100, 80, 60));
+        let output = Spotlight::new(10, 10, 6.0, 0.5, 1.0)
+            .apply(&source)
+            .unwrap();
+
+        assert_eq!(rgb(output.pixels[10 * 21 + 10]), (150, 120, 90));
+        assert_eq!(rgb(output.pixels[0]), (100, 80, 60));
+        assert_eq!(rgb(output.pixels[10 * 21 + 17]), (100, 80, 60));
+        assert!(
+            Spotlight::new(10, 10, 0.0, 0.5, 1.0)
+                .apply(&source)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn supersampling_smooths_a_rotated_edge() {
+        let step = white_at(40, 40, |x, _| x >= 20);
+        let (sin, cos) = 30.0f32.to_radians().sin_cos();
+        let rotate = |x: f32, y: f32| {
+            let (dx, dy) = (x - 19.5, y - 19.5);
+            (19.5 + dx * cos - dy * sin, 19.5 + dx * sin + dy * cos)
+        };
+
+        // A 16x16 grid stands in for the exact pixel coverage
+        let reference = render_supersampled(&step, 40, 40, 16, rotate);
+        let error = |supersample: u8| {
+            let rendered = render_supersampled(&step, 40, 40, supersample, rotate);
+            rendered
+                .iter()
+                .zip(&reference)
+                .map(|(a, b)| a.r.abs_diff(b.r) as u32)
+                .sum::<u32>()
+        };
+
+        assert!(
+            error(4) * 3 < error(1),
+            "4x {} vs 1x {}",
+            error(4),
+            error(1)
+        );
+        assert!(error(2) < error(1));
+    }
+
+    #[test]
+    fn supersample_factor_must_be_1_2_or_4() {
+        for factor in [1, 2, 4] {
+            assert!(check_supersample("test", factor).is_ok());
+        }
+        for factor in [0, 3, 8] {
+            assert!(check_supersample("test", factor).is_err());
+        }
+        assert!(
+            LensCorrect::new(0.1, 0.0)
+                .with_supersample(3)
+                .apply(&noise(4, 4))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn map_pixels_builds_a_vignette() {
+        let (width, height) = (21, 11);
+        let (cx, cy) = ((width - 1) as f32 / 2.0, (height - 1) as f32 / 2.0);
+        let max = (cx * cx + cy * cy).sqrt();
+        let vignette = MapPixels::new(move |x, y, p: Pixel| {
+            let d = ((x as f32 - cx).powi(2) + (y as f32 - cy).powi(2)).sqrt() / max;
+            let k = 1.0 - 0.6 * d * d;
+            Pixel::from_f32(
+                (p.r as f32 * k).round(),
+                (p.g as f32 * k).round(),
+                (p.b as f32 * k).round(),
+            )
+        });
+
+        let output = vignette
+            .apply(&solid(width, height, Pixel::new(200, 200, 200)))
+            .unwrap();
+        assert_eq!(
+            rgb(output.pixels[(5 * width + 10) as usize]),
+            (200, 200, 200)
+        );
+        // The corners are at distance `max`, so k = 0.4
+        assert_eq!(rgb(output.pixels[0]), (80, 80, 80));
+        assert_eq!(
+            rgb(output.pixels[(width * height - 1) as usize]),
+            (80, 80, 80)
+        );
+        let edge = rgb(output.pixels[(5 * width) as usize]).0;
+        assert!(edge > 80 && edge < 200);
+    }
+
+    #[test]
+    fn map_pixels_with_identity_closure_returns_an_equal_image() {
+        let source = noise(7, 5);
+        let output = MapPixels::new(|_, _, p| p).apply(&source).unwrap();
+
+        assert_eq!((output.width, output.height), (7, 5));
+        for (a, b) in source.pixels.iter().zip(&output.pixels) {
+            assert_eq!(rgb(*a), rgb(*b));
+        }
+    }
+
+    #[test]
+    fn fused_adjustments_match_sequential_application() {
+        let source = image(256, 1, |x, _| {
+            Pixel::new(x as u8, 255 - x as u8, (x * 7 % 256) as u8)
+        });
+
+        let fused = AdjustmentStack::new()
+            .brightness(1.2)
+            .invert()
+            .build()
+            .apply(&source)
+            .unwrap();
+        let sequential = Pipeline::new()
+            .then(Brightness::new(1.2))
+            .then(Invert::new())
+            .apply(&source)
+            .unwrap();
+        for (a, b) in fused.pixels.iter().zip(&sequential.pixels) {
+            assert_eq!(rgb(*a), rgb(*b));
+        }
+
+        let fused = AdjustmentStack::new()
+            .contrast(1.5)
+            .brightness(0.8)
+            .build()
+            .apply(&source)
+            .unwrap();
+        let sequential = Pipeline::new()
+            .then(Contrast::new(1.5))
+            .then(Brightness::new(0.8))
+            .apply(&source)
+            .unwrap();
+        for (a, b) in fused.pixels.iter().zip(&sequential.pixels) {
+            assert_eq!(rgb(*a), rgb(*b));
+        }
+    }
+
+    #[test]
+    fn average_stack_yields_the_midpoint() {
+        let dark = solid(3, 3, Pixel::new(20, 100, 200));
+        let light = solid(3, 3, Pixel::new(220, 50, 0));
+
+        let output = AverageStack::new(vec![light]).apply(&dark).unwrap();
+        assert!(output.pixels.iter().all(|p| rgb(*p) == (120, 75, 100)));
+        assert!(
+            AverageStack::new(vec![solid(2, 3, Pixel::new(0, 0, 0))])
+                .apply(&dark)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn max_stack_keeps_the_brighter_channels() {
+        let trail = image(4, 1, |x, _| {
+            if x == 1 {
+                Pixel::new(250, 240, 10)
+            } else {
+                Pixel::new(10, 10, 30)
+            }
+        });
+        let later = image(4, 1, |x, _| {
+            if x == 2 {
+                Pixel::new(240, 250, 10)
+            } else {
+                Pixel::new(10, 10, 30)
+            }
+        });
+
+        let output = MaxStack::new(vec![later]).apply(&trail).unwrap();
+        let got: Vec<_> = output.pixels.iter().map(|p| rgb(*p)).collect();
+        assert_eq!(
+            got,
+            [(10, 10, 30), (250, 240, 30), (240, 250, 30), (10, 10, 30)]
+        );
+    }
+
+    #[test]
+    fn kaleidoscope_repeats_every_segment_angle() {
+        let source = noise(21, 21);
+        let output = Kaleidoscope::new(4).apply(&source).unwrap();
+
+        // A quarter turn about the center (10, 10) maps the pixel grid onto itself
+        for y in 0..21 {
+            for x in 0..21 {
+                let (rx, ry) = (20 - y, x);
+                let a = output.pixels[(y * 21 + x) as usize];
+                let b = output.pixels[(ry * 21 + rx) as usize];
+                assert!(a.r.abs_diff(b.r) <= 1 && a.g.abs_diff(b.g) <= 1 && a.b.abs_diff(b.b) <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn kaleidoscope_with_one_segment_is_identity() {
+        let source = noise(9, 7);
+        let output = Kaleidoscope::new(1).apply(&source).unwrap();
+        for (a, b) in source.pixels.iter().zip(&output.pixels) {
+            assert_eq!(rgb(*a), rgb(*b));
+        }
+        assert!(Kaleidoscope::new(0).apply(&source).is_err());
+    }
+
+    #[test]
+    fn keystone_top_inset_narrows_the_top_row() {
+        let source = solid(20, 10, Pixel::new(200, 200, 200));
+        let fill = Pixel::new(0, 0, 0);
+
+        let output = Keystone::new(4, 0, fill).apply(&source).unwrap();
+        let content = |y: i32| {
+            (0..20)
+                .filter(|&x| output.pixels[(y * 20 + x) as usize].r > 100)
+                .count()
+        };
+        assert!(content(0) <= 13, "top row keeps {} pixels", content(0));
+        assert_eq!(content(9), 20);
+        assert_eq!(rgb(output.pixels[0]), (0, 0, 0));
+        assert!(Keystone::new(10, 0, fill).apply(&source).is_err());
+    }
+
+    #[test]
+    fn keystone_supersampling_softens_the_slanted_edge() {
+        let source = solid(20, 10, Pixel::new(255, 255, 255));
+        let fill = Pixel::new(0, 0, 0);
+        let partial = |image: &Image| image.pixels.iter().filter(|p| p.r > 0 && p.r < 255).count();
+
+        let plain = Keystone::new(4, 0, fill).apply(&source).unwrap();
+        let smooth = Keystone::new(4, 0, fill)
+            .with_supersample(4)
+            .apply(&source)
+            .unwrap();
+        assert!(partial(&smooth) > partial(&plain));
+        assert!(
+            Keystone::new(4, 0, fill)
+                .with_supersample(3)
+                .apply(&source)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn luma_sharpen_leaves_flat_color_alone_and_steepens_a_luma_edge() {
+        let flat = solid(12, 12, Pixel::new(200, 80, 40));
+        let output = LumaSharpen::new(1.5, 2).apply(&flat).unwrap();
+        assert!(output.pixels.iter().all(|&p| rgb(p) == (200, 80, 40)));
+
+        let edge = image(12, 4, |x, _| {
+            if x < 6 {
+                Pixel::new(60, 60, 60)
+            } else {
+                Pixel::new(180, 180, 180)
+            }
+        });
+        let output = LumaSharpen::new(1.5, 2).apply(&edge).unwrap();
+        let (dark, light) = (output.pixels[5], output.pixels[6]);
+        assert!(
+            dark.r < 60 && light.r > 180,
+            "{:?} {:?}",
+            rgb(dark),
+            rgb(light)
+        );
+        // Gray stays gray: only luma moved
+        assert!(output.pixels.iter().all(|p| p.r == p.g && p.g == p.b));
+        assert_eq!(rgb(output.pixels[0]), (60, 60, 60));
+    }
+
+    #[test]
+    fn luma_sharpen_rejects_a_zero_radius() {
+        assert!(
+            LumaSharpen::new(1.0, 0)
+                .apply(&solid(2, 2, Pixel::new(0, 0, 0)))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn tone_map_pulls_highlights_down_and_keeps_mid_tones() {
+        // Mostly middle gray with a bright white patch
+        let scene = image(10, 10, |x, y| {
+            if x < 3 && y < 3 {
+                Pixel::new(255, 255, 255)
+            } else {
+                Pixel::new(118, 118, 118)
+            }
+        });
+        let output = ToneMapReinhard::new(0.25).apply(&scene).unwrap();
+
+        let highlight = output.pixels[0];
+        let mid = output.pixels[99];
+        assert!(highlight.r < 230, "highlight {:?}", rgb(highlight));
+        assert!((mid.r as i32 - 118).abs() <= 10, "mid-tone {:?}", rgb(mid));
+        assert!(highlight.r > mid.r);
+        assert!(
+            output
+                .pixels
+                .iter()
+                .all(|p| p.r < 255 && p.g < 255 && p.b < 255)
+        );
+    }
+
+    #[test]
+    fn tone_map_rejects_a_non_positive_key() {
+        assert!(
+            ToneMapReinhard::new(0.0)
+                .apply(&solid(2, 2, Pixel::new(9, 9, 9)))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn temperature_is_neutral_at_daylight_and_warms_below_it() {
+        let gray = solid(4, 4, Pixel::new(150, 150, 150));
+
+        let neutral = Temperature::new(6500.0, 0.0).apply(&gray).unwrap();
+        let p = neutral.pixels[0];
+        assert!(
+            [p.r, p.g, p.b].iter().all(|&v| (v as i32 - 150).abs() <= 2),
+            "{:?}",
+            rgb(p)
+        );
+
+        let warm = Temperature::new(3200.0, 0.0).apply(&gray).unwrap().pixels[0];
+        assert!(warm.r as i32 - warm.b as i32 > 40, "{:?}", rgb(warm));
+
+        let cool = Temperature::new(10000.0, 0.0).apply(&gray).unwrap().pixels[0];
+        assert!(cool.b > cool.r, "{:?}", rgb(cool));
+
+        let magenta = Temperature::new(6500.0, 1.0).apply(&gray).unwrap().pixels[0];
+        assert!(magenta.g < magenta.r && magenta.g < magenta.b);
+    }
+
+    #[test]
+    fn temperature_rejects_out_of_range_values() {
+        let gray = solid(2, 2, Pixel::new(150, 150, 150));
+        assert!(Temperature::new(500.0, 0.0).apply(&gray).is_err());
+        assert!(Temperature::new(5500.0, 2.0).apply(&gray).is_err());
+    }
+
+    #[test]
+    fn mirror_pad_reflects_the_edges_without_repeating_them() {
+        let source = image(3, 3, |x, y| Pixel::new((y * 3 + x) as u8, 0, 0));
+        let padded = MirrorPad::new(2).apply(&source).unwrap();
+        assert_eq!((padded.width, padded.height), (7, 7));
+
+        let at = |x: i32, y: i32| padded.pixels[(y * 7 + x) as usize].r;
+        // Interior is the original
+        for y in 0..3 {
+            for x in 0..3 {
+                assert_eq!(at(x + 2, y + 2), (y * 3 + x) as u8);
+            }
+        }
+        // Rows above mirror rows 1 and 2, columns to the right mirror 1 and 0
+        assert_eq!((at(2, 1), at(2, 0)), (3, 6));
+        assert_eq!((at(5, 2), at(6, 2)), (1, 0));
+        // Corners reflect in both directions
+        assert_eq!(at(0, 0), 8);
+    }
+
+    #[test]
+    fn mirror_pad_wider_than_the_image_bounces() {
+        let row = image(2, 1, |x, _| Pixel::new(x as u8 * 100, 0, 0));
+        let padded = MirrorPad::new(3).apply(&row).unwrap();
+        let first_row: Vec<u8> = padded.pixels[..8].iter().map(|p| p.r).collect();
+        assert_eq!(first_row, vec![100, 0, 100, 0, 100, 0, 100, 0]);
+
+        assert!(MirrorPad::new(-1).apply(&row).is_err());
+    }
+
+    #[test]
+    fn mirror_pad_rejects_an_empty_image() {
+        let empty = image(0, 0, |_, _| Pixel::new(0, 0, 0));
+        assert!(MirrorPad::new(2).apply(&empty).is_err());
+        assert!(MirrorPad::new(0).apply(&empty).unwrap().pixels.is_empty());
+    }
+
+    #[test]
+    fn zoom_blur_streaks_toward_the_center() {
+        // Vertical stripes: radial streaks across them average out, streaks
+        // running along them do not
+        let stripes = image(33, 33, |x, _| {
+            if x % 2 == 0 {
+                Pixel::new(255, 255, 255)
+            } else {
+                Pixel::new(0, 0, 0)
+            }
+        });
+        let output = ZoomBlur::new(16, 16, 0.5).apply(&stripes).unwrap();
+        let at = |img: &Image, x: i32, y: i32| img.pixels[(y * 33 + x) as usize].r;
+
+        assert_eq!(at(&output, 16, 16), at(&stripes, 16, 16));
+        assert_eq!(at(&output, 16, 0), at(&stripes, 16, 0));
+        let across = at(&output, 0, 16);
+        assert!((100..=155).contains(&across), "{}", across);
+    }
+
+    #[test]
+    fn zoom_blur_with_no_strength_is_identity() {
+        let source = noise(12, 9);
+        let output = ZoomBlur::new(3, 4, 0.0).apply(&source).unwrap();
+        assert!(
+            source
+                .pixels
+                .iter()
+                .zip(&output.pixels)
+                .all(|(&a, &b)| rgb(a) == rgb(b))
+        );
+
+        assert!(ZoomBlur::new(0, 0, 1.5).apply(&source).is_err());
+    }
+
+    #[test]
+    fn halftone_dot_size_follows_darkness() {
+        let mean_luma = |img: &Image| -> f32 {
+            img.pixels.iter().map(|p| p.luma()).sum::<f32>() / img.pixels.len() as f32
+        };
+
+        for angle in [0.0, 45.0] {
+            let halftone = Halftone::new(6, angle);
+            let dark = halftone.apply(&solid(36, 36, Pixel::new(0, 0, 0))).unwrap();
+            let mid = halftone
+                .apply(&solid(36, 36, Pixel::new(128, 128, 128)))
+                .unwrap();
+            let light = halftone
+                .apply(&solid(36, 36, Pixel::new(255, 255, 255)))
+                .unwrap();
+
+            let (dark, mid, light) = (mean_luma(&dark), mean_luma(&mid), mean_luma(&light));
+            assert!(dark < 60.0, "dark {} at {}", dark, angle);
+            assert!((100.0..=160.0).contains(&mid), "mid {} at {}", mid, angle);
+            assert!(light > 245.0, "light {} at {}", light, angle);
+        }
+    }
+
+    #[test]
+    fn halftone_output_is_gray_and_rejects_tiny_dots() {
+        let output = Halftone::new(4, 15.0).apply(&noise(16, 16)).unwrap();
+        assert!(output.pixels.iter().all(|p| p.r == p.g && p.g == p.b));
+
+        assert!(Halftone::new(1, 0.0).apply(&noise(4, 4)).is_err());
+    }
+
+    #[test]
+    fn relief_swaps_lit_and_shaded_sides_when_the_light_turns_around() {
+        // A step up from left to right: the slope faces left
+        let step = image(10, 5, |x, _| {
+            if x < 5 {
+                Pixel::new(40, 40, 40)
+            } else {
+                Pixel::new(220, 220, 220)
+            }
+        });
+        let edge = |img: &Image| img.pixels[2 * 10 + 5].r;
+        let flat = |img: &Image| img.pixels[2 * 10].r;
+
+        let from_right = Relief::new(0.0, 45.0, 4.0).apply(&step).unwrap();
+        let from_left = Relief::new(180.0, 45.0, 4.0).apply(&step).unwrap();
+
+        // Flat ground takes sin(elevation) either way
+        assert_eq!(flat(&from_right), 180);
+        assert_eq!(flat(&from_left), 180);
+        assert!(edge(&from_right) < 180, "{}", edge(&from_right));
+        assert!(edge(&from_left) > 180, "{}", edge(&from_left));
+    }
+
+    #[test]
+    fn relief_rejects_bad_elevation_or_depth() {
+        let gray = solid(3, 3, Pixel::new(90, 90, 90));
+        assert!(Relief::new(0.0, 120.0, 1.0).apply(&gray).is_err());
+        assert!(Relief::new(0.0, 45.0, -1.0).apply(&gray).is_err());
+    }
+
+    #[test]
+    fn caption_bar_extends_the_canvas_and_centers_the_text() {
+        let (bg, fg) = (Pixel::new(20, 20, 20), Pixel::new(250, 250, 0));
+        let source = solid(60, 10, Pixel::new(200, 0, 0));
+        let output = CaptionBar::new("HI".to_string(), 20, bg, fg)
+            .apply(&source)
+            .unwrap();
+
+        assert_eq!((output.width, output.height), (60, 30));
+        assert!(output.pixels[..600].iter().all(|&p| rgb(p) == (200, 0, 0)));
+
+        let bar = &output.pixels[600..];
+        assert!(bar.iter().all(|&p| rgb(p) == rgb(bg) || rgb(p) == rgb(fg)));
+        let text_columns: Vec<i32> = (0..60)
+            .filter(|&x| (0..20).any(|y| rgb(bar[(y * 60 + x) as usize]) == rgb(fg)))
+            .collect();
+        assert!(!text_columns.is_empty());
+        let (left, right) = (text_columns[0], 59 - text_columns[text_columns.len() - 1]);
+        assert!((left - right).abs() <= 2, "margins {} and {}", left, right);
+    }
+
+    #[test]
+    fn caption_bar_without_text_is_a_plain_bar() {
+        let bg = Pixel::new(0, 0, 90);
+        let output = CaptionBar::new(String::new(), 5, bg, Pixel::new(255, 255, 255))
+            .apply(&solid(4, 4, Pixel::new(1, 2, 3)))
+            .unwrap();
+
+        assert_eq!(output.height, 9);
+        assert!(output.pixels[16..].iter().all(|&p| rgb(p) == rgb(bg)));
+        assert!(
+            CaptionBar::new("x".to_string(), 0, bg, bg)
+                .apply(&solid(4, 4, bg))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn pixel_sort_orders_bright_runs_and_leaves_dark_pixels_in_place() {
+        let source = noise(24, 6);
+        let threshold = 100.0;
+        let output = PixelSort::new(threshold, SortDirection::Horizontal)
+            .apply(&source)
+            .unwrap();
+
+        for y in 0..6 {
+            let row = |img: &Image| -> Vec<Pixel> {
+                img.pixels[(y * 24) as usize..((y + 1) * 24) as usize].to_vec()
+            };
+            let (before, after) = (row(&source), row(&output));
+
+            for x in 0..24 {
+                if before[x].luma() < threshold {
+                    assert_eq!(rgb(after[x]), rgb(before[x]), "({}, {}) moved", x, y);
+                } else if x > 0 && before[x - 1].luma() >= threshold {
+                    assert!(after[x - 1].luma() <= after[x].luma());
+                }
+            }
+
+            // Sorting only permutes the row
+            let sorted = |pixels: Vec<Pixel>| {
+                let mut values: Vec<_> = pixels.into_iter().map(rgb).collect();
+                values.sort();
+                values
+            };
+            assert_eq!(sorted(before), sorted(after));
+        }
+    }
+
+    #[test]
+    fn pixel_sort_runs_along_columns_when_vertical() {
+        let column = image(1, 5, |_, y| {
+            let v = [200, 150, 10, 250, 180][y as usize];
+            Pixel::new(v, v, v)
+        });
+        let output = PixelSort::new(100.0, SortDirection::Vertical)
+            .apply(&column)
+            .unwrap();
+
+        let values: Vec<u8> = output.pixels.iter().map(|p| p.r).collect();
+        assert_eq!(values, vec![150, 200, 10, 180, 250]);
+        assert!(
+            PixelSort::new(300.0, SortDirection::Vertical)
+                .apply(&column)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn gradient_map_from_black_to_white_is_grayscale() {
+        let source = noise(10, 10);
+        let output = GradientMap::new(vec![
+            (0.0, Pixel::new(0, 0, 0)),
+            (1.0, Pixel::new(255, 255, 255)),
+        ])
+        .apply(&source)
+        .unwrap();
+
+        for (before, after) in source.pixels.iter().zip(&output.pixels) {
+            let luma = before.luma().round() as i32;
+            assert!(after.r == after.g && after.g == after.b);
+            assert!(
+                (after.r as i32 - luma).abs() <= 1,
+                "{} vs {}",
+                after.r,
+                luma
+            );
+        }
+    }
+
+    #[test]
+    fn gradient_map_hits_the_middle_stop_at_mid_luma() {
+        let map = GradientMap::new(vec![
+            (0.0, Pixel::new(0, 0, 80)),
+            (0.5, Pixel::new(220, 40, 10)),
+            (1.0, Pixel::new(255, 240, 0)),
+        ]);
+        let output = map
+            .apply(&image(3, 1, |x, _| {
+                let v = [0, 128, 255][x as usize];
+                Pixel::new(v, v, v)
+            }))
+            .unwrap();
+
+        assert_eq!(rgb(output.pixels[0]), (0, 0, 80));
+        let mid = output.pixels[1];
+        assert!((mid.r as i32 - 220).abs() <= 2 && (mid.g as i32 - 40).abs() <= 2);
+        assert_eq!(rgb(output.pixels[2]), (255, 240, 0));
+    }
+
+    #[test]
+    fn gradient_map_validates_its_stops() {
+        let black = Pixel::new(0, 0, 0);
+        let gray = solid(2, 2, black);
+        assert!(GradientMap::new(vec![(0.0, black)]).apply(&gray).is_err());
+        assert!(
+            GradientMap::new(vec![(0.0, black), (0.7, black), (0.3, black), (1.0, black)])
+                .apply(&gray)
+                .is_err()
+        );
+        assert!(
+            GradientMap::new(vec![(0.1, black), (1.0, black)])
+                .apply(&gray)
+                .is_err()
+        );
+        assert!(
+            GradientMap::new(vec![(0.0, black), (0.9, black)])
+                .apply(&gray)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn chroma_blur_smooths_color_and_keeps_luma() {
+        // Two colors of equal luma in a checkerboard: only chroma varies
+        let checker = image(10, 10, |x, y| {
+            if (x + y) % 2 == 0 {
+                Pixel::from_ycbcr(128.0, 100.0, 160.0)
+            } else {
+                Pixel::from_ycbcr(128.0, 156.0, 96.0)
+            }
+        });
+        let output = ChromaBlur::new(2).apply(&checker).unwrap();
+
+        let cb_swing = |img: &Image| {
+            let (_, a, _) = img.pixels[44].to_ycbcr();
+            let (_, b, _) = img.pixels[45].to_ycbcr();
+            (a - b).abs()
+        };
+        assert!(
+            cb_swing(&output) < cb_swing(&checker) / 2.0,
+            "{} -> {}",
+            cb_swing(&checker),
+            cb_swing(&output)
+        );
+        for (before, after) in checker.pixels.iter().zip(&output.pixels) {
+            let (y0, y1) = (before.to_ycbcr().0, after.to_ycbcr().0);
+            assert!((y0 - y1).abs() <= 1.5, "luma {} -> {}", y0, y1);
+        }
+    }
+
+    #[test]
+    fn chroma_blur_keeps_a_gray_edge_sharp() {
+        let edge = image(8, 4, |x, _| {
+            if x < 4 {
+                Pixel::new(30, 30, 30)
+            } else {
+                Pixel::new(220, 220, 220)
+            }
+        });
+        let output = ChromaBlur::new(3).apply(&edge).unwrap();
+        assert!(
+            edge.pixels
+                .iter()
+                .zip(&output.pixels)
+                .all(|(&a, &b)| rgb(a) == rgb(b))
+        );
+
+        assert!(ChromaBlur::new(0).apply(&edge).is_err());
+    }
+
+    #[test]
+    fn masked_by_follows_the_mask_luma() {
+        let source = noise(8, 8);
+        let masked = |value: u8| {
+            let mask = MapPixels::new(move |_, _, _| Pixel::new(value, value, value));
+            MaskedBy::new(Box::new(mask), Box::new(Invert::new()))
+                .apply(&source)
+                .unwrap()
+        };
+
+        let black = masked(0);
+        assert!(
+            source
+                .pixels
+                .iter()
+                .zip(&black.pixels)
+                .all(|(&a, &b)| rgb(a) == rgb(b))
+        );
+
+        let white = masked(255);
+        assert!(
+            source
+                .pixels
+                .iter()
+                .zip(&white.pixels)
+                .all(|(a, b)| b.r == 255 - a.r)
+        );
+
+        // Halfway between a value and its inverse is mid-gray
+        let half = masked(128);
+        assert!(half.pixels.iter().all(|p| (p.r as i32 - 128).abs() <= 1));
+    }
+
+    #[test]
+    fn masked_by_rejects_steps_that_resize() {
+        let masked = MaskedBy::new(Box::new(Resize::new(4, 4)), Box::new(Invert::new()));
+        assert!(masked.apply(&noise(8, 8)).is_err());
+    }
+
+    #[test]
+    fn composite_space_mixes_black_over_white_to_128_or_188() {
+        assert_eq!(CompositeSpace::Srgb.mix(255, 0.0, 0.5).round(), 128.0);
+        assert_eq!(CompositeSpace::Linear.mix(255, 0.0, 0.5).round(), 188.0);
+
+        let white = solid(3, 3, Pixel::new(255, 255, 255));
+        let black = Pixel::new(0, 0, 0);
+        let overlay = GradientOverlay::new(black, black, GradientKind::Radial, 0.5);
+        let srgb = overlay.apply(&white).unwrap();
+        let linear = GradientOverlay::new(black, black, GradientKind::Radial, 0.5)
+            .with_space(CompositeSpace::Linear)
+            .apply(&white)
+            .unwrap();
+        assert_eq!(rgb(srgb.pixels[4]), (128, 128, 128));
+        assert_eq!(rgb(linear.pixels[4]), (188, 188, 188));
+    }
+
+    #[test]
+    fn light_leak_and_diff_heatmap_honor_the_composite_space() {
+        let gray = solid(8, 8, Pixel::new(60, 60, 60));
+        let leak = |space| {
+            LightLeak::new(Pixel::new(255, 120, 0), LeakPosition::TopLeft, 0.8, 3)
+                .with_space(space)
+                .apply(&gray)
+                .unwrap()
+        };
+        let (srgb, linear) = (leak(CompositeSpace::Srgb), leak(CompositeSpace::Linear));
+        // Partially lit pixels come out brighter when mixed in linear light
+        assert!(linear.pixels[9].r > srgb.pixels[9].r);
+        assert_eq!(rgb(linear.pixels[63]), rgb(srgb.pixels[63]));
+
+        let diff = |space| {
+            DiffHeatmap::new(solid(8, 8, Pixel::new(160, 60, 60)), 1.0)
+                .with_space(space)
+                .apply(&gray)
+                .unwrap()
+                .pixels[0]
+        };
+        let (srgb, linear) = (diff(CompositeSpace::Srgb), diff(CompositeSpace::Linear));
+        assert!(
+            linear.r > srgb.r && linear.g > srgb.g,
+            "{:?} {:?}",
+            rgb(srgb),
+            rgb(linear)
+        );
+    }
+
+    #[test]
+    fn zone_overlay_puts_black_white_and_mid_gray_in_zones_0_x_and_v() {
+        let ramp = image(3, 1, |x, _| {
+            let v = [0, 128, 255][x as usize];
+            Pixel::new(v, v, v)
+        });
+        let output = ZoneOverlay::new().apply(&ramp).unwrap();
+
+        let zone = |i: usize| rgb(Pixel::from_hex(ZONE_COLORS[i]));
+        assert_eq!(rgb(output.pixels[0]), zone(0));
+        assert_eq!(rgb(output.pixels[1]), zone(5));
+        assert_eq!(rgb(output.pixels[2]), zone(10));
+    }
+
+    #[test]
+    fn zone_overlay_steps_through_every_zone_in_order() {
+        let ramp = image(256, 1, |x, _| Pixel::new(x as u8, x as u8, x as u8));
+        let output = ZoneOverlay::new().apply(&ramp).unwrap();
+
+        let mut zones: Vec<usize> = output
+            .pixels
+            .iter()
+            .map(|&p| {
+                (0..11)
+                    .position(|z| rgb(Pixel::from_hex(ZONE_COLORS[z])) == rgb(p))
+                    .unwrap()
+            })
+            .collect();
+        assert!(zones.windows(2).all(|w| w[0] <= w[1]));
+        zones.dedup();
+        assert_eq!(zones, (0..11).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn depth_blur_keeps_the_focal_plane_sharp() {
+        // Left half at the focus depth, right half far behind it
+        let stripes = image(16, 8, |x, _| {
+            if x % 2 == 0 {
+                Pixel::new(255, 255, 255)
+            } else {
+                Pixel::new(0, 0, 0)
+            }
+        });
+        let depth = image(16, 8, |x, _| {
+            if x < 8 {
+                Pixel::new(128, 128, 128)
+            } else {
+                Pixel::new(255, 255, 255)
+            }
+        });
+        let output = DepthBlur::new(depth, 128.0 / 255.0, 6)
+            .apply(&stripes)
+            .unwrap();
+
+        for y in 0..8 {
+            for x in 0..16 {
+                let index = (y * 16 + x) as usize;
+                let (before, after) = (stripes.pixels[index].r, output.pixels[index].r);
+                if x < 8 {
+                    assert_eq!(after, before, "({}, {}) blurred", x, y);
+                } else if x >= 10 {
+                    assert!(
+                        (60..=195).contains(&after),
+                        "({}, {}) sharp: {}",
+                        x,
+                        y,
+                        after
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn depth_blur_rejects_a_mismatched_depth_map() {
+        let depth = solid(4, 5, Pixel::new(0, 0, 0));
+        assert!(DepthBlur::new(depth, 0.5, 3).apply(&noise(4, 4)).is_err());
+        let depth = solid(4, 4, Pixel::new(0, 0, 0));
+        assert!(DepthBlur::new(depth, 1.5, 3).apply(&noise(4, 4)).is_err());
+    }
+
+    #[test]
+    fn displacement_map_at_neutral_gray_is_identity() {
+        let source = noise(9, 7);
+        let neutral = solid(9, 7, Pixel::new(128, 128, 128));
+        let output = DisplacementMap::new(neutral, 10.0).apply(&source).unwrap();
+        assert!(
+            source
+                .pixels
+                .iter()
+                .zip(&output.pixels)
+                .all(|(&a, &b)| rgb(a) == rgb(b))
+        );
+
+        // Supersampling a linear ramp averages symmetric samples back to the
+        // center value
+        let ramp = image(9, 7, |x, y| Pixel::new((x * 20) as u8, (y * 30) as u8, 0));
+        let neutral = solid(9, 7, Pixel::new(128, 128, 128));
+        let output = DisplacementMap::new(neutral, 10.0)
+            .with_supersample(4)
+            .apply(&ramp)
+            .unwrap();
+        for y in 1..6 {
+            for x in 1..8 {
+                // Each sub-sample is rounded to 8 bits before averaging
+                let (a, b) = (
+                    output.pixels[(y * 9 + x) as usize],
+                    ramp.pixels[(y * 9 + x) as usize],
+                );
+                assert!((a.r as i32 - b.r as i32).abs() <= 1, "({}, {})", x, y);
+                assert!((a.g as i32 - b.g as i32).abs() <= 1, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn displacement_map_shifts_by_the_map_value() {
+        let ramp = image(12, 4, |x, _| Pixel::new((x * 20) as u8, 0, 0));
+        // Red 192 is half of full scale: a 2 pixel shift to the right
+        let map = solid(12, 4, Pixel::new(192, 128, 0));
+
+        for supersample in [1, 2] {
+            let output = DisplacementMap::new(image(12, 4, |_, _| Pixel::new(192, 128, 0)), 4.0)
+                .with_supersample(supersample)
+                .apply(&ramp)
+                .unwrap();
+            for x in 1..9 {
+                assert_eq!(output.pixels[(12 + x) as usize].r, ((x + 2) * 20) as u8);
+            }
+        }
+
+        assert!(
+            DisplacementMap::new(map, 4.0)
+                .with_supersample(3)
+                .apply(&ramp)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn shape_vignette_keeps_the_inside_and_darkens_corners_fully() {
+        let gray = solid(40, 30, Pixel::new(200, 200, 200));
+        let at = |img: &Image, x: i32, y: i32| img.pixels[(y * 40 + x) as usize].r;
+
+        for shape in [
+            VignetteShape::Circle,
+            VignetteShape::Ellipse,
+            VignetteShape::Rectangle,
+        ] {
+            let output = ShapeVignette::new(shape, 5, 0.5).apply(&gray).unwrap();
+            assert_eq!(at(&output, 20, 15), 200, "{:?} center", shape);
+            for (x, y) in [(0, 0), (39, 0), (0, 29), (39, 29)] {
+                assert_eq!(at(&output, x, y), 100, "{:?} corner ({}, {})", shape, x, y);
+            }
+
+            // Darkening only grows toward the edge
+            let row: Vec<u8> = (0..20).map(|x| at(&output, x, 15)).collect();
+            assert!(
+                row.windows(2).all(|w| w[0] <= w[1]),
+                "{:?} {:?}",
+                shape,
+                row
+            );
+        }
+
+        // (8, 8) is inside the rectangle but outside the circle
+        let rect = ShapeVignette::new(VignetteShape::Rectangle, 5, 0.5)
+            .apply(&gray)
+            .unwrap();
+        let circle = ShapeVignette::new(VignetteShape::Circle, 5, 0.5)
+            .apply(&gray)
+            .unwrap();
+        assert_eq!(at(&rect, 8, 8), 200);
+        assert!(at(&circle, 8, 8) < 200);
+    }
+
+    #[test]
+    fn shape_vignette_rejects_bad_feather_or_darkness() {
+        let gray = solid(4, 4, Pixel::new(200, 200, 200));
+        assert!(
+            ShapeVignette::new(VignetteShape::Circle, -1, 0.5)
+                .apply(&gray)
+                .is_err()
+        );
+        assert!(
+            ShapeVignette::new(VignetteShape::Circle, 2, 1.5)
+                .apply(&gray)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn tilt_shift_keeps_the_band_sharp_and_blurs_the_edges_by_max_blur() {
+        let stripes = image(12, 21, |x, _| {
+            if x % 2 == 0 {
+                Pixel::new(255, 255, 255)
+            } else {
+                Pixel::new(0, 0, 0)
+            }
+        });
+        let output = TiltShift::new(10, 6, 3).apply(&stripes).unwrap();
+        let at = |img: &Image, x: i32, y: i32| img.pixels[(y * 12 + x) as usize].r;
+
+        for y in 7..=13 {
+            for x in 0..12 {
+                assert_eq!(at(&output, x, y), at(&stripes, x, y), "({}, {})", x, y);
+            }
+        }
+
+        // Top and bottom rows get a full 7x7 box blur
+        for y in [0, 20] {
+            for x in 3..9 {
+                let white = (x - 3..=x + 3).filter(|sx| sx % 2 == 0).count() as f32;
+                let expected = (white * 255.0 / 7.0).round() as u8;
+                assert_eq!(at(&output, x, y), expected, "({}, {})", x, y);
+            }
+        }
+
+        // Blur grows with distance from the band
+        let spread = |y: i32| at(&output, 6, y).abs_diff(at(&output, 7, y));
+        assert!(spread(0) < spread(4) && spread(4) < spread(7));
+    }
+
+    #[test]
+    fn tilt_shift_vibrance_saturates_and_bad_focus_is_rejected() {
+        let muted = solid(6, 9, Pixel::new(150, 120, 110));
+        let plain = TiltShift::new(4, 2, 2).apply(&muted).unwrap();
+        let vivid = TiltShift::new(4, 2, 2)
+            .with_vibrance(0.8)
+            .apply(&muted)
+            .unwrap();
+        let spread = |p: Pixel| p.r as i32 - p.b as i32;
+        assert!(spread(vivid.pixels[24]) > spread(plain.pixels[24]));
+
+        assert!(TiltShift::new(9, 2, 2).apply(&muted).is_err());
+        assert!(TiltShift::new(4, -1, 2).apply(&muted).is_err());
     }
 }